@@ -0,0 +1,172 @@
+//! Alias expansion, done on the tokens of a command before it is parsed.
+
+use crate::lexer::{tokenize, Position, Token};
+use crate::parser::{is_keyword, split_assignment};
+use std::collections::BTreeMap;
+
+/// How deep aliases may expand inside one another. Names already being
+/// expanded aren't expanded again, so only a chain of this many different
+/// aliases gets here.
+const MAX_DEPTH: usize = 100;
+
+/// Replaces each word in command position that names an alias with the
+/// tokens of its value. Inside its own expansion an alias is just a word,
+/// so `alias ls='ls -F'` and aliases naming each other stop. A value that
+/// ends in a blank has the word after it checked for an alias too. The
+/// tokens of a value are placed where the alias was. `shell` is the
+/// shell's name, for the warning when they nest too deeply.
+pub fn expand(
+    tokens: Vec<(Token, Position)>,
+    aliases: &BTreeMap<String, String>,
+    shell: &str,
+) -> Vec<(Token, Position)> {
+    let mut expander = Expander {
+        aliases,
+        shell,
+        out: Vec::new(),
+        warned: false,
+    };
+    expander.expand(tokens, &mut Vec::new(), true);
+    expander.out
+}
+
+struct Expander<'a> {
+    aliases: &'a BTreeMap<String, String>,
+    shell: &'a str,
+    out: Vec<(Token, Position)>,
+    /// Whether the too-deep message has been given, so it's given once.
+    warned: bool,
+}
+
+impl Expander<'_> {
+    /// Expands `tokens`, the aliases in `active` being expanded already,
+    /// starting in command position if `command` is set. Returns whether
+    /// the token after them is checked for an alias.
+    fn expand(
+        &mut self,
+        tokens: Vec<(Token, Position)>,
+        active: &mut Vec<String>,
+        mut command: bool,
+    ) -> bool {
+        let mut redirect_target = false;
+        for (token, at) in tokens {
+            let Token::Word(word) = token else {
+                redirect_target = matches!(
+                    token,
+                    Token::Less
+                        | Token::DLess
+                        | Token::DLessDash
+                        | Token::Great
+                        | Token::DGreat
+                        | Token::LessAnd
+                        | Token::GreatAnd
+                        | Token::AndGreat
+                        | Token::AndDGreat
+                );
+                let operand = matches!(token, Token::IoNumber(_) | Token::HereDoc(..));
+                command |= !redirect_target && !operand;
+                self.out.push((token, at));
+                continue;
+            };
+            // `>file ll` still has `ll` in command position
+            if std::mem::take(&mut redirect_target) {
+                self.out.push((Token::Word(word), at));
+                continue;
+            }
+            if let Some(value) = self.aliases.get(&word).filter(|_| command) {
+                if !active.contains(&word) && self.check_depth(&word, active) {
+                    if let Ok(replacement) = tokenize(value) {
+                        let replacement = replacement.into_iter().map(|tok| (tok, at)).collect();
+                        active.push(word);
+                        let after = self.expand(replacement, active, true);
+                        active.pop();
+                        command = after || value.ends_with([' ', '\t']);
+                        continue;
+                    }
+                }
+            }
+            let assignment = split_assignment(&word).is_some();
+            command &= assignment || starts_command(&word);
+            self.out.push((Token::Word(word), at));
+        }
+        command
+    }
+
+    /// Whether `word` may expand inside the aliases in `active`, saying
+    /// why not the first time it may not.
+    fn check_depth(&mut self, word: &str, active: &[String]) -> bool {
+        if active.len() < MAX_DEPTH {
+            return true;
+        }
+        if !self.warned {
+            eprintln!(
+                "{}: {}: alias expansion nested too deeply",
+                self.shell, word
+            );
+            self.warned = true;
+        }
+        false
+    }
+}
+
+/// Whether the word after `word` is a command name, as after `then`.
+fn starts_command(word: &str) -> bool {
+    let takes_words = matches!(word, "[[" | "]]" | "case" | "for" | "function" | "in");
+    is_keyword(word) && !takes_words && !matches!(word, "}" | "done" | "esac" | "fi")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize_with_positions;
+
+    fn expanded(line: &str, aliases: &[(&str, &str)]) -> String {
+        let aliases = aliases
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let tokens = tokenize_with_positions(line, true).unwrap();
+        let tokens = expand(tokens, &aliases, "sh");
+        let words: Vec<&str> = tokens.iter().map(|(tok, _)| tok.describe()).collect();
+        words.join(" ")
+    }
+
+    #[test]
+    fn only_command_words_expand() {
+        let aliases = [("ll", "ls -l"), ("x", "echo")];
+        assert_eq!(
+            expanded("ll x; x ll | ll", &aliases),
+            "ls -l x ; echo ll | ls -l"
+        );
+        assert_eq!(expanded("A=1 ll >x ll", &aliases), "A=1 ls -l > x ll");
+        assert_eq!(
+            expanded("if ll; then x; fi", &aliases),
+            "if ls -l ; then echo ; fi"
+        );
+    }
+
+    #[test]
+    fn a_trailing_blank_expands_the_next_word() {
+        let aliases = [("sudo", "sudo "), ("ll", "ls -l")];
+        assert_eq!(expanded("sudo ll", &aliases), "sudo ls -l");
+    }
+
+    #[test]
+    fn aliases_naming_each_other_stop() {
+        assert_eq!(expanded("ls", &[("ls", "ls -F")]), "ls -F");
+        assert_eq!(expanded("a", &[("a", "b"), ("b", "a")]), "a");
+        assert_eq!(expanded("c", &[("c", "d x"), ("d", "c y")]), "c y x");
+    }
+
+    #[test]
+    fn long_chains_stop_at_the_limit() {
+        let names: Vec<(String, String)> = (0..150)
+            .map(|i| (format!("a{}", i), format!("a{}", i + 1)))
+            .collect();
+        let aliases: Vec<(&str, &str)> = names
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(expanded("a0", &aliases), format!("a{}", MAX_DEPTH));
+    }
+}
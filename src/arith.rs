@@ -0,0 +1,318 @@
+//! Integer arithmetic, as evaluated for `declare -i` variables: C-like
+//! operators on 64-bit integers, with bare names standing for variables.
+
+use crate::parser::is_valid_name;
+use crate::state::ShellState;
+
+/// How deep variables whose values are themselves expressions may nest.
+const MAX_DEPTH: u32 = 32;
+
+pub fn eval(state: &ShellState, expr: &str) -> Result<i64, String> {
+    eval_at(state, expr, 0)
+}
+
+fn eval_at(state: &ShellState, expr: &str, depth: u32) -> Result<i64, String> {
+    if depth > MAX_DEPTH {
+        return Err(format!(
+            "{}: expression recursion level exceeded",
+            expr.trim()
+        ));
+    }
+    let tokens = tokenize(expr)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut parser = Parser {
+        state,
+        tokens,
+        pos: 0,
+        depth,
+        expr,
+    };
+    let value = parser.binary(0)?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(value),
+        Some(tok) => Err(parser.error(&tok.text())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Num(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+impl Tok {
+    fn text(&self) -> String {
+        match self {
+            Tok::Num(n) => n.to_string(),
+            Tok::Name(name) => name.clone(),
+            Tok::Op(op) => op.to_string(),
+        }
+    }
+}
+
+/// Longest first, so `<<` wins over `<`.
+const OPS: &[&str] = &[
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "&", "|",
+    "^", "!", "~", "(", ")", "?", ":",
+];
+
+fn tokenize(expr: &str) -> Result<Vec<Tok>, String> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push(match c.is_ascii_digit() {
+                true => Tok::Num(parse_number(&word)?),
+                false => Tok::Name(word),
+            });
+        } else {
+            let rest: String = chars[i..].iter().take(2).collect();
+            let Some(op) = OPS.iter().find(|op| rest.starts_with(*op)) else {
+                let rest: String = chars[i..].iter().collect();
+                return Err(format!(
+                    "{}: syntax error: invalid arithmetic operator (error token is \"{}\")",
+                    expr.trim(),
+                    rest
+                ));
+            };
+            tokens.push(Tok::Op(op));
+            i += op.len();
+        }
+    }
+    Ok(tokens)
+}
+
+/// Decimal, `0x` hex or leading-zero octal.
+fn parse_number(word: &str) -> Result<i64, String> {
+    let parsed = if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if word.len() > 1 && word.starts_with('0') {
+        i64::from_str_radix(&word[1..], 8)
+    } else {
+        word.parse()
+    };
+    parsed.map_err(|_| {
+        format!(
+            "{}: value too great for base (error token is \"{}\")",
+            word, word
+        )
+    })
+}
+
+struct Parser<'a> {
+    state: &'a ShellState,
+    tokens: Vec<Tok>,
+    pos: usize,
+    depth: u32,
+    expr: &'a str,
+}
+
+/// Binding power of each binary operator; higher binds tighter.
+fn precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "?" => 1,
+        "||" => 2,
+        "&&" => 3,
+        "|" => 4,
+        "^" => 5,
+        "&" => 6,
+        "==" | "!=" => 7,
+        "<" | "<=" | ">" | ">=" => 8,
+        "<<" | ">>" => 9,
+        "+" | "-" => 10,
+        "*" | "/" | "%" => 11,
+        _ => return None,
+    })
+}
+
+impl Parser<'_> {
+    fn error(&self, token: &str) -> String {
+        format!(
+            "{}: syntax error in expression (error token is \"{}\")",
+            self.expr.trim(),
+            token
+        )
+    }
+
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Tok::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.peek_op() {
+            Some(found) if found == op => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.error(&self.tokens.get(self.pos).map_or(String::new(), Tok::text))),
+        }
+    }
+
+    /// Parses operators binding at least as tightly as `min`.
+    fn binary(&mut self, min: u8) -> Result<i64, String> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.peek_op() {
+            let Some(prec) = precedence(op) else {
+                break;
+            };
+            if prec < min {
+                break;
+            }
+            self.pos += 1;
+            if op == "?" {
+                let then = self.binary(0)?;
+                self.expect(":")?;
+                let otherwise = self.binary(prec)?;
+                lhs = if lhs != 0 { then } else { otherwise };
+                continue;
+            }
+            let rhs = self.binary(prec + 1)?;
+            lhs = apply(op, lhs, rhs).ok_or_else(|| {
+                format!(
+                    "{}: division by 0 (error token is \"{}\")",
+                    self.expr.trim(),
+                    rhs
+                )
+            })?;
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        match self.tokens.get(self.pos).cloned() {
+            Some(Tok::Op(op @ ("-" | "+" | "!" | "~"))) => {
+                self.pos += 1;
+                let value = self.unary()?;
+                Ok(match op {
+                    "-" => value.wrapping_neg(),
+                    "+" => value,
+                    "!" => i64::from(value == 0),
+                    _ => !value,
+                })
+            }
+            Some(Tok::Op("(")) => {
+                self.pos += 1;
+                let value = self.binary(0)?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Some(Tok::Num(n)) => {
+                self.pos += 1;
+                Ok(n)
+            }
+            Some(Tok::Name(name)) if is_valid_name(&name) => {
+                self.pos += 1;
+                match self.state.get_var(&name) {
+                    None => Ok(0),
+                    Some(value) => eval_at(self.state, value, self.depth + 1),
+                }
+            }
+            Some(tok) => Err(self.error(&tok.text())),
+            None => Err(format!(
+                "{}: syntax error: operand expected",
+                self.expr.trim()
+            )),
+        }
+    }
+}
+
+/// `None` for division by zero.
+fn apply(op: &str, a: i64, b: i64) -> Option<i64> {
+    Some(match op {
+        "+" => a.wrapping_add(b),
+        "-" => a.wrapping_sub(b),
+        "*" => a.wrapping_mul(b),
+        "/" | "%" if b == 0 => return None,
+        "/" => a.wrapping_div(b),
+        "%" => a.wrapping_rem(b),
+        "<<" => a.wrapping_shl(b as u32),
+        ">>" => a.wrapping_shr(b as u32),
+        "<" => i64::from(a < b),
+        "<=" => i64::from(a <= b),
+        ">" => i64::from(a > b),
+        ">=" => i64::from(a >= b),
+        "==" => i64::from(a == b),
+        "!=" => i64::from(a != b),
+        "&" => a & b,
+        "^" => a ^ b,
+        "|" => a | b,
+        "&&" => i64::from(a != 0 && b != 0),
+        "||" => i64::from(a != 0 || b != 0),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(expr: &str) -> i64 {
+        eval(&ShellState::default(), expr).unwrap()
+    }
+
+    #[test]
+    fn follows_c_precedence() {
+        assert_eq!(value("1 + 2 * 3"), 7);
+        assert_eq!(value("(1 + 2) * 3"), 9);
+        assert_eq!(value("10 - 4 - 3"), 3);
+        assert_eq!(value("2 + 3 == 5 && 1 < 2"), 1);
+        assert_eq!(value("1 << 4 | 1"), 17);
+        assert_eq!(value("-7 / 2"), -3);
+        assert_eq!(value("-7 % 3"), -1);
+        assert_eq!(value("!0 + ~0"), 0);
+    }
+
+    #[test]
+    fn evaluates_the_ternary_operator() {
+        assert_eq!(value("1 ? 2 : 3"), 2);
+        assert_eq!(value("0 ? 2 : 0 ? 3 : 4"), 4);
+    }
+
+    #[test]
+    fn reads_hex_and_octal() {
+        assert_eq!(value("0x1f + 010"), 39);
+        assert_eq!(value(""), 0);
+    }
+
+    #[test]
+    fn names_stand_for_their_values_as_expressions() {
+        let mut state = ShellState::default();
+        state.set_var("a", "3".to_string());
+        state.set_var("b", "a * 2".to_string());
+        assert_eq!(eval(&state, "b + unset"), Ok(6));
+        state.set_var("loop", "loop".to_string());
+        assert!(eval(&state, "loop")
+            .unwrap_err()
+            .contains("recursion level exceeded"));
+    }
+
+    #[test]
+    fn reports_errors() {
+        let state = ShellState::default();
+        assert_eq!(
+            eval(&state, "1/0"),
+            Err("1/0: division by 0 (error token is \"0\")".into())
+        );
+        assert_eq!(
+            eval(&state, "2 +"),
+            Err("2 +: syntax error: operand expected".into())
+        );
+        assert!(eval(&state, "09").is_err());
+        assert!(eval(&state, "1 2").is_err());
+    }
+}
@@ -0,0 +1,2530 @@
+use crate::exec;
+use crate::parser::{is_keyword, is_valid_name, split_assignment};
+use crate::path::{executable_path, executables_named};
+use crate::redirect::{describe, FdTable};
+use crate::state::{Array, Flow, ShellState, Variable};
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::iter::Peekable;
+use std::path::{Component, Path, PathBuf};
+use std::str::Chars;
+
+pub type Builtin = fn(&mut ShellState, &[String], &FdTable) -> i32;
+
+const BUILTINS: &[(&str, Builtin)] = &[
+    ("exit", exit),
+    (":", colon),
+    ("echo", echo),
+    ("printf", printf),
+    ("type", type_),
+    ("command", command),
+    ("pwd", pwd),
+    ("cd", cd),
+    ("break", break_),
+    ("continue", continue_),
+    ("return", return_),
+    ("read", read),
+    ("mapfile", mapfile),
+    ("readarray", mapfile),
+    ("getopts", getopts),
+    ("set", set),
+    ("shopt", shopt),
+    ("history", history),
+    ("alias", alias),
+    ("unalias", unalias),
+    (".", source),
+    ("source", source),
+    ("declare", declare),
+    ("export", export),
+    ("readonly", readonly),
+    ("unset", unset),
+    ("help", help),
+    #[cfg(unix)]
+    ("jobs", jobs),
+    #[cfg(unix)]
+    ("fg", fg),
+    #[cfg(unix)]
+    ("bg", bg),
+    #[cfg(unix)]
+    ("kill", kill),
+    #[cfg(unix)]
+    ("wait", wait),
+    #[cfg(unix)]
+    ("trap", trap),
+];
+
+/// What `help` says about each builtin: its synopsis and what it does.
+const HELP: &[(&str, &str, &str)] = &[
+    (
+        ":",
+        ": [arg ...]",
+        "Do nothing, successfully, after expanding the arguments.",
+    ),
+    (".", ". file", "Run the commands in a file in this shell."),
+    (
+        "alias",
+        "alias [-p] [name[=value] ...]",
+        "Define or print aliases.",
+    ),
+    (
+        "bg",
+        "bg [job ...]",
+        "Continue stopped jobs in the background.",
+    ),
+    (
+        "break",
+        "break [n]",
+        "Leave the innermost n enclosing loops.",
+    ),
+    ("cd", "cd [-L|-P] [dir|-]", "Change the working directory."),
+    (
+        "command",
+        "command [-vV] name [arg ...]",
+        "Run a command, skipping aliases, or describe it.",
+    ),
+    (
+        "continue",
+        "continue [n]",
+        "Go on with the next iteration of the nth enclosing loop.",
+    ),
+    (
+        "declare",
+        "declare [-p] [-aAirx|+irx] [name[=value] ...]",
+        "Set variables and attributes.",
+    ),
+    (
+        "echo",
+        "echo [-neE] [arg ...]",
+        "Write the arguments to standard output.",
+    ),
+    ("exit", "exit [n]", "Leave the shell."),
+    (
+        "export",
+        "export [-n] [name[=value] ...]",
+        "Mark variables for the environment.",
+    ),
+    ("fg", "fg [job]", "Continue a job in the foreground."),
+    (
+        "getopts",
+        "getopts optstring name [arg ...]",
+        "Parse options from the arguments.",
+    ),
+    ("help", "help [-s] [pattern ...]", "Describe the builtins."),
+    (
+        "history",
+        "history [-c] [n]",
+        "List or clear the command history.",
+    ),
+    ("jobs", "jobs", "List the background and stopped jobs."),
+    (
+        "kill",
+        "kill [-s sig | -n num | -sig] pid|job ... or kill -l [sig]",
+        "Send a signal.",
+    ),
+    (
+        "mapfile",
+        "mapfile [-t] [-n count] [-O origin] [-s count] [-u fd] [-C callback] [-c quantum] \
+                 [array]",
+        "Read lines into an array.",
+    ),
+    (
+        "printf",
+        "printf format [arg ...]",
+        "Write the arguments as the format says.",
+    ),
+    ("pwd", "pwd [-LP]", "Print the working directory."),
+    (
+        "read",
+        "read [-r] [-a array] [name ...]",
+        "Read a line and split it into variables.",
+    ),
+    (
+        "readarray",
+        "readarray [-t] [-n count] [-O origin] [-s count] [-u fd] [-C callback] \
+                   [-c quantum] [array]",
+        "Read lines into an array.",
+    ),
+    (
+        "readonly",
+        "readonly [name[=value] ...]",
+        "Make variables unchangeable.",
+    ),
+    ("return", "return [n]", "Leave the function being run."),
+    (
+        "set",
+        "set [-eH|+eH] [-o name|+o name] [--] [arg ...]",
+        "Set options and arguments.",
+    ),
+    (
+        "shopt",
+        "shopt [-s|-u] [-pq] [name ...]",
+        "Set, unset or print shell options.",
+    ),
+    (
+        "source",
+        "source file",
+        "Run the commands in a file in this shell.",
+    ),
+    (
+        "trap",
+        "trap [-lp] [[action] signal ...]",
+        "Run a command when a signal arrives.",
+    ),
+    (
+        "type",
+        "type [-at] name ...",
+        "Say what each name would run.",
+    ),
+    ("unalias", "unalias [-a] name ...", "Remove aliases."),
+    (
+        "unset",
+        "unset [-v] name ...",
+        "Remove variables or array elements.",
+    ),
+    (
+        "wait",
+        "wait [-n] [pid|job ...]",
+        "Wait for jobs to finish.",
+    ),
+];
+
+pub fn find(name: &str) -> Option<Builtin> {
+    BUILTINS.iter().find(|(n, _)| *n == name).map(|(_, f)| *f)
+}
+
+pub fn names() -> impl Iterator<Item = &'static str> {
+    BUILTINS.iter().map(|(name, _)| *name)
+}
+
+/// `exit [n]`: leaves the shell with status `n`, by default that of the
+/// last command, `$?`, even inside a loop or `if`. An `n` that isn't a
+/// number is reported and leaves with 2. An interactive shell with jobs
+/// left warns about them instead, and only a second `exit` straight after
+/// leaves.
+fn exit(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    use crate::jobs::JobStatus;
+    if state.interactive && !state.exit_warned {
+        let stopped = |job: &&crate::jobs::Job| matches!(job.status, JobStatus::Stopped(_));
+        let warning = match state.jobs.iter().find(stopped) {
+            Some(_) => Some("stopped"),
+            None => {
+                let running = state
+                    .jobs
+                    .iter()
+                    .any(|job| job.status == JobStatus::Running);
+                running.then_some("running")
+            }
+        };
+        if let Some(kind) = warning {
+            let _ = writeln!(fds.stderr(), "There are {} jobs.", kind);
+            state.exit_warned = true;
+            return 1;
+        }
+    }
+    let code = match args.first() {
+        None => state.last_status,
+        Some(arg) => match arg.parse::<i32>() {
+            Ok(code) => code,
+            Err(_) => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: exit: {}: numeric argument required",
+                    state.name,
+                    arg
+                );
+                2
+            }
+        },
+    };
+    state.flow = Some(Flow::Exit(code));
+    code
+}
+
+/// `: [arg...]` does nothing, successfully; its arguments are still
+/// expanded, for the side effects of things like `${VAR:=default}`.
+fn colon(_state: &mut ShellState, _args: &[String], _fds: &FdTable) -> i32 {
+    0
+}
+
+/// `help [-s] [pattern...]`: describes the builtins whose names match any
+/// of the glob patterns, or lists the synopses of all of them without
+/// patterns. `-s` gives just the synopses.
+fn help(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let short = args.first().is_some_and(|arg| arg == "-s");
+    let patterns = &args[short as usize..];
+    let topics = HELP.iter().filter(|(name, _, _)| find(name).is_some());
+    let mut out = fds.buffered_stdout();
+    if patterns.is_empty() {
+        for (_, synopsis, _) in topics {
+            let _ = writeln!(out, "{}", synopsis);
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for pattern in patterns {
+        let mut matched = false;
+        for (name, synopsis, summary) in topics.clone() {
+            if !crate::glob::glob_match(pattern, name) {
+                continue;
+            }
+            matched = true;
+            let _ = match short {
+                true => writeln!(out, "{}: {}", name, synopsis),
+                false => writeln!(out, "{}: {}\n    {}", name, synopsis, summary),
+            };
+        }
+        if !matched {
+            let _ = out.flush();
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: help: no help topics match `{}'",
+                state.name,
+                pattern
+            );
+            status = 1;
+        }
+    }
+    status
+}
+
+/// `echo [-neE] [arg...]`: prints the arguments separated by spaces, then
+/// a newline unless `-n` is given. No arguments prints an empty line. `-e`
+/// turns on backslash escapes (see [`echo_escapes`]) and `-E` off again.
+/// In POSIX mode there are no options and every argument is printed.
+fn echo(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let posix = state.options.posix;
+    let is_flag = |arg: &&String| match arg.strip_prefix('-') {
+        Some(flags) if !posix => {
+            !flags.is_empty() && flags.bytes().all(|b| matches!(b, b'n' | b'e' | b'E'))
+        }
+        _ => false,
+    };
+    let flags: String = args
+        .iter()
+        .take_while(is_flag)
+        .map(|arg| &arg[1..])
+        .collect();
+    let mut newline = !flags.contains('n');
+    let escapes = flags.rfind('e') > flags.rfind('E');
+    let text = args[args.iter().take_while(is_flag).count()..].join(" ");
+    let mut out = Vec::new();
+    if escapes {
+        // `\c` stops all output, the newline included
+        newline &= echo_escapes(&text, &mut out);
+    } else {
+        out.extend_from_slice(text.as_bytes());
+    }
+    if newline {
+        out.push(b'\n');
+    }
+    match fds.stdout().write_all(&out) {
+        Ok(()) => 0,
+        Err(_) => 1,
+    }
+}
+
+/// Appends `text` to `out` with `echo -e` escapes replaced: `\a \b \e \f
+/// \n \r \t \v \\`, `\0nnn` octal, `\xHH` hex bytes, and `\uHHHH` and
+/// `\UHHHHHHHH` characters, where a code point that isn't a valid character
+/// comes out as U+FFFD. Returns false if it stopped at a `\c`.
+fn echo_escapes(text: &str, out: &mut Vec<u8>) -> bool {
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let next = match c {
+            '\\' => chars.next(),
+            _ => {
+                out.extend_from_slice(c.to_string().as_bytes());
+                continue;
+            }
+        };
+        let byte = match next {
+            Some('a') => 0x07,
+            Some('b') => 0x08,
+            Some('e' | 'E') => 0x1b,
+            Some('f') => 0x0c,
+            Some('n') => b'\n',
+            Some('r') => b'\r',
+            Some('t') => b'\t',
+            Some('v') => 0x0b,
+            Some('\\') => b'\\',
+            Some('c') => return false,
+            Some('0') => digits(&mut chars, 8, 3).unwrap_or(0) as u8,
+            Some(kind @ ('x' | 'u' | 'U')) => {
+                let max = match kind {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                match (kind, digits(&mut chars, 16, max)) {
+                    (_, None) => out.extend_from_slice(format!("\\{}", kind).as_bytes()),
+                    ('x', Some(value)) => out.push(value as u8),
+                    (_, Some(value)) => {
+                        let c = char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER);
+                        out.extend_from_slice(c.to_string().as_bytes());
+                    }
+                }
+                continue;
+            }
+            // not an escape: keep the backslash
+            Some(other) => {
+                out.extend_from_slice(format!("\\{}", other).as_bytes());
+                continue;
+            }
+            None => b'\\',
+        };
+        out.push(byte);
+    }
+    true
+}
+
+/// Consumes up to `max` digits in `radix`, returning their value, or
+/// `None` if there were none.
+fn digits(chars: &mut Peekable<Chars>, radix: u32, max: usize) -> Option<u32> {
+    let mut value = None;
+    for _ in 0..max {
+        let Some(d) = chars.peek().and_then(|c| c.to_digit(radix)) else {
+            break;
+        };
+        value = Some(value.unwrap_or(0u32).wrapping_mul(radix).wrapping_add(d));
+        chars.next();
+    }
+    value
+}
+
+/// `printf format [arg...]`: prints `format` with its `echo -e` escapes
+/// replaced and each conversion filled in from the next argument, going
+/// through `format` again while arguments are left. Conversions are `%s`,
+/// `%b` (the argument with escapes replaced), `%q` (the argument quoted for
+/// reuse as input), `%c`, `%d`/`%i`, `%u`, `%o`, `%x`/`%X` and `%%`, with
+/// the usual flags, width and precision.
+fn printf(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let args = match args.first() {
+        Some(arg) if arg == "--" => &args[1..],
+        _ => args,
+    };
+    let Some((format, args)) = args.split_first() else {
+        let _ = writeln!(
+            fds.stderr(),
+            "{}: printf: usage: printf format [arguments]",
+            state.name
+        );
+        return 2;
+    };
+    let mut printer = Printer {
+        shell: &state.name,
+        args,
+        next: 0,
+        out: Vec::new(),
+        status: 0,
+    };
+    loop {
+        let before = printer.next;
+        if !printer.print(format, fds) || printer.next == before || printer.next >= args.len() {
+            break;
+        }
+    }
+    match fds.stdout().write_all(&printer.out) {
+        Ok(()) => printer.status,
+        Err(_) => 1,
+    }
+}
+
+struct Printer<'a> {
+    /// The shell's name, for error messages.
+    shell: &'a str,
+    args: &'a [String],
+    next: usize,
+    out: Vec<u8>,
+    status: i32,
+}
+
+impl Printer<'_> {
+    /// Prints `format` once. Returns false if output has to stop, at a
+    /// `\c` or a bad conversion.
+    fn print(&mut self, format: &str, fds: &FdTable) -> bool {
+        let mut rest = format;
+        while let Some(percent) = rest.find('%') {
+            if !echo_escapes(&rest[..percent], &mut self.out) {
+                return false;
+            }
+            let spec = &rest[percent + 1..];
+            let flags = spec.len() - spec.trim_start_matches(['-', '+', ' ', '0', '#']).len();
+            let width_len = number_len(&spec[flags..]);
+            let mut end = flags + width_len;
+            let mut precision = None;
+            if spec[end..].starts_with('.') {
+                let len = number_len(&spec[end + 1..]);
+                precision = Some(self.number(&spec[end + 1..end + 1 + len]));
+                end += 1 + len;
+            }
+            let Some(conversion) = spec[end..].chars().next() else {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: printf: `%': missing format character",
+                    self.shell
+                );
+                self.status = 1;
+                return false;
+            };
+            let width = self.number(&spec[flags..flags + width_len]);
+            let conv = Conversion {
+                flags: &spec[..flags],
+                width,
+                precision,
+            };
+            if !self.convert(&conv, conversion, fds) {
+                return false;
+            }
+            rest = &spec[end + conversion.len_utf8()..];
+        }
+        echo_escapes(rest, &mut self.out)
+    }
+
+    /// A width or precision, where `*` takes it from the next argument.
+    fn number(&mut self, text: &str) -> usize {
+        match text {
+            "*" => {
+                let arg = self.take();
+                usize::try_from(parse_printf_int(&arg).unwrap_or(0)).unwrap_or(0)
+            }
+            _ => text.parse().unwrap_or(0),
+        }
+    }
+
+    /// The next argument, or an empty one once they run out.
+    fn take(&mut self) -> String {
+        let arg = self.args.get(self.next).cloned().unwrap_or_default();
+        self.next += 1;
+        arg
+    }
+
+    fn convert(&mut self, conv: &Conversion, conversion: char, fds: &FdTable) -> bool {
+        let text = match conversion {
+            '%' => "%".to_string(),
+            's' | 'q' | 'c' => {
+                let arg = self.take();
+                let text = match conversion {
+                    'q' => crate::lexer::quote(&arg),
+                    'c' => arg.chars().take(1).collect(),
+                    _ => arg,
+                };
+                let text = match conv.precision {
+                    Some(max) => text.chars().take(max).collect(),
+                    None => text,
+                };
+                conv.pad(text, false)
+            }
+            'b' => {
+                let mut bytes = Vec::new();
+                let go_on = echo_escapes(&self.take(), &mut bytes);
+                let text = String::from_utf8_lossy(&bytes).into_owned();
+                self.out.extend_from_slice(conv.pad(text, false).as_bytes());
+                return go_on;
+            }
+            'd' | 'i' | 'u' | 'o' | 'x' | 'X' => {
+                let arg = self.take();
+                let value = match parse_printf_int(&arg) {
+                    Some(value) => value,
+                    None => {
+                        let _ = writeln!(
+                            fds.stderr(),
+                            "{}: printf: {}: invalid number",
+                            self.shell,
+                            arg
+                        );
+                        self.status = 1;
+                        0
+                    }
+                };
+                conv.pad(conv.integer(value, conversion), true)
+            }
+            other => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: printf: `{}': invalid format character",
+                    self.shell,
+                    other
+                );
+                self.status = 1;
+                return false;
+            }
+        };
+        self.out.extend_from_slice(text.as_bytes());
+        true
+    }
+}
+
+/// The flags, width and precision of one `printf` conversion.
+struct Conversion<'a> {
+    flags: &'a str,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Conversion<'_> {
+    /// `value` in the base `conversion` asks for, with the sign, prefix and
+    /// minimum digits the flags and precision ask for.
+    fn integer(&self, value: i64, conversion: char) -> String {
+        let digits = match conversion {
+            'u' => (value as u64).to_string(),
+            'o' => format!("{:o}", value),
+            'x' => format!("{:x}", value),
+            'X' => format!("{:X}", value),
+            _ => value.unsigned_abs().to_string(),
+        };
+        let digits = match self.precision {
+            Some(min) if digits.len() < min => format!("{:0>min$}", digits, min = min),
+            _ => digits,
+        };
+        let sign = match conversion {
+            'd' | 'i' if value < 0 => "-",
+            'd' | 'i' if self.flags.contains('+') => "+",
+            'd' | 'i' if self.flags.contains(' ') => " ",
+            _ => "",
+        };
+        let prefix = match (self.flags.contains('#') && value != 0, conversion) {
+            (true, 'o') if !digits.starts_with('0') => "0",
+            (true, 'x') => "0x",
+            (true, 'X') => "0X",
+            _ => "",
+        };
+        format!("{}{}{}", sign, prefix, digits)
+    }
+
+    /// Pads `text` to the width, on the left unless the `-` flag is given,
+    /// and with zeros after any sign for a number with the `0` flag.
+    fn pad(&self, text: String, numeric: bool) -> String {
+        let len = text.chars().count();
+        if len >= self.width {
+            return text;
+        }
+        let fill = self.width - len;
+        if self.flags.contains('-') {
+            return format!("{}{}", text, " ".repeat(fill));
+        }
+        if numeric && self.flags.contains('0') && self.precision.is_none() {
+            let split = text.len() - text.trim_start_matches(['-', '+', ' ']).len();
+            let hex = text[split..].starts_with("0x") || text[split..].starts_with("0X");
+            let (sign, digits) = text.split_at(split + if hex { 2 } else { 0 });
+            return format!("{}{}{}", sign, "0".repeat(fill), digits);
+        }
+        format!("{}{}", " ".repeat(fill), text)
+    }
+}
+
+/// The length of the width or precision at the start of `spec`.
+fn number_len(spec: &str) -> usize {
+    match spec.starts_with('*') {
+        true => 1,
+        false => spec.len() - spec.trim_start_matches(|c: char| c.is_ascii_digit()).len(),
+    }
+}
+
+/// A `printf` integer argument: decimal, `0x` hex or leading-zero octal,
+/// or a quote followed by a character for that character's code. Empty
+/// is 0.
+fn parse_printf_int(arg: &str) -> Option<i64> {
+    let arg = arg.trim_start();
+    if let Some(quoted) = arg.strip_prefix(['\'', '"']) {
+        return Some(quoted.chars().next().map_or(0, |c| c as i64));
+    }
+    let (negative, digits) = match arg.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, arg.strip_prefix('+').unwrap_or(arg)),
+    };
+    let value = if digits.is_empty() {
+        0
+    } else if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+/// What a command name runs, as `type` and `command -v` report it.
+enum Resolved {
+    Alias(String),
+    Keyword,
+    /// A function, with its definition as `type` prints it.
+    Function(String),
+    Builtin,
+    File(PathBuf),
+}
+
+impl Resolved {
+    /// The one word `type -t` prints.
+    fn kind(&self) -> &'static str {
+        match self {
+            Resolved::Alias(_) => "alias",
+            Resolved::Keyword => "keyword",
+            Resolved::Function(_) => "function",
+            Resolved::Builtin => "builtin",
+            Resolved::File(_) => "file",
+        }
+    }
+
+    /// How `type` and `command -V` describe what `name` runs.
+    fn describe(&self, name: &str) -> String {
+        match self {
+            Resolved::Alias(value) => format!("{} is aliased to `{}'", name, value),
+            Resolved::Keyword => format!("{} is a shell keyword", name),
+            Resolved::Function(definition) => format!("{} is a function\n{}", name, definition),
+            Resolved::Builtin => format!("{} is a shell builtin", name),
+            Resolved::File(p) => format!("{} is {}", name, p.display()),
+        }
+    }
+}
+
+fn resolve(state: &ShellState, name: &str) -> Option<Resolved> {
+    if let Some(value) = state.aliases.get(name) {
+        return Some(Resolved::Alias(value.clone()));
+    }
+    if is_keyword(name) {
+        return Some(Resolved::Keyword);
+    }
+    if let Some(body) = state.functions.get(name) {
+        return Some(Resolved::Function(format!("{}() {}", name, body)));
+    }
+    if find(name).is_some() {
+        return Some(Resolved::Builtin);
+    }
+    if name.contains('/') {
+        return executable_path(Path::new(name), state.search_var("PATHEXT")).map(Resolved::File);
+    }
+    state.find_command(name).map(Resolved::File)
+}
+
+/// Everything `name` could run, in the order the shell tries them: like
+/// [`resolve`], but going on past the first and finding every executable
+/// of that name on `PATH`.
+fn resolve_all(state: &ShellState, name: &str) -> Vec<Resolved> {
+    let mut found = Vec::new();
+    if let Some(value) = state.aliases.get(name) {
+        found.push(Resolved::Alias(value.clone()));
+    }
+    if is_keyword(name) {
+        found.push(Resolved::Keyword);
+    }
+    if let Some(body) = state.functions.get(name) {
+        found.push(Resolved::Function(format!("{}() {}", name, body)));
+    }
+    if find(name).is_some() {
+        found.push(Resolved::Builtin);
+    }
+    if name.contains('/') {
+        found.extend(resolve(state, name));
+    } else {
+        let (path, pathext) = (state.search_var("PATH"), state.search_var("PATHEXT"));
+        found.extend(executables_named(name, path, pathext).map(Resolved::File));
+    }
+    found
+}
+
+/// `type [-at] name...`: says what each name would run. `-t` prints just
+/// `alias`, `keyword`, `builtin` or `file`, and nothing for a name that
+/// isn't found. `-a` goes on to everything else the name could run, every
+/// executable of that name on `PATH` included.
+fn type_(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let (mut short, mut all) = (false, false);
+    let mut names = args;
+    while let Some(arg) = names.first() {
+        match arg.strip_prefix('-') {
+            Some("-") => {
+                names = &names[1..];
+                break;
+            }
+            Some(flags) if !flags.is_empty() && flags.chars().all(|c| c == 'a' || c == 't') => {
+                short |= flags.contains('t');
+                all |= flags.contains('a');
+                names = &names[1..];
+            }
+            _ => break,
+        }
+    }
+    let mut out = fds.stdout();
+    let mut status = 0;
+    for name in names {
+        let found = match all {
+            true => resolve_all(state, name),
+            false => resolve(state, name).into_iter().collect(),
+        };
+        if found.is_empty() {
+            if !short {
+                let _ = writeln!(out, "{} not found", name);
+            }
+            status = 1;
+        }
+        for found in found {
+            let _ = match short {
+                true => writeln!(out, "{}", found.kind()),
+                false => writeln!(out, "{}", found.describe(name)),
+            };
+        }
+    }
+    status
+}
+
+/// `command name [arg...]` runs a builtin or external command; `-v` prints
+/// what each name would run and `-V` describes it like `type`. Either way
+/// the status is nonzero if a name isn't found.
+fn command(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let (verbose, names) = match args.first().map(|s| s.as_str()) {
+        Some("-v") => (false, &args[1..]),
+        Some("-V") => (true, &args[1..]),
+        Some("--") => return run_command(state, &args[1..], fds),
+        _ => return run_command(state, args, fds),
+    };
+    let mut out = fds.stdout();
+    let mut status = 0;
+    for name in names {
+        match (resolve(state, name), verbose) {
+            (Some(Resolved::Alias(value)), false) => {
+                let _ = writeln!(out, "alias {}={}", name, single_quote(&value));
+            }
+            (Some(Resolved::Keyword | Resolved::Function(_) | Resolved::Builtin), false) => {
+                let _ = writeln!(out, "{}", name);
+            }
+            (Some(Resolved::File(p)), false) => {
+                let _ = writeln!(out, "{}", p.display());
+            }
+            (Some(found), true) => {
+                let _ = writeln!(out, "{}", found.describe(name));
+            }
+            (None, false) => status = 1,
+            (None, true) => {
+                let _ = writeln!(fds.stderr(), "{}: command: {}: not found", state.name, name);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn run_command(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let Some((name, rest)) = args.split_first() else {
+        return 0;
+    };
+    match find(name) {
+        Some(builtin) => builtin(state, rest, fds),
+        None => exec::run_external(state, name, rest, &[], fds),
+    }
+}
+
+/// `pwd [-LP]`: prints the working directory as `PWD` has it, symlinks
+/// and all, or with `-P` the physical one.
+fn pwd(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let Some(physical) = physical_flag(state, "pwd", args, fds) else {
+        return 2;
+    };
+    let logical = logical_pwd(state).filter(|_| !physical);
+    match logical.map_or_else(env::current_dir, Ok) {
+        Ok(dir) => {
+            let _ = writeln!(fds.stdout(), "{}", dir.display());
+            0
+        }
+        Err(e) => {
+            let _ = writeln!(fds.stderr(), "{}: pwd: {}", state.name, e);
+            1
+        }
+    }
+}
+
+/// `cd [-L|-P] [dir|-]`: changes directory, keeping `PWD` and `OLDPWD` up
+/// to date. With no `dir` it goes to `HOME`, and `cd -` goes back to
+/// `OLDPWD` and prints where it went. By default the new `PWD` is worked
+/// out from the old one, so a symlink stays in it and `..` goes back out
+/// of the link; `-P` resolves symlinks instead.
+fn cd(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let Some(physical) = physical_flag(state, "cd", args, fds) else {
+        return 2;
+    };
+    let args = &args[args.iter().take_while(|arg| is_option(arg)).count()..];
+    let back = args.first().is_some_and(|arg| arg == "-");
+    let target = if back {
+        match state.get_var("OLDPWD") {
+            Some(dir) if !dir.is_empty() => dir.to_string(),
+            _ => {
+                let _ = writeln!(fds.stderr(), "{}: cd: OLDPWD not set", state.name);
+                return 1;
+            }
+        }
+    } else if let Some(dir) = args.first() {
+        dir.clone()
+    } else {
+        match state.home() {
+            Some(home) => home,
+            None => {
+                let _ = writeln!(fds.stderr(), "{}: cd: HOME not set", state.name);
+                return 1;
+            }
+        }
+    };
+    let old = logical_pwd(state).or_else(|| env::current_dir().ok());
+    let logical = match (physical, &old) {
+        (false, Some(old)) => Some(normalize(&old.join(&target))),
+        _ => None,
+    };
+    // a `..` after a symlink can name a directory that isn't there; bash
+    // then falls back to the path as given
+    let entered = match &logical {
+        Some(dir) if env::set_current_dir(dir).is_ok() => logical.clone(),
+        _ => match env::set_current_dir(Path::new(&target)) {
+            Ok(()) => None,
+            // on failure the working directory is left untouched
+            Err(e) => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: cd: {}: {}",
+                    state.name,
+                    target,
+                    describe(&e)
+                );
+                return 1;
+            }
+        },
+    };
+    if let Some(old) = old {
+        state.set_var("OLDPWD", old.display().to_string());
+    }
+    if let Some(dir) = entered.or_else(|| env::current_dir().ok()) {
+        state.set_var("PWD", dir.display().to_string());
+    }
+    if back {
+        let _ = writeln!(fds.stdout(), "{}", state.get_var("PWD").unwrap_or(&target));
+    }
+    0
+}
+
+fn is_option(arg: &str) -> bool {
+    arg.len() > 1 && arg.starts_with('-')
+}
+
+/// Reads the `-L` and `-P` options of `cd` and `pwd`, the last one
+/// winning: whether `-P` is in effect, or `None` after reporting a bad one.
+fn physical_flag(state: &ShellState, name: &str, args: &[String], fds: &FdTable) -> Option<bool> {
+    let mut physical = false;
+    for arg in args.iter().take_while(|arg| is_option(arg)) {
+        for flag in arg[1..].chars() {
+            match flag {
+                'L' => physical = false,
+                'P' => physical = true,
+                _ => {
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: {}: -{}: invalid option",
+                        state.name,
+                        name,
+                        flag
+                    );
+                    return None;
+                }
+            }
+        }
+    }
+    Some(physical)
+}
+
+/// `PWD`, as long as it still names the working directory.
+fn logical_pwd(state: &ShellState) -> Option<PathBuf> {
+    let pwd = Path::new(state.get_var("PWD")?);
+    let same = pwd.is_absolute() && fs::canonicalize(pwd).ok() == env::current_dir().ok();
+    same.then(|| pwd.to_path_buf())
+}
+
+/// Drops `.` components and takes `..` back over the one before it,
+/// without looking at the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn break_(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    match loop_count(state, "break", args, fds) {
+        Some(n) => {
+            state.flow = Some(Flow::Break(n));
+            0
+        }
+        None => 1,
+    }
+}
+
+fn continue_(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    match loop_count(state, "continue", args, fds) {
+        Some(n) => {
+            state.flow = Some(Flow::Continue(n));
+            0
+        }
+        None => 1,
+    }
+}
+
+/// `return [n]`: leaves the function being run with status `n`, by
+/// default that of the last command. An `n` that isn't a number is
+/// reported and returns 2.
+fn return_(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    if state.function_depth == 0 {
+        let _ = writeln!(
+            fds.stderr(),
+            "{}: return: can only `return' from a function",
+            state.name
+        );
+        return 1;
+    }
+    let code = match args.first() {
+        None => state.last_status,
+        Some(arg) => match arg.parse::<i32>() {
+            Ok(code) => code,
+            Err(_) => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: return: {}: numeric argument required",
+                    state.name,
+                    arg
+                );
+                2
+            }
+        },
+    };
+    state.flow = Some(Flow::Return(code));
+    code
+}
+
+/// Parses the optional loop count of `break`/`continue`, capped at the
+/// number of enclosing loops.
+fn loop_count(state: &ShellState, name: &str, args: &[String], fds: &FdTable) -> Option<u32> {
+    let n = match args.first() {
+        None => 1,
+        Some(arg) => match arg.parse::<u32>() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: {}: {}: loop count out of range",
+                    state.name,
+                    name,
+                    arg
+                );
+                return None;
+            }
+        },
+    };
+    if state.loop_depth == 0 {
+        let _ = writeln!(
+            fds.stderr(),
+            "{}: {}: only meaningful in a `for', `while', or `until' loop",
+            state.name,
+            name
+        );
+        return None;
+    }
+    Some(n.min(state.loop_depth))
+}
+
+/// `read [-r] [-a array] [name...]`: reads a line from stdin and splits it
+/// on `IFS` into the named variables, the last one getting the rest of the
+/// line, or with `-a` into the elements of `array`, replacing any it had.
+/// Unless `-r` is given, a backslash escapes the next character, so it
+/// isn't split on, and a backslash at the end of the line continues it.
+fn read(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let mut raw = false;
+    let mut array = None;
+    let mut names = args;
+    while let Some(arg) = names.first() {
+        if arg == "--" {
+            names = &names[1..];
+            break;
+        }
+        let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            break;
+        };
+        names = &names[1..];
+        for (i, flag) in flags.char_indices() {
+            match flag {
+                'r' => raw = true,
+                // the name is the rest of this word, or else the next one
+                'a' => {
+                    array = match &flags[i + 1..] {
+                        "" => match names.split_first() {
+                            Some((name, after)) => {
+                                names = after;
+                                Some(name.as_str())
+                            }
+                            None => {
+                                let _ = writeln!(
+                                    fds.stderr(),
+                                    "{}: read: -a: option requires an argument",
+                                    state.name
+                                );
+                                return 2;
+                            }
+                        },
+                        name => Some(name),
+                    };
+                    break;
+                }
+                _ => {
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: read: -{}: invalid option",
+                        state.name,
+                        flag
+                    );
+                    return 2;
+                }
+            }
+        }
+    }
+    if let Some(name) = array {
+        if !is_valid_name(name) {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: read: `{}': not a valid identifier",
+                state.name,
+                name
+            );
+            return 1;
+        }
+        if state.var(name).is_some_and(|var| var.readonly) {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: read: {}: readonly variable",
+                state.name,
+                name
+            );
+            return 1;
+        }
+    }
+
+    let mut reader = fds.reader(0);
+    let mut line = String::new();
+    loop {
+        let part = match reader.read_line() {
+            Ok(Some(part)) => part,
+            Ok(None) if line.is_empty() => return 1,
+            Ok(None) => break,
+            Err(e) => {
+                let _ = writeln!(fds.stderr(), "{}: read: {}", state.name, describe(&e));
+                return 1;
+            }
+        };
+        let Some(part) = part.strip_suffix('\n') else {
+            line.push_str(&part);
+            break;
+        };
+        let trailing = part.len() - part.trim_end_matches('\\').len();
+        if raw || trailing % 2 == 0 {
+            line.push_str(part);
+            break;
+        }
+        line.push_str(&part[..part.len() - 1]);
+    }
+    let chars = read_chars(&line, raw);
+    let ifs = state.get_var("IFS").unwrap_or(" \t\n").to_string();
+    let is_ifs = |&(c, escaped): &(char, bool)| !escaped && ifs.contains(c);
+
+    if let Some(name) = array {
+        let elements = chars
+            .split(is_ifs)
+            .filter(|field| !field.is_empty())
+            .map(|field| field.iter().map(|&(c, _)| c).collect())
+            .collect();
+        state.set_array(name, elements);
+        return 0;
+    }
+    let mut fields = Vec::new();
+    if names.is_empty() {
+        fields.push(("REPLY", chars.iter().map(|&(c, _)| c).collect()));
+    } else {
+        let mut rest = &chars[..];
+        for (i, name) in names.iter().enumerate() {
+            let start = rest.iter().position(|c| !is_ifs(c)).unwrap_or(rest.len());
+            rest = &rest[start..];
+            let end = match i == names.len() - 1 {
+                true => rest
+                    .iter()
+                    .rposition(|c| !is_ifs(c))
+                    .map_or(0, |end| end + 1),
+                false => rest.iter().position(is_ifs).unwrap_or(rest.len()),
+            };
+            fields.push((name.as_str(), rest[..end].iter().map(|&(c, _)| c).collect()));
+            rest = &rest[end..];
+        }
+    }
+    for (name, value) in fields {
+        if let Err(e) = state.assign(name, value) {
+            let _ = writeln!(fds.stderr(), "{}: read: {}", state.name, e);
+            return 1;
+        }
+    }
+    0
+}
+
+/// The characters of a line `read` got, each with whether a backslash
+/// escaped it; the backslashes themselves are dropped unless `raw`.
+fn read_chars(line: &str, raw: bool) -> Vec<(char, bool)> {
+    let mut chars = Vec::new();
+    let mut iter = line.chars();
+    while let Some(c) = iter.next() {
+        match c {
+            '\\' if !raw => {
+                if let Some(next) = iter.next() {
+                    chars.push((next, true));
+                }
+            }
+            c => chars.push((c, false)),
+        }
+    }
+    chars
+}
+
+/// `mapfile [-t] [-n count] [-O origin] [-s count] [-u fd] [-C callback
+/// [-c quantum]] [array]`, or `readarray`: reads lines into `array`,
+/// `MAPFILE` by default. `-t` drops each line's newline, `-n` stops after
+/// `count` lines, `-s` skips the first `count`, and `-O` puts the first
+/// at index `origin` and keeps the elements before it, where otherwise
+/// the array is emptied first. `-C` runs `callback index line` before
+/// every `quantum`th line, 5000 by default, is stored.
+fn mapfile(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let (mut chop, mut fd, mut count, mut origin, mut skip) = (false, 0, 0, None, 0);
+    let (mut callback, mut quantum) = (None, 5000);
+    let mut rest = args;
+    while let Some(arg) = rest.first() {
+        if arg == "--" {
+            rest = &rest[1..];
+            break;
+        }
+        let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            break;
+        };
+        rest = &rest[1..];
+        for (i, flag) in flags.char_indices() {
+            if flag == 't' {
+                chop = true;
+                continue;
+            }
+            if !"nOsuCc".contains(flag) {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: mapfile: -{}: invalid option",
+                    state.name,
+                    flag
+                );
+                return 2;
+            }
+            // the value is the rest of this word, or else the next one
+            let value = match &flags[i + 1..] {
+                "" => match rest.split_first() {
+                    Some((value, after)) => {
+                        rest = after;
+                        value.as_str()
+                    }
+                    None => {
+                        let _ = writeln!(
+                            fds.stderr(),
+                            "{}: mapfile: -{}: option requires an argument",
+                            state.name,
+                            flag
+                        );
+                        return 2;
+                    }
+                },
+                attached => attached,
+            };
+            if flag == 'C' {
+                callback = Some(value.to_string());
+                break;
+            }
+            let Ok(number) = value.parse::<usize>() else {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: mapfile: {}: invalid number",
+                    state.name,
+                    value
+                );
+                return 1;
+            };
+            match flag {
+                'n' => count = number,
+                'O' => origin = Some(number),
+                's' => skip = number,
+                'u' => fd = number as i32,
+                _ if number == 0 => {
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: mapfile: {}: invalid callback quantum",
+                        state.name,
+                        value
+                    );
+                    return 1;
+                }
+                _ => quantum = number,
+            }
+            break;
+        }
+    }
+    let name = rest.first().map_or("MAPFILE", String::as_str);
+    if !is_valid_name(name) {
+        let _ = writeln!(
+            fds.stderr(),
+            "{}: mapfile: `{}': not a valid identifier",
+            state.name,
+            name
+        );
+        return 1;
+    }
+    if state.var(name).is_some_and(|var| var.readonly) {
+        let _ = writeln!(
+            fds.stderr(),
+            "{}: mapfile: {}: readonly variable",
+            state.name,
+            name
+        );
+        return 1;
+    }
+
+    let start = origin.unwrap_or(0);
+    if origin.is_none() {
+        state.set_array(name, Vec::new());
+    }
+    let mut reader = fds.reader(fd);
+    let mut stored = 0;
+    while count == 0 || stored < count {
+        let mut line = match reader.read_line() {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                let _ = writeln!(fds.stderr(), "{}: mapfile: {}", state.name, describe(&e));
+                return 1;
+            }
+        };
+        if skip > 0 {
+            skip -= 1;
+            continue;
+        }
+        if chop && line.ends_with('\n') {
+            line.pop();
+        }
+        stored += 1;
+        if let Some(callback) = callback.as_ref().filter(|_| stored % quantum == 0) {
+            let index = start + stored - 1;
+            let command = format!("{} {} {}", callback, index, single_quote(&line));
+            exec::run_text(state, &command, fds);
+        }
+        state.set_elements(name, start + stored - 1, vec![line]);
+    }
+    0
+}
+
+/// `getopts optstring name [arg...]`: puts the next option from the
+/// arguments in `name`, with its argument in `OPTARG` if `optstring` has a
+/// `:` after it, and the index of the next argument in `OPTIND`. Fails at
+/// the first non-option, after a `--`, or at the end. A bad option or a
+/// missing argument sets `name` to `?` with a message; if `optstring`
+/// starts with `:` there is no message, `OPTARG` gets the option, and a
+/// missing argument sets `name` to `:` instead.
+fn getopts(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let [optstring, name, args @ ..] = args else {
+        let _ = writeln!(
+            fds.stderr(),
+            "{}: getopts: usage: getopts optstring name [arg ...]",
+            state.name
+        );
+        return 2;
+    };
+    if !is_valid_name(name) {
+        let _ = writeln!(
+            fds.stderr(),
+            "{}: getopts: `{}': not a valid identifier",
+            state.name,
+            name
+        );
+        return 1;
+    }
+    let silent = optstring.starts_with(':');
+    let quiet = silent || state.get_var("OPTERR") == Some("0");
+    let mut index = state
+        .get_var("OPTIND")
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(1)
+        .max(1);
+    let (last_index, last_offset) = state.getopts_position;
+    let mut offset = if last_index == index { last_offset } else { 0 };
+
+    let arg = args.get(index - 1).map(String::as_str);
+    let option = match arg {
+        Some("--") if offset == 0 => {
+            index += 1;
+            None
+        }
+        Some(arg) if offset > 0 || (arg.starts_with('-') && arg != "-") => {
+            let start = offset.max(1);
+            arg[start..]
+                .chars()
+                .next()
+                .map(|c| (c, start + c.len_utf8()))
+        }
+        _ => None,
+    };
+    let Some((option, next)) = option else {
+        state.getopts_position = (0, 0);
+        state.set_var("OPTIND", index.to_string());
+        let _ = state.assign(name, "?".to_string());
+        return 1;
+    };
+    let arg = arg.unwrap_or_default();
+    offset = next;
+    if offset >= arg.len() {
+        index += 1;
+        offset = 0;
+    }
+
+    let spec = optstring.find(option).filter(|_| option != ':');
+    let takes_arg = spec.is_some_and(|i| optstring[i + option.len_utf8()..].starts_with(':'));
+    let (value, optarg) = match spec {
+        None => {
+            if !quiet {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: getopts: illegal option -- {}",
+                    state.name,
+                    option
+                );
+            }
+            ("?".to_string(), silent.then(|| option.to_string()))
+        }
+        Some(_) if !takes_arg => (option.to_string(), None),
+        // the rest of this argument, or else the next one
+        Some(_) if offset > 0 => {
+            let rest = arg[offset..].to_string();
+            index += 1;
+            offset = 0;
+            (option.to_string(), Some(rest))
+        }
+        Some(_) => match args.get(index - 1) {
+            Some(next) => {
+                index += 1;
+                (option.to_string(), Some(next.clone()))
+            }
+            None if silent => (":".to_string(), Some(option.to_string())),
+            None => {
+                if !quiet {
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: getopts: option requires an argument -- {}",
+                        state.name,
+                        option
+                    );
+                }
+                ("?".to_string(), None)
+            }
+        },
+    };
+    state.getopts_position = (index, offset);
+    state.set_var("OPTIND", index.to_string());
+    match optarg {
+        Some(optarg) => state.set_var("OPTARG", optarg),
+        None => state.restore_var("OPTARG", None),
+    }
+    if let Err(e) = state.assign(name, value) {
+        let _ = writeln!(fds.stderr(), "{}: getopts: {}", state.name, e);
+        return 1;
+    }
+    0
+}
+
+/// `alias [-p] [name[=value]...]`: defines each `name=value` alias and
+/// prints each named one, or all of them without arguments, as `alias`
+/// commands.
+fn alias(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let args = match args.first() {
+        Some(arg) if arg == "-p" || arg == "--" => &args[1..],
+        _ => args,
+    };
+    if args.is_empty() {
+        let mut out = fds.buffered_stdout();
+        for (name, value) in &state.aliases {
+            let _ = writeln!(out, "alias {}={}", name, single_quote(value));
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, _)) if !is_alias_name(name) => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: alias: `{}': invalid alias name",
+                    state.name,
+                    name
+                );
+                status = 1;
+            }
+            Some((name, value)) => {
+                state.aliases.insert(name.to_string(), value.to_string());
+            }
+            None => match state.aliases.get(arg) {
+                Some(value) => {
+                    let _ = writeln!(fds.stdout(), "alias {}={}", arg, single_quote(value));
+                }
+                None => {
+                    let _ = writeln!(fds.stderr(), "{}: alias: {}: not found", state.name, arg);
+                    status = 1;
+                }
+            },
+        }
+    }
+    status
+}
+
+/// Alias names can't hold anything the lexer would split or treat
+/// specially, so only a plain unquoted word can match one.
+fn is_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || "/$`=\\'\"|&;()<>".contains(c))
+}
+
+/// `unalias [-a] name...`: removes the named aliases, or all with `-a`.
+fn unalias(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    match args.first().map(String::as_str) {
+        None => {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: unalias: usage: unalias [-a] name [name ...]",
+                state.name
+            );
+            return 2;
+        }
+        Some("-a") => {
+            state.aliases.clear();
+            return 0;
+        }
+        _ => {}
+    }
+    let mut status = 0;
+    for name in args {
+        if state.aliases.remove(name).is_none() {
+            let _ = writeln!(fds.stderr(), "{}: unalias: {}: not found", state.name, name);
+            status = 1;
+        }
+    }
+    status
+}
+
+/// `text` in single quotes, as the shell would read it back.
+fn single_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// `. file` or `source file`: runs the commands in `file` in this shell.
+/// A `file` with no `/` that isn't in the working directory is looked
+/// for in `PATH`.
+fn source(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let Some(file) = args.first() else {
+        let _ = writeln!(
+            fds.stderr(),
+            "{}: source: filename argument required",
+            state.name
+        );
+        return 2;
+    };
+    let mut path = PathBuf::from(file);
+    if !file.contains('/') && !path.is_file() {
+        let found = crate::path::file_in_path(file, state.search_var("PATH"));
+        path = found.unwrap_or(path);
+    }
+    exec::run_file(state, &path, fds)
+}
+
+/// `set [-eH|+eH] [-o name|+o name] [--] [arg...]`: turns shell options
+/// on (`-`) or off (`+`). The words after `--`, or after the options,
+/// become the positional parameters.
+fn set(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let on = arg.starts_with('-');
+        let flags = match arg.strip_prefix(['-', '+']) {
+            // a lone `-` leaves them be if nothing follows
+            _ if arg == "--" || arg == "-" => {
+                let rest: Vec<String> = args.cloned().collect();
+                if arg == "--" || !rest.is_empty() {
+                    state.positional = rest;
+                }
+                break;
+            }
+            Some(flags) if !flags.is_empty() => flags,
+            _ => {
+                state.positional = std::iter::once(arg).chain(args).cloned().collect();
+                break;
+            }
+        };
+        for flag in flags.chars() {
+            let name = match flag {
+                'e' => "errexit",
+                'H' => "histexpand",
+                'o' => match args.next() {
+                    Some(name) => name.as_str(),
+                    None => {
+                        let _ = writeln!(
+                            fds.stderr(),
+                            "{}: set: -o: option requires an argument",
+                            state.name
+                        );
+                        return 2;
+                    }
+                },
+                _ => {
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: set: -{}: invalid option",
+                        state.name,
+                        flag
+                    );
+                    return 2;
+                }
+            };
+            // the two editing modes are one option's two sides
+            if name == "emacs" {
+                state.options.vi = !on;
+                continue;
+            }
+            let Some(option) = option_mut(state, name) else {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: set: {}: invalid option name",
+                    state.name,
+                    name
+                );
+                return 1;
+            };
+            *option = on;
+        }
+    }
+    0
+}
+
+fn option_mut<'a>(state: &'a mut ShellState, name: &str) -> Option<&'a mut bool> {
+    match name {
+        "errexit" => Some(&mut state.options.errexit),
+        "histexpand" => Some(&mut state.options.histexpand),
+        "posix" => Some(&mut state.options.posix),
+        "vi" => Some(&mut state.options.vi),
+        _ => None,
+    }
+}
+
+/// `history [-c] [n]`: lists the last `n` history entries, or all of them,
+/// numbered, with the time each was entered first if `HISTTIMEFORMAT` is
+/// set. `-c` clears the history instead.
+fn history(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let count = match args.first().map(String::as_str) {
+        None => None,
+        Some("-c") => {
+            state.history.clear();
+            return 0;
+        }
+        Some(arg) => match arg.parse::<usize>() {
+            Ok(n) => Some(n),
+            Err(_) => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: history: {}: numeric argument required",
+                    state.name,
+                    arg
+                );
+                return 1;
+            }
+        },
+    };
+    let entries = state.history.entries();
+    let skip = count.map_or(0, |n| entries.len().saturating_sub(n));
+    let format = state.get_var("HISTTIMEFORMAT");
+    let mut out = fds.buffered_stdout();
+    for (i, entry) in entries.iter().enumerate().skip(skip) {
+        let time = match (format, entry.time) {
+            #[cfg(unix)]
+            (Some(format), Some(time)) => crate::sys::format_time(format, time),
+            _ => String::new(),
+        };
+        let _ = writeln!(out, "{:>5}  {}{}", i + 1, time, entry.line);
+    }
+    0
+}
+
+/// The options `shopt` knows, in the order it lists them.
+const SHOPT_NAMES: &[&str] = &["expand_aliases", "interactive_comments"];
+
+fn shopt_mut<'a>(state: &'a mut ShellState, name: &str) -> Option<&'a mut bool> {
+    match name {
+        "expand_aliases" => Some(&mut state.options.expand_aliases),
+        "interactive_comments" => Some(&mut state.options.interactive_comments),
+        _ => None,
+    }
+}
+
+/// `shopt [-s|-u] [-pq] [name...]`: sets (`-s`) or unsets (`-u`) the named
+/// options, or prints them, all of them if none are named. `-p` prints them
+/// as `shopt` commands and `-q` prints nothing; the status then says
+/// whether all the named options are on.
+fn shopt(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let (mut set, mut print, mut quiet) = (None, false, false);
+    let mut names = args;
+    while let Some(flags) = names.first().and_then(|arg| arg.strip_prefix('-')) {
+        if flags.is_empty() || flags == "-" {
+            names = &names[usize::from(!flags.is_empty())..];
+            break;
+        }
+        for flag in flags.chars() {
+            match flag {
+                's' => set = Some(true),
+                'u' => set = Some(false),
+                'p' => print = true,
+                'q' => quiet = true,
+                _ => {
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: shopt: -{}: invalid option",
+                        state.name,
+                        flag
+                    );
+                    return 2;
+                }
+            }
+        }
+        names = &names[1..];
+    }
+
+    let listed: Vec<&str> = match names.is_empty() {
+        true => SHOPT_NAMES.to_vec(),
+        false => names.iter().map(String::as_str).collect(),
+    };
+    let mut out = fds.stdout();
+    let mut status = 0;
+    for name in listed {
+        let Some(option) = shopt_mut(state, name) else {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: shopt: {}: invalid shell option name",
+                state.name,
+                name
+            );
+            status = 1;
+            continue;
+        };
+        match set {
+            Some(on) if !names.is_empty() => {
+                *option = on;
+                continue;
+            }
+            // `shopt -s` alone lists the options that are on
+            Some(on) if *option != on => continue,
+            _ => {}
+        }
+        let on = *option;
+        if !on && !names.is_empty() {
+            status = 1;
+        }
+        if quiet {
+            continue;
+        }
+        let _ = match print {
+            true => writeln!(out, "shopt {} {}", if on { "-s" } else { "-u" }, name),
+            false => writeln!(out, "{:<15}\t{}", name, if on { "on" } else { "off" }),
+        };
+    }
+    status
+}
+
+/// Attributes to turn on and off, as given by `-aAirx`/`+irx` flags.
+#[derive(Default)]
+struct Attributes {
+    on: String,
+    off: String,
+}
+
+/// `declare [-p] [-aAirx|+irx] [name[=value]...]` sets variables and their
+/// attributes: `a` indexed array, `A` associative array, `i` integer, `r`
+/// readonly, `x` exported. With `-p`, or no names, it prints definitions
+/// that can be read back in.
+fn declare(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let mut attrs = Attributes::default();
+    let mut print = false;
+    let mut names = args;
+    while let Some(arg) = names.first() {
+        let (on, flags) = match arg.as_bytes().first() {
+            Some(b'-') if arg == "--" => {
+                names = &names[1..];
+                break;
+            }
+            Some(b'-') if arg.len() > 1 => (true, &arg[1..]),
+            Some(b'+') if arg.len() > 1 => (false, &arg[1..]),
+            _ => break,
+        };
+        for flag in flags.chars() {
+            match flag {
+                'p' => print = true,
+                'a' | 'A' | 'i' | 'r' | 'x' if on => attrs.on.push(flag),
+                'i' | 'r' | 'x' => attrs.off.push(flag),
+                _ => {
+                    let sign = if on { '-' } else { '+' };
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: declare: {}{}: invalid option",
+                        state.name,
+                        sign,
+                        flag
+                    );
+                    return 2;
+                }
+            }
+        }
+        names = &names[1..];
+    }
+    if print || names.is_empty() {
+        let definitions = print || !attrs.on.is_empty();
+        return print_declarations(state, "declare", &attrs.on, names, definitions, fds);
+    }
+    set_attributes(state, "declare", &attrs, names, fds)
+}
+
+/// `export [-n] [name[=value]...]`: marks variables for the environment of
+/// commands, or unmarks them with `-n`. Without names, lists them.
+fn export(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let mut attrs = Attributes::default();
+    let names = match args.first().map(|s| s.as_str()) {
+        Some("-n") => {
+            attrs.off.push('x');
+            &args[1..]
+        }
+        Some("-p") => &args[1..],
+        _ => args,
+    };
+    if names.is_empty() {
+        return print_declarations(state, "export", "x", names, true, fds);
+    }
+    if attrs.off.is_empty() {
+        attrs.on.push('x');
+    }
+    set_attributes(state, "export", &attrs, names, fds)
+}
+
+/// `readonly [name[=value]...]`: makes variables unchangeable. Without
+/// names, lists them.
+fn readonly(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let names = match args.first().map(|s| s.as_str()) {
+        Some("-p") => &args[1..],
+        _ => args,
+    };
+    if names.is_empty() {
+        return print_declarations(state, "readonly", "r", names, true, fds);
+    }
+    let attrs = Attributes {
+        on: "r".to_string(),
+        off: String::new(),
+    };
+    set_attributes(state, "readonly", &attrs, names, fds)
+}
+
+/// `unset [-v] name...`: removes variables, or with `name[subscript]` a
+/// single element of an array.
+fn unset(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let names = match args.first().map(String::as_str) {
+        Some("-v") => &args[1..],
+        _ => args,
+    };
+    let mut status = 0;
+    for arg in names {
+        let (name, subscript) = match arg.strip_suffix(']').and_then(|arg| arg.split_once('[')) {
+            Some((name, subscript)) => (name, Some(subscript)),
+            None => (arg.as_str(), None),
+        };
+        if !is_valid_name(name) {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: unset: `{}': not a valid identifier",
+                state.name,
+                arg
+            );
+            status = 1;
+            continue;
+        }
+        if state.var(name).is_some_and(|var| var.readonly) {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: unset: {}: cannot unset: readonly variable",
+                state.name,
+                name
+            );
+            status = 1;
+            continue;
+        }
+        let Some(subscript) = subscript else {
+            state.restore_var(name, None);
+            continue;
+        };
+        if let Err(e) = state.unset_element(name, subscript) {
+            let _ = writeln!(fds.stderr(), "{}: unset: {}", state.name, e);
+            status = 1;
+        }
+    }
+    status
+}
+
+fn set_attributes(
+    state: &mut ShellState,
+    builtin: &str,
+    attrs: &Attributes,
+    names: &[String],
+    fds: &FdTable,
+) -> i32 {
+    let mut status = 0;
+    for arg in names {
+        let (name, subscript, value) = match split_assignment(arg) {
+            Some(a) if a.subscript.is_some() => (a.name, a.subscript, Some(a.value)),
+            Some(a) if a.append => {
+                let value = state.appended_value(&a.name, a.value);
+                (a.name, None, Some(value))
+            }
+            Some(a) => (a.name, None, Some(a.value)),
+            None => (arg.clone(), None, None),
+        };
+        let name = name.as_str();
+        if !is_valid_name(name) {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: {}: `{}': not a valid identifier",
+                state.name,
+                builtin,
+                arg
+            );
+            status = 1;
+            continue;
+        }
+        let readonly = state.var(name).is_some_and(|v| v.readonly);
+        if readonly && (value.is_some() || attrs.off.contains('r')) {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: {}: {}: readonly variable",
+                state.name,
+                builtin,
+                name
+            );
+            status = 1;
+            continue;
+        }
+        let var = state.var_mut(name);
+        for (flags, on) in [(&attrs.on, true), (&attrs.off, false)] {
+            if flags.contains('i') {
+                var.integer = on;
+            }
+            if flags.contains('x') {
+                var.exported = on;
+            }
+        }
+        let array = attrs.on.chars().find(|&flag| flag == 'a' || flag == 'A');
+        if let Some(Err(e)) = array.map(|flag| state.make_array(name, flag == 'A')) {
+            let _ = writeln!(fds.stderr(), "{}: {}: {}", state.name, builtin, e);
+            status = 1;
+            continue;
+        }
+        if let Some(value) = value {
+            let assigned = match &subscript {
+                Some(subscript) => state.assign_element(name, subscript, value),
+                None => state.assign(name, value),
+            };
+            if let Err(e) = assigned {
+                let _ = writeln!(fds.stderr(), "{}: {}: {}", state.name, builtin, e);
+                status = 1;
+                continue;
+            }
+        }
+        if attrs.on.contains('r') {
+            state.var_mut(name).readonly = true;
+        }
+    }
+    status
+}
+
+/// Prints `declare -x NAME="value"` lines for `names`, or for every
+/// variable having all of `required`. Without `definitions` and names it
+/// prints plain `NAME=value` lines, like `declare` on its own.
+fn print_declarations(
+    state: &ShellState,
+    builtin: &str,
+    required: &str,
+    names: &[String],
+    definitions: bool,
+    fds: &FdTable,
+) -> i32 {
+    if names.is_empty() {
+        let mut out = fds.buffered_stdout();
+        for (name, var) in state.vars() {
+            let flags = attribute_flags(var);
+            if !required.chars().all(|c| flags.contains(c)) {
+                continue;
+            }
+            let _ = match definitions {
+                true => writeln!(out, "{}", declaration(name, var)),
+                false => match &var.array {
+                    Some(array) => writeln!(out, "{}={}", name, array_literal(array)),
+                    None => writeln!(out, "{}={}", name, shell_quoted(&var.value)),
+                },
+            };
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for name in names {
+        match state.var(name) {
+            Some(var) => {
+                let _ = writeln!(fds.stdout(), "{}", declaration(name, var));
+            }
+            None => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: {}: {}: not found",
+                    state.name,
+                    builtin,
+                    name
+                );
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn attribute_flags(var: &Variable) -> String {
+    let mut flags = String::new();
+    let attributes = [
+        (matches!(var.array, Some(Array::Indexed(_))), 'a'),
+        (matches!(var.array, Some(Array::Associative(_))), 'A'),
+        (var.integer, 'i'),
+        (var.readonly, 'r'),
+        (var.exported, 'x'),
+    ];
+    for (set, flag) in attributes {
+        if set {
+            flags.push(flag);
+        }
+    }
+    flags
+}
+
+/// The `declare` command that recreates `var`, as `declare -p` prints it.
+fn declaration(name: &str, var: &Variable) -> String {
+    let flags = attribute_flags(var);
+    let flags = if flags.is_empty() {
+        "-".to_string()
+    } else {
+        flags
+    };
+    match &var.array {
+        Some(array) => format!("declare -{} {}={}", flags, name, array_literal(array)),
+        None => format!("declare -{} {}={}", flags, name, double_quoted(&var.value)),
+    }
+}
+
+/// An array as the `(...)` of an assignment, e.g. `([0]="a" [1]="b")`.
+fn array_literal(array: &Array) -> String {
+    let elements: Vec<String> = array
+        .keys()
+        .iter()
+        .zip(array.values())
+        .map(|(key, value)| format!("[{}]={}", key, double_quoted(value)))
+        .collect();
+    format!("({})", elements.join(" "))
+}
+
+/// Quotes `value` in single quotes, unless it's safe as it is.
+fn shell_quoted(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./:,@%+=".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Quotes `value` in double quotes, escaping what's still special there.
+fn double_quoted(value: &str) -> String {
+    let mut out = String::from('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+    out
+}
+
+/// `jobs`: lists the background and stopped jobs, forgetting the finished
+/// ones once they've been shown.
+#[cfg(unix)]
+fn jobs(state: &mut ShellState, _args: &[String], fds: &FdTable) -> i32 {
+    state.jobs.poll();
+    let mut out = fds.buffered_stdout();
+    for job in state.jobs.iter() {
+        let _ = writeln!(out, "{}", state.jobs.format(job.id));
+    }
+    state.jobs.remove_done();
+    0
+}
+
+/// Resolves the job spec a job-control builtin was given, reporting a bad
+/// one as `name: spec: no such job`.
+#[cfg(unix)]
+fn job_arg(state: &ShellState, name: &str, spec: Option<&String>, fds: &FdTable) -> Option<usize> {
+    match state.jobs.resolve(spec.map(|s| s.as_str())) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            let _ = writeln!(fds.stderr(), "{}: {}: {}", state.name, name, e);
+            None
+        }
+    }
+}
+
+/// `fg [job]`: continues a job in the foreground and waits for it to
+/// finish or stop again.
+#[cfg(unix)]
+fn fg(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    use crate::jobs::JobStatus;
+    use crate::sys;
+
+    let Some(id) = job_arg(state, "fg", args.first(), fds) else {
+        return 1;
+    };
+    let Some(job) = state.jobs.get(id) else {
+        return 1;
+    };
+    let pgid = job.pgid;
+    let _ = writeln!(fds.stdout(), "{}", job.command);
+    if state.job_control {
+        sys::give_terminal_to(pgid);
+    }
+    let _ = sys::send_signal(-pgid, sys::SIGCONT);
+    state.jobs.set_status(id, JobStatus::Running);
+    let status = state.jobs.wait_for(id, true);
+    if state.job_control {
+        sys::give_terminal_to(sys::process_group());
+    }
+    match status {
+        JobStatus::Stopped(_) => {
+            let _ = writeln!(fds.stderr(), "\n{}", state.jobs.format(id));
+        }
+        _ => state.jobs.remove(id),
+    }
+    status.code()
+}
+
+/// `bg [job...]`: continues stopped jobs in the background.
+#[cfg(unix)]
+fn bg(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    use crate::jobs::JobStatus;
+    use crate::sys;
+
+    let specs: Vec<Option<&String>> = match args.is_empty() {
+        true => vec![None],
+        false => args.iter().map(Some).collect(),
+    };
+    let mut status = 0;
+    for spec in specs {
+        let Some(id) = job_arg(state, "bg", spec, fds) else {
+            status = 1;
+            continue;
+        };
+        let Some(job) = state.jobs.get(id) else {
+            continue;
+        };
+        if job.status == JobStatus::Running {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: bg: job {} already in background",
+                state.name,
+                id
+            );
+            continue;
+        }
+        let _ = sys::send_signal(-job.pgid, sys::SIGCONT);
+        state.jobs.set_status(id, JobStatus::Running);
+        let _ = writeln!(fds.stdout(), "{}", state.jobs.format(id));
+    }
+    status
+}
+
+/// `kill [-s sig | -n num | -sig] pid|job...` sends a signal (TERM by
+/// default) to processes or whole jobs; `kill -l [sig...]` lists signals.
+/// Signal 0, as in `kill -0 pid`, only checks the target can be signalled.
+#[cfg(unix)]
+fn kill(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    use crate::sys;
+
+    const USAGE: &str = "kill: usage: kill [-s sigspec | -n signum | -sigspec] pid | jobspec ... \
+                         or kill -l [sigspec]";
+    let mut sig = sys::SIGTERM;
+    let mut args = args;
+    match args.first().map(|s| s.as_str()) {
+        Some("-l") => return list_signals(state, &args[1..], fds),
+        Some("-s" | "-n") => {
+            let Some(spec) = args.get(1) else {
+                let _ = writeln!(fds.stderr(), "{}: {}", state.name, USAGE);
+                return 2;
+            };
+            let Some(n) = parse_signal(spec) else {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: kill: {}: invalid signal specification",
+                    state.name,
+                    spec
+                );
+                return 1;
+            };
+            sig = n;
+            args = &args[2..];
+        }
+        Some("--") => args = &args[1..],
+        Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+            let spec = &flag[1..];
+            let Some(n) = parse_signal(spec) else {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: kill: {}: invalid signal specification",
+                    state.name,
+                    spec
+                );
+                return 1;
+            };
+            sig = n;
+            args = &args[1..];
+        }
+        _ => {}
+    }
+    if args.is_empty() {
+        let _ = writeln!(fds.stderr(), "{}: {}", state.name, USAGE);
+        return 2;
+    }
+
+    let mut status = 0;
+    for arg in args {
+        let (target, job) = if arg.starts_with('%') {
+            match job_arg(state, "kill", Some(arg), fds) {
+                Some(id) => match state.jobs.get(id) {
+                    Some(job) => (-job.pgid, Some(id)),
+                    None => continue,
+                },
+                None => {
+                    status = 1;
+                    continue;
+                }
+            }
+        } else {
+            match arg.parse::<crate::jobs::Pid>() {
+                Ok(pid) => (pid, None),
+                Err(_) => {
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: kill: {}: arguments must be process or job IDs",
+                        state.name,
+                        arg
+                    );
+                    status = 1;
+                    continue;
+                }
+            }
+        };
+        if let Err(e) = sys::send_signal(target, sig) {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: kill: ({}) - {}",
+                state.name,
+                target.abs(),
+                describe(&e)
+            );
+            status = 1;
+            continue;
+        }
+        // a stopped job only acts on the signal once it runs again
+        let stopped = job
+            .and_then(|id| state.jobs.get(id))
+            .is_some_and(|j| matches!(j.status, crate::jobs::JobStatus::Stopped(_)));
+        if stopped && sig != 0 && sig != sys::SIGCONT {
+            let _ = sys::send_signal(target, sys::SIGCONT);
+        }
+    }
+    status
+}
+
+/// `trap [-lp] [[action] signal...]`: sets `action` to run when the shell
+/// gets one of the signals, or for `EXIT` (or 0) when it exits. An empty
+/// action ignores the signal, and `-` or no action at all puts back the
+/// default. With no arguments or `-p`, prints the traps as the commands
+/// that would set them again, only those for the signals given to `-p`.
+#[cfg(unix)]
+fn trap(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    let mut args = args;
+    match args.first().map(String::as_str) {
+        Some("-l") => return list_signals(state, &[], fds),
+        None | Some("-p") => return print_traps(state, args.get(1..).unwrap_or_default(), fds),
+        Some("--") => args = &args[1..],
+        _ => {}
+    }
+    let (action, signals) = match args {
+        [] => return print_traps(state, &[], fds),
+        [first, rest @ ..] if first == "-" => (None, rest),
+        // a lone signal, or POSIX's leading number, resets them all
+        [first, ..] if args.len() == 1 || first.parse::<u32>().is_ok() => (None, args),
+        [first, rest @ ..] => (Some(first), rest),
+    };
+    let mut status = 0;
+    for spec in signals {
+        let Some(sig) = trap_signal(spec) else {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: trap: {}: invalid signal specification",
+                state.name,
+                spec
+            );
+            status = 1;
+            continue;
+        };
+        match action {
+            Some(action) => {
+                state.traps.insert(sig, action.clone());
+                match (sig, action.is_empty()) {
+                    (0, _) => {}
+                    (_, true) => crate::sys::ignore_signal(sig),
+                    (_, false) => crate::sys::trap_signal(sig),
+                }
+            }
+            None => {
+                state.traps.remove(&sig);
+                untrap(state, sig);
+            }
+        }
+    }
+    status
+}
+
+/// The signal number a `trap` argument names, with 0 for `EXIT`.
+#[cfg(unix)]
+fn trap_signal(spec: &str) -> Option<i32> {
+    match spec.to_ascii_uppercase().as_str() {
+        "EXIT" | "SIGEXIT" | "0" => Some(0),
+        _ => parse_signal(spec).filter(|&sig| signal_name(sig).is_some()),
+    }
+}
+
+/// Gives `sig` back the handling the shell had before any trap: its own
+/// handlers for the signals it watches, and otherwise the default.
+#[cfg(unix)]
+fn untrap(state: &ShellState, sig: i32) {
+    use crate::sys;
+
+    match sig {
+        0 => {}
+        sys::SIGCHLD => sys::watch_children(),
+        sys::SIGWINCH if state.interactive => sys::watch_window_size(),
+        sys::SIGTTOU if state.job_control => sys::ignore_signal(sig),
+        sys::SIGINT if state.interactive => sys::watch_interrupts(),
+        // the shell itself relies on write errors rather than dying
+        sys::SIGPIPE => sys::ignore_signal(sig),
+        _ => sys::default_signal(sig),
+    }
+}
+
+/// Prints the traps for `signals`, or all of them, as `trap` commands.
+#[cfg(unix)]
+fn print_traps(state: &ShellState, signals: &[String], fds: &FdTable) -> i32 {
+    let mut status = 0;
+    let mut wanted = Vec::new();
+    for spec in signals {
+        match trap_signal(spec) {
+            Some(sig) => wanted.push(sig),
+            None => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: trap: {}: invalid signal specification",
+                    state.name,
+                    spec
+                );
+                status = 1;
+            }
+        }
+    }
+    let mut out = fds.buffered_stdout();
+    for (sig, action) in &state.traps {
+        if !wanted.is_empty() && !wanted.contains(sig) {
+            continue;
+        }
+        let name = match signal_name(*sig) {
+            Some(name) => format!("SIG{}", name),
+            None => "EXIT".to_string(),
+        };
+        let _ = writeln!(out, "trap -- {} {}", single_quote(action), name);
+    }
+    status
+}
+
+#[cfg(unix)]
+fn signal_name(sig: i32) -> Option<&'static str> {
+    crate::sys::SIGNALS
+        .iter()
+        .find(|(_, n)| *n == sig)
+        .map(|(name, _)| *name)
+}
+
+/// Parses a signal name (with or without `SIG`, any case) or number.
+#[cfg(unix)]
+fn parse_signal(spec: &str) -> Option<i32> {
+    if let Ok(n) = spec.parse::<i32>() {
+        return (0..=64).contains(&n).then_some(n);
+    }
+    let upper = spec.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    crate::sys::SIGNALS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, sig)| *sig)
+}
+
+/// `kill -l`: every signal name, or the name for each number given (and
+/// the number for each name).
+#[cfg(unix)]
+fn list_signals(state: &ShellState, args: &[String], fds: &FdTable) -> i32 {
+    use crate::sys::SIGNALS;
+
+    let mut out = fds.stdout();
+    if args.is_empty() {
+        let mut signals = SIGNALS.to_vec();
+        signals.sort_by_key(|(_, sig)| *sig);
+        let names: Vec<&str> = signals.iter().map(|(name, _)| *name).collect();
+        let _ = writeln!(out, "{}", names.join(" "));
+        return 0;
+    }
+    let mut status = 0;
+    for arg in args {
+        // exit statuses of signalled commands name the signal too
+        let by_number = arg
+            .parse::<i32>()
+            .ok()
+            .map(|n| if n > 128 { n - 128 } else { n });
+        let found = match by_number {
+            Some(n) => signal_name(n).map(str::to_string),
+            None => parse_signal(arg).map(|sig| sig.to_string()),
+        };
+        match found {
+            Some(text) => {
+                let _ = writeln!(out, "{}", text);
+            }
+            None => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: kill: {}: invalid signal specification",
+                    state.name,
+                    arg
+                );
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+/// `wait [-n] [pid|job...]`: waits for the given jobs, or for all of them,
+/// and forgets them. The status is the last one waited for, or 0 without
+/// arguments. `-n` waits for just the first of them to finish and returns
+/// its status, or 127 if none are left.
+#[cfg(unix)]
+fn wait(state: &mut ShellState, args: &[String], fds: &FdTable) -> i32 {
+    if args.first().is_some_and(|arg| arg == "-n") {
+        let mut status = 0;
+        let mut among = Vec::new();
+        for arg in &args[1..] {
+            match wait_arg(state, arg, fds) {
+                Ok(id) => among.push(id),
+                Err(code) => status = code,
+            }
+        }
+        if among.is_empty() && args.len() > 1 {
+            return status;
+        }
+        return match state.jobs.wait_next(&among) {
+            Some(id) => {
+                let status = state.jobs.get(id).map_or(127, |job| job.status.code());
+                state.jobs.remove(id);
+                status
+            }
+            None => 127,
+        };
+    }
+    if args.is_empty() {
+        let ids: Vec<usize> = state.jobs.iter().map(|j| j.id).collect();
+        for id in ids {
+            state.jobs.wait_for(id, false);
+            state.jobs.remove(id);
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for arg in args {
+        match wait_arg(state, arg, fds) {
+            Ok(id) => {
+                status = state.jobs.wait_for(id, false).code();
+                state.jobs.remove(id);
+            }
+            Err(code) => status = code,
+        }
+    }
+    status
+}
+
+/// The job a `wait` argument names, or the status to give after saying
+/// why there isn't one.
+#[cfg(unix)]
+fn wait_arg(state: &ShellState, arg: &String, fds: &FdTable) -> Result<usize, i32> {
+    if arg.starts_with('%') {
+        return job_arg(state, "wait", Some(arg), fds).ok_or(127);
+    }
+    let found = match arg.parse::<crate::jobs::Pid>() {
+        Ok(pid) => state.jobs.find_pid(pid),
+        Err(_) => {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: wait: `{}': not a pid or valid job spec",
+                state.name,
+                arg
+            );
+            return Err(2);
+        }
+    };
+    found.ok_or_else(|| {
+        let _ = writeln!(
+            fds.stderr(),
+            "{}: wait: pid {} is not a child of this shell",
+            state.name,
+            arg
+        );
+        127
+    })
+}
@@ -0,0 +1,187 @@
+//! Evaluation of `[[ ... ]]`. Its operands are expanded without field
+//! splitting or globbing, and the right side of `==` and `!=` is a glob
+//! pattern, that of `=~` a regex.
+
+use crate::arith;
+use crate::expand::{expand_pattern, expand_regex, expand_word};
+use crate::glob::glob_match;
+use crate::parser::CondExpr;
+use crate::redirect::FdTable;
+use crate::regex::Regex;
+use crate::state::ShellState;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Evaluates `expr` to the status of the command: 0 if true, 1 if false
+/// or an integer operand is bad, and 2 for a bad regex. An error
+/// expanding a word is returned.
+pub fn eval(state: &mut ShellState, expr: &CondExpr, fds: &FdTable) -> Result<i32, String> {
+    let truth = |value: bool| if value { 0 } else { 1 };
+    Ok(match expr {
+        CondExpr::Word(word) => truth(!expand_word(state, word)?.is_empty()),
+        CondExpr::Unary(op, word) => {
+            let operand = expand_word(state, word)?;
+            truth(unary(state, op, &operand))
+        }
+        CondExpr::Binary(lhs, op, rhs) => binary(state, lhs, op, rhs, fds)?,
+        CondExpr::Not(expr) => match eval(state, expr, fds)? {
+            0 => 1,
+            1 => 0,
+            status => status,
+        },
+        CondExpr::And(lhs, rhs) => match eval(state, lhs, fds)? {
+            0 => eval(state, rhs, fds)?,
+            status => status,
+        },
+        CondExpr::Or(lhs, rhs) => match eval(state, lhs, fds)? {
+            0 => 0,
+            _ => eval(state, rhs, fds)?,
+        },
+        CondExpr::Group(expr) => eval(state, expr, fds)?,
+    })
+}
+
+fn binary(
+    state: &mut ShellState,
+    lhs: &str,
+    op: &str,
+    rhs: &str,
+    fds: &FdTable,
+) -> Result<i32, String> {
+    let truth = |value: bool| if value { 0 } else { 1 };
+    let lhs = expand_word(state, lhs)?;
+    match op {
+        "=" | "==" | "!=" => {
+            let pattern = expand_pattern(state, rhs)?;
+            return Ok(truth(glob_match(&pattern, &lhs) == (op != "!=")));
+        }
+        "=~" => {
+            let regex = match Regex::new(&expand_regex(state, rhs)?) {
+                Ok(regex) => regex,
+                Err(_) => return Ok(2),
+            };
+            let Some(groups) = regex.captures(&lhs) else {
+                state.set_array("BASH_REMATCH", Vec::new());
+                return Ok(1);
+            };
+            // the whole match, then what each group matched
+            let groups = groups.into_iter().map(Option::unwrap_or_default).collect();
+            state.set_array("BASH_REMATCH", groups);
+            return Ok(0);
+        }
+        _ => {}
+    }
+    let rhs = expand_word(state, rhs)?;
+    Ok(match op {
+        "<" => truth(lhs < rhs),
+        ">" => truth(lhs > rhs),
+        "-nt" => truth(match (modified(&lhs), modified(&rhs)) {
+            (Some(a), Some(b)) => a > b,
+            (a, b) => a.is_some() && b.is_none(),
+        }),
+        "-ot" => truth(match (modified(&lhs), modified(&rhs)) {
+            (Some(a), Some(b)) => a < b,
+            (a, b) => a.is_none() && b.is_some(),
+        }),
+        "-ef" => truth(same_file(&lhs, &rhs)),
+        _ => {
+            let (a, b) = match (arith::eval(state, &lhs), arith::eval(state, &rhs)) {
+                (Ok(a), Ok(b)) => (a, b),
+                (Err(e), _) | (_, Err(e)) => {
+                    let _ = writeln!(fds.stderr(), "{}: [[: {}", state.name, e);
+                    return Ok(1);
+                }
+            };
+            truth(match op {
+                "-eq" => a == b,
+                "-ne" => a != b,
+                "-lt" => a < b,
+                "-le" => a <= b,
+                "-gt" => a > b,
+                _ => a >= b,
+            })
+        }
+    })
+}
+
+fn unary(state: &ShellState, op: &str, operand: &str) -> bool {
+    match op {
+        "-n" => !operand.is_empty(),
+        "-z" => operand.is_empty(),
+        "-v" => state.var(operand).is_some(),
+        "-t" => operand.parse().is_ok_and(is_terminal),
+        "-h" | "-L" => fs::symlink_metadata(operand).is_ok_and(|md| md.file_type().is_symlink()),
+        _ => file_test(op, Path::new(operand)),
+    }
+}
+
+/// The tests on the file `path` names, following symlinks.
+#[cfg(unix)]
+fn file_test(op: &str, path: &Path) -> bool {
+    use crate::sys::{accessible, R_OK, W_OK, X_OK};
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+    let Ok(md) = fs::metadata(path) else {
+        return false;
+    };
+    let kind = md.file_type();
+    match op {
+        "-a" | "-e" => true,
+        "-f" => kind.is_file(),
+        "-d" => kind.is_dir(),
+        "-b" => kind.is_block_device(),
+        "-c" => kind.is_char_device(),
+        "-p" => kind.is_fifo(),
+        "-S" => kind.is_socket(),
+        "-s" => md.len() > 0,
+        "-u" => md.mode() & 0o4000 != 0,
+        "-g" => md.mode() & 0o2000 != 0,
+        "-k" => md.mode() & 0o1000 != 0,
+        "-r" => accessible(path, R_OK),
+        "-w" => accessible(path, W_OK),
+        "-x" => accessible(path, X_OK),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn file_test(op: &str, path: &Path) -> bool {
+    let Ok(md) = fs::metadata(path) else {
+        return false;
+    };
+    match op {
+        "-a" | "-e" | "-r" | "-x" => true,
+        "-f" => md.is_file(),
+        "-d" => md.is_dir(),
+        "-s" => md.len() > 0,
+        "-w" => !md.permissions().readonly(),
+        _ => false,
+    }
+}
+
+fn is_terminal(fd: i32) -> bool {
+    #[cfg(unix)]
+    return crate::sys::is_terminal(fd);
+    #[cfg(not(unix))]
+    return fd == 0 && std::io::IsTerminal::is_terminal(&std::io::stdin());
+}
+
+fn modified(path: &str) -> Option<std::time::SystemTime> {
+    fs::metadata(path).and_then(|md| md.modified()).ok()
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
@@ -0,0 +1,732 @@
+//! The line editor used when an interactive shell reads from a terminal:
+//! the terminal is put in raw mode and keys are handled one at a time, for
+//! cursor movement, history recall and tab completion.
+
+use crate::builtins;
+use crate::parser::KEYWORDS;
+use crate::path::commands_starting_with;
+use crate::state::ShellState;
+use crate::sys::RawMode;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+pub enum Input {
+    /// A line, with its `\n`.
+    Line(String),
+    /// Ctrl-C threw the line away.
+    Interrupted,
+    /// Ctrl-D on an empty line.
+    Eof,
+}
+
+/// Shows `prompt` and reads a line from the terminal on stdin.
+pub fn read_line(state: &ShellState, prompt: &str) -> io::Result<Input> {
+    let _raw = RawMode::enable(0)?;
+    let mut editor = Editor::new(state, prompt);
+    let mut out = io::stdout();
+    write!(out, "{}", crate::prompt::displayed(prompt))?;
+    out.flush()?;
+    loop {
+        let Some(key) = read_key()? else {
+            return Ok(Input::Eof);
+        };
+        if let Some(input) = editor.key(key, &mut out)? {
+            return Ok(input);
+        }
+        editor.redraw(&mut out)?;
+    }
+}
+
+enum Key {
+    Char(char),
+    /// Escape on its own, not starting a sequence.
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+    Other,
+}
+
+/// Reads one key press, decoding UTF-8 and the escape sequences terminals
+/// send for the arrow keys and friends. `None` at end of input.
+fn read_key() -> io::Result<Option<Key>> {
+    let Some(byte) = read_byte()? else {
+        return Ok(None);
+    };
+    if byte == 0x1b {
+        // a sequence comes all at once, while a key pressed after Escape
+        // is a while coming
+        if !crate::sys::input_ready(0, Duration::from_millis(50)) {
+            return Ok(Some(Key::Escape));
+        }
+        let key = match read_byte()? {
+            Some(b'[' | b'O') => match read_byte()? {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                Some(b'H') => Key::Home,
+                Some(b'F') => Key::End,
+                Some(digit @ b'0'..=b'9') => {
+                    // `ESC [ 3 ~` and the like
+                    let mut last = digit;
+                    while last.is_ascii_digit() || last == b';' {
+                        last = read_byte()?.unwrap_or(b'~');
+                    }
+                    match digit {
+                        b'3' => Key::Delete,
+                        b'1' | b'7' => Key::Home,
+                        b'4' | b'8' => Key::End,
+                        _ => Key::Other,
+                    }
+                }
+                _ => Key::Other,
+            },
+            _ => Key::Other,
+        };
+        return Ok(Some(key));
+    }
+    let len = match byte {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    let mut bytes = vec![byte];
+    for _ in 1..len {
+        bytes.extend(read_byte()?);
+    }
+    let text = String::from_utf8_lossy(&bytes);
+    Ok(text.chars().next().map(Key::Char))
+}
+
+fn read_byte() -> io::Result<Option<u8>> {
+    let mut byte = [0u8];
+    match io::stdin().read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+struct Editor<'a> {
+    state: &'a ShellState,
+    /// The prompt's last line, as written out.
+    prompt: String,
+    /// The columns the prompt takes, not counting its non-printing text.
+    prompt_width: usize,
+    line: Vec<char>,
+    cursor: usize,
+    /// The history entry shown, or the number of entries for the line
+    /// being typed.
+    recalled: usize,
+    /// The line being typed, kept while history is shown instead.
+    draft: Vec<char>,
+    /// Whether the last key was a Tab, so a second one lists candidates.
+    tabbed: bool,
+    /// `set -o vi`: vi's keys rather than emacs's.
+    vi: bool,
+    /// In vi's command mode rather than inserting.
+    command_mode: bool,
+    /// A vi command waiting for the key it applies to, as `d` waits for a
+    /// motion and `r` for the replacement.
+    pending: Option<char>,
+}
+
+impl<'a> Editor<'a> {
+    fn new(state: &'a ShellState, prompt: &str) -> Editor<'a> {
+        Editor {
+            state,
+            // redraws only repeat the prompt's last line
+            prompt: crate::prompt::displayed(prompt.rsplit('\n').next().unwrap_or_default()),
+            prompt_width: crate::prompt::width(prompt),
+            line: Vec::new(),
+            cursor: 0,
+            recalled: state.history.entries().len(),
+            draft: Vec::new(),
+            tabbed: false,
+            vi: state.options.vi,
+            command_mode: false,
+            pending: None,
+        }
+    }
+
+    /// Handles one key, returning what was read once the line is done.
+    fn key(&mut self, key: Key, out: &mut impl Write) -> io::Result<Option<Input>> {
+        let tabbed = self.tabbed;
+        self.tabbed = false;
+        if self.command_mode && self.vi_command(&key) {
+            return Ok(None);
+        }
+        match key {
+            Key::Char('\r' | '\n') => {
+                write!(out, "\r\n")?;
+                let mut line: String = self.line.iter().collect();
+                line.push('\n');
+                return Ok(Some(Input::Line(line)));
+            }
+            Key::Char('\x03') => {
+                write!(out, "^C\r\n")?;
+                return Ok(Some(Input::Interrupted));
+            }
+            Key::Char('\x04') if self.line.is_empty() => {
+                write!(out, "\r\n")?;
+                return Ok(Some(Input::Eof));
+            }
+            Key::Char('\x04') | Key::Delete => {
+                if self.cursor < self.line.len() {
+                    self.line.remove(self.cursor);
+                }
+            }
+            Key::Char('\x7f' | '\x08') => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.line.remove(self.cursor);
+                }
+            }
+            Key::Char('\x01') | Key::Home => self.cursor = 0,
+            Key::Char('\x05') | Key::End => self.cursor = self.line.len(),
+            Key::Char('\x02') | Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Char('\x06') | Key::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            Key::Char('\x0b') => self.line.truncate(self.cursor),
+            Key::Char('\x15') => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Key::Char('\x17') => {
+                let start = self.word_back();
+                self.line.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            Key::Char('\x0c') => write!(out, "\x1b[H\x1b[2J")?,
+            Key::Char('\x10') | Key::Up => self.recall(-1),
+            Key::Char('\x0e') | Key::Down => self.recall(1),
+            Key::Char('\t') => {
+                self.tabbed = true;
+                self.complete(out, tabbed)?;
+            }
+            // vi starts its command mode on the character before the cursor
+            Key::Escape if self.vi => {
+                self.command_mode = true;
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+            Key::Char(c) if c.is_control() => {}
+            Key::Char(c) => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Escape | Key::Other => {}
+        }
+        Ok(None)
+    }
+
+    /// Handles `key` in vi's command mode, unless it finishes the line the
+    /// way it does when inserting, which is left to [`Editor::key`].
+    fn vi_command(&mut self, key: &Key) -> bool {
+        let c = match key {
+            Key::Char('\r' | '\n' | '\x03' | '\x04') => return false,
+            Key::Char(c) => *c,
+            Key::Left => 'h',
+            Key::Right => 'l',
+            Key::Up => 'k',
+            Key::Down => 'j',
+            Key::Home => '0',
+            Key::End => '$',
+            Key::Delete => 'x',
+            Key::Escape | Key::Other => {
+                self.pending = None;
+                return true;
+            }
+        };
+        let len = self.line.len();
+        match self.pending.take() {
+            Some('r') => {
+                if self.cursor < len && !c.is_control() {
+                    self.line[self.cursor] = c;
+                }
+            }
+            Some(op) => {
+                // `dd` and `cc` take the whole line, and `cw` changes just
+                // the word, like `ce`
+                let motion = if op == 'c' && c == 'w' { 'e' } else { c };
+                let range = match self.motion(motion) {
+                    _ if c == op => 0..len,
+                    Some(to) if to < self.cursor => to..self.cursor,
+                    Some(to) if motion == 'e' => self.cursor..(to + 1).min(len),
+                    Some(to) => self.cursor..to,
+                    None => return true,
+                };
+                self.cursor = range.start;
+                self.line.drain(range);
+                self.command_mode = op == 'd';
+            }
+            None => match c {
+                'i' => self.command_mode = false,
+                'a' => {
+                    self.cursor = (self.cursor + 1).min(len);
+                    self.command_mode = false;
+                }
+                'I' => {
+                    self.cursor = 0;
+                    self.command_mode = false;
+                }
+                'A' => {
+                    self.cursor = len;
+                    self.command_mode = false;
+                }
+                'x' | 's' if self.cursor < len => {
+                    self.line.remove(self.cursor);
+                    self.command_mode = c == 'x';
+                }
+                'X' if self.cursor > 0 => {
+                    self.cursor -= 1;
+                    self.line.remove(self.cursor);
+                }
+                'D' | 'C' => {
+                    self.line.truncate(self.cursor);
+                    self.command_mode = c == 'D';
+                }
+                'S' => {
+                    self.line.clear();
+                    self.cursor = 0;
+                    self.command_mode = false;
+                }
+                '~' if self.cursor < len => {
+                    let ch = self.line[self.cursor];
+                    let swapped = match ch.is_uppercase() {
+                        true => ch.to_lowercase().next(),
+                        false => ch.to_uppercase().next(),
+                    };
+                    self.line[self.cursor] = swapped.unwrap_or(ch);
+                    self.cursor += 1;
+                }
+                'd' | 'c' | 'r' => self.pending = Some(c),
+                'k' | 'j' => {
+                    self.recall(if c == 'k' { -1 } else { 1 });
+                    self.cursor = 0;
+                }
+                _ => {
+                    if let Some(to) = self.motion(c) {
+                        self.cursor = to;
+                    }
+                }
+            },
+        }
+        // in command mode the cursor sits on a character, not past them
+        if self.command_mode && self.cursor >= self.line.len() {
+            self.cursor = self.line.len().saturating_sub(1);
+        }
+        true
+    }
+
+    /// Where vi's motion `c` takes the cursor, if `c` is one: `h` and `l`
+    /// by a character, `0`, `^` and `$` to the ends, and `w`, `b` and `e`
+    /// by blank-separated words.
+    fn motion(&self, c: char) -> Option<usize> {
+        let line = &self.line;
+        let blank = |i: usize| line[i].is_whitespace();
+        let mut to = self.cursor;
+        match c {
+            'h' => to = to.saturating_sub(1),
+            'l' | ' ' => to = (to + 1).min(line.len()),
+            '0' => to = 0,
+            '^' => {
+                to = line
+                    .iter()
+                    .position(|c| !c.is_whitespace())
+                    .unwrap_or(line.len())
+            }
+            '$' => to = line.len(),
+            'w' => {
+                while to < line.len() && !blank(to) {
+                    to += 1;
+                }
+                while to < line.len() && blank(to) {
+                    to += 1;
+                }
+            }
+            'b' => to = self.word_back(),
+            'e' => {
+                to += 1;
+                while to < line.len() && blank(to) {
+                    to += 1;
+                }
+                while to + 1 < line.len() && !blank(to + 1) {
+                    to += 1;
+                }
+                to = to.min(line.len().saturating_sub(1));
+            }
+            _ => return None,
+        }
+        Some(to)
+    }
+
+    /// The start of the word before the cursor, past any blanks first.
+    fn word_back(&self) -> usize {
+        let mut start = self.cursor;
+        while start > 0 && self.line[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !self.line[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        start
+    }
+
+    fn redraw(&self, out: &mut impl Write) -> io::Result<()> {
+        let line: String = self.line.iter().collect();
+        write!(out, "\r{}{}\x1b[K", self.prompt, line)?;
+        // the cursor goes to its column from the left, as escapes in the
+        // prompt take up bytes but no room
+        let column = self.prompt_width + self.cursor;
+        if self.cursor < self.line.len() {
+            write!(out, "\r")?;
+            if column > 0 {
+                write!(out, "\x1b[{}C", column)?;
+            }
+        }
+        out.flush()
+    }
+
+    /// Moves `step` entries through the history, back to the line being
+    /// typed past the newest one.
+    fn recall(&mut self, step: isize) {
+        let entries = self.state.history.entries();
+        let Some(next) = self.recalled.checked_add_signed(step) else {
+            return;
+        };
+        if next > entries.len() {
+            return;
+        }
+        if self.recalled == entries.len() {
+            self.draft = std::mem::take(&mut self.line);
+        }
+        self.line = match entries.get(next) {
+            Some(entry) => entry.line.chars().collect(),
+            None => std::mem::take(&mut self.draft),
+        };
+        self.recalled = next;
+        self.cursor = self.line.len();
+    }
+
+    /// Completes the word before the cursor: a command name in command
+    /// position, else a file name. A unique match is filled in; otherwise
+    /// the common prefix is, and a second Tab in a row lists the matches.
+    /// What is filled in is quoted the way the word is so far.
+    fn complete(&mut self, out: &mut impl Write, tabbed: bool) -> io::Result<()> {
+        let Partial { start, word, open } = partial_word(&self.line[..self.cursor]);
+        let before: String = self.line[..start].iter().collect();
+        let before = before.trim_end();
+        let command = before.is_empty() || before.ends_with([';', '|', '&', '(']);
+        let mut candidates = match command && !word.contains('/') {
+            true => command_candidates(self.state, &word),
+            false => file_candidates(self.state, &word),
+        };
+        candidates.sort();
+        candidates.dedup();
+
+        // what the word is missing, past the characters already typed
+        let rest = |candidate: &str| -> String {
+            let added: String = candidate.chars().skip(word.chars().count()).collect();
+            quoted(&added, open, word.is_empty())
+        };
+        let addition = match &candidates[..] {
+            [] => None,
+            [only] if only.ends_with('/') => Some(rest(only)),
+            [only] => Some(format!(
+                "{}{} ",
+                rest(only),
+                open.map_or(String::new(), String::from)
+            )),
+            _ => Some(common_prefix(&candidates))
+                .filter(|prefix| prefix.len() > word.len())
+                .map(|prefix| rest(&prefix)),
+        };
+        if let Some(addition) = addition {
+            self.line.splice(self.cursor..self.cursor, addition.chars());
+            self.cursor += addition.chars().count();
+            return Ok(());
+        }
+        if candidates.len() < 2 || !tabbed {
+            return write!(out, "\x07");
+        }
+        // list just the last component, as `ls` would show it
+        let names: Vec<String> = candidates
+            .iter()
+            .map(|c| match c.trim_end_matches('/').rfind('/') {
+                Some(slash) => c[slash + 1..].to_string(),
+                None => c.clone(),
+            })
+            .collect();
+        let listing = columns(&names, self.state.columns());
+        write!(out, "\r\n{}", listing.replace('\n', "\r\n"))
+    }
+}
+
+/// The word before the cursor, as the completer sees it.
+#[derive(Debug, PartialEq)]
+struct Partial {
+    /// Where the word starts in the line.
+    start: usize,
+    /// The word with its quotes and escapes taken out.
+    word: String,
+    /// The quote the word is still inside, if any.
+    open: Option<char>,
+}
+
+/// Scans `line`, the text before the cursor, for the word being completed,
+/// following quotes and backslashes the way the lexer does so that quoted
+/// blanks don't end it.
+fn partial_word(line: &[char]) -> Partial {
+    let mut partial = Partial {
+        start: 0,
+        word: String::new(),
+        open: None,
+    };
+    let mut escaped = false;
+    for (i, &c) in line.iter().enumerate() {
+        match partial.open {
+            _ if escaped => {
+                // in double quotes a backslash only escapes a few characters
+                if partial.open.is_some() && !"\"\\$`".contains(c) {
+                    partial.word.push('\\');
+                }
+                partial.word.push(c);
+                escaped = false;
+            }
+            Some('\'') if c == '\'' => partial.open = None,
+            Some('\'') => partial.word.push(c),
+            Some(_) if c == '"' => partial.open = None,
+            Some(_) if c == '\\' => escaped = true,
+            Some(_) => partial.word.push(c),
+            None if c == '\'' || c == '"' => partial.open = Some(c),
+            None if c == '\\' => escaped = true,
+            None if ends_word(c) => {
+                partial.start = i + 1;
+                partial.word.clear();
+            }
+            None => partial.word.push(c),
+        }
+    }
+    partial
+}
+
+/// `text` quoted to go on a word left inside the quote `open`, or with
+/// backslashes before the special characters outside quotes, where `~`
+/// and `#` are only special when they `start` the word.
+fn quoted(text: &str, open: Option<char>, start: bool) -> String {
+    let special = |i: usize, c: char| match open {
+        Some('\'') => false,
+        Some(_) => "\"\\$`".contains(c),
+        None if c == '~' || c == '#' => start && i == 0,
+        None => !(c.is_alphanumeric() || "%+,-./:=@_^".contains(c)),
+    };
+    let mut out = String::new();
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\'' if open == Some('\'') => out.push_str("'\\''"),
+            c if special(i, c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Characters that end the word being completed.
+fn ends_word(c: char) -> bool {
+    c.is_whitespace() || matches!(c, ';' | '|' | '&' | '<' | '>' | '(' | ')')
+}
+
+fn command_candidates(state: &ShellState, word: &str) -> Vec<String> {
+    let mut names: Vec<String> = builtins::names()
+        .chain(KEYWORDS.iter().copied())
+        .filter(|name| name.starts_with(word))
+        .map(str::to_string)
+        .collect();
+    names.extend(commands_starting_with(word, state.search_var("PATH")));
+    names
+}
+
+/// The files matching `word`, each as the word completed to name it, with
+/// a `/` after directories. Hidden files only match a word that names them
+/// with a leading `.`.
+fn file_candidates(state: &ShellState, word: &str) -> Vec<String> {
+    let (dir_part, prefix) = match word.rfind('/') {
+        Some(slash) => word.split_at(slash + 1),
+        None => ("", word),
+    };
+    let dir = match dir_part {
+        "" => Path::new(".").to_path_buf(),
+        _ => crate::path::expand_tilde(dir_part, state.home().as_deref()),
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let hidden = name.starts_with('.') && !prefix.starts_with('.');
+            if hidden || !name.starts_with(prefix) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir_part, name, slash))
+        })
+        .collect()
+}
+
+fn common_prefix(words: &[String]) -> String {
+    let first = &words[0];
+    let mut len = first.len();
+    for word in &words[1..] {
+        len = first
+            .char_indices()
+            .zip(word.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(len.min(word.len()), |((i, _), _)| i.min(len));
+    }
+    first[..len].to_string()
+}
+
+/// Lays `items` out in columns that fit in `width`, filling each column
+/// top to bottom before the next, like `ls`.
+fn columns(items: &[String], width: usize) -> String {
+    let column = items
+        .iter()
+        .map(|item| item.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let per_row = (width / column).max(1);
+    let rows = items.len().div_ceil(per_row);
+    let mut out = String::new();
+    for row in 0..rows {
+        let mut line = String::new();
+        for item in items.iter().skip(row).step_by(rows) {
+            line.push_str(&format!("{:<width$}", item, width = column));
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(names: &str) -> Vec<String> {
+        names.split(' ').map(str::to_string).collect()
+    }
+
+    /// The line after typing `keys` into an editor with vi's keys or
+    /// emacs's, where an escape character is the Escape key.
+    fn typed(vi: bool, keys: &str) -> String {
+        let mut state = ShellState::default();
+        state.options.vi = vi;
+        let mut editor = Editor::new(&state, "$ ");
+        for c in keys.chars() {
+            let key = if c == '\x1b' {
+                Key::Escape
+            } else {
+                Key::Char(c)
+            };
+            editor.key(key, &mut Vec::new()).unwrap();
+        }
+        editor.line.iter().collect()
+    }
+
+    #[test]
+    fn emacs_keys_move_and_kill() {
+        assert_eq!(typed(false, "hello\x01X\x05Y"), "XhelloY");
+        assert_eq!(typed(false, "one two\x17three"), "one three");
+        assert_eq!(typed(false, "abc\x02\x02\x0b\x02\x15z"), "za");
+        // Escape does nothing in emacs mode
+        assert_eq!(typed(false, "ab\x1bxc"), "abxc");
+    }
+
+    #[test]
+    fn vi_keys_switch_modes_and_edit() {
+        assert_eq!(typed(true, "abc\x1b0ix"), "xabc");
+        assert_eq!(typed(true, "hello\x1bIsay \x1bA!"), "say hello!");
+        assert_eq!(typed(true, "echo hello world\x1bbdw"), "echo hello ");
+        assert_eq!(typed(true, "one two\x1b0cwuno"), "uno two");
+        assert_eq!(typed(true, "abc\x1bhrX"), "aXc");
+        assert_eq!(typed(true, "foo bar\x1bddibaz"), "baz");
+        assert_eq!(typed(true, "word\x1b0~~"), "WOrd");
+        assert_eq!(typed(true, "a b c\x1b0wD"), "a ");
+        assert_eq!(typed(true, "ab cd ef\x1b0dex$X"), "cd f");
+        assert_eq!(typed(true, "abcd\x1b0lsX\x1bxx"), "ad");
+    }
+
+    #[test]
+    fn columns_fill_the_width() {
+        let names = items("alpha beta gamma delta epsilon");
+        // each column is 9 wide: the longest name and two spaces
+        assert_eq!(
+            columns(&names, 80),
+            "alpha    beta     gamma    delta    epsilon\n"
+        );
+        assert_eq!(
+            columns(&names, 27),
+            "alpha    gamma    epsilon\nbeta     delta\n"
+        );
+        assert_eq!(
+            columns(&names, 18),
+            "alpha    delta\nbeta     epsilon\ngamma\n"
+        );
+    }
+
+    #[test]
+    fn narrow_widths_still_give_one_column() {
+        assert_eq!(columns(&items("alpha beta"), 3), "alpha\nbeta\n");
+    }
+
+    fn partial(line: &str) -> Partial {
+        partial_word(&line.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn partial_words_follow_quotes_and_escapes() {
+        let word = |start, word: &str, open| Partial {
+            start,
+            word: word.into(),
+            open,
+        };
+        assert_eq!(partial("cat \"my fi"), word(4, "my fi", Some('"')));
+        assert_eq!(partial("cat my\\ fi"), word(4, "my fi", None));
+        assert_eq!(
+            partial("ls a'b c'd\"e\\\"f\\g"),
+            word(3, "ab cde\"f\\g", Some('"'))
+        );
+        assert_eq!(partial("echo 'x' y|z"), word(11, "z", None));
+        assert_eq!(partial("echo \"done\" "), word(12, "", None));
+    }
+
+    #[test]
+    fn completions_are_quoted_for_the_word() {
+        assert_eq!(quoted("le name.txt", None, false), "le\\ name.txt");
+        assert_eq!(quoted("~a#b (1)", None, true), "\\~a#b\\ \\(1\\)");
+        assert_eq!(
+            quoted("a \"$b\" 'c'", Some('"'), false),
+            "a \\\"\\$b\\\" 'c'"
+        );
+        assert_eq!(quoted("it's", Some('\''), false), "it'\\''s");
+    }
+
+    #[test]
+    fn common_prefixes() {
+        assert_eq!(common_prefix(&items("echo exec exit")), "e");
+        assert_eq!(common_prefix(&items("export exports")), "export");
+        assert_eq!(common_prefix(&items("cd ls")), "");
+    }
+}
@@ -0,0 +1,1238 @@
+use crate::builtins;
+use crate::conditional;
+use crate::expand::{
+    expand_assignment, expand_here_doc, expand_pattern, expand_subscript, expand_word, expand_words,
+};
+use crate::glob::glob_match;
+use crate::parser::{
+    self, AndOr, CaseItem, Command, CompoundCommand, Connector, List, ListItem, ParseError,
+    Pipeline, RedirOp, Redirect, SimpleCommand, Timing,
+};
+use crate::path::{executable_path, find_in_path};
+use crate::prompt;
+use crate::redirect::{self, FdTable, FdTarget};
+use crate::state::{Flow, ShellState, Variable};
+use crate::timing;
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+
+/// Reads and runs commands until end of input or `exit`, returning the
+/// status to exit with. Lines are accumulated until they form complete
+/// commands, so compound commands and quotes can span lines; an
+/// interactive shell prompts with `> ` while it waits for the rest, and
+/// reports background jobs that changed state before each new prompt.
+/// An interactive shell also reads its history from `HISTFILE` first and
+/// writes it back at the end, and first runs the file named by `ENV`. Any
+/// `EXIT` trap runs last. History expansion and `ENV` are only for commands
+/// from a terminal; piped input is prompted for but read as a script
+/// would be.
+pub fn run_input(
+    state: &mut ShellState,
+    read_line: impl FnMut(&mut String) -> io::Result<usize>,
+    interactive: bool,
+) -> i32 {
+    state.interactive = interactive;
+    let terminal = interactive && io::stdin().is_terminal();
+    state.job_control = terminal;
+    state.options.histexpand = terminal && !state.options.posix;
+    state.options.expand_aliases = interactive || state.options.posix;
+    #[cfg(unix)]
+    {
+        crate::sys::watch_children();
+        if interactive {
+            crate::sys::watch_window_size();
+            crate::sys::watch_interrupts();
+        }
+        if terminal {
+            // handing the terminal back from a job sends the shell SIGTTOU
+            crate::sys::ignore_signal(crate::sys::SIGTTOU);
+        }
+    }
+    let histfile = state
+        .get_var("HISTFILE")
+        .filter(|_| interactive)
+        .map(PathBuf::from);
+    if let Some(text) = histfile
+        .as_ref()
+        .and_then(|path| fs::read_to_string(path).ok())
+    {
+        state.history.load(&text);
+    }
+    if terminal && !state.norc && !state.parse_only {
+        run_env_file(state);
+        if let Some(Flow::Exit(code)) = state.flow {
+            return code;
+        }
+    }
+    let mut status = read_and_run(state, read_line, interactive);
+    if let Some(action) = state.traps.remove(&0) {
+        state.flow = None;
+        state.last_status = status;
+        run_trap(state, &action);
+        if let Some(Flow::Exit(code)) = state.flow {
+            status = code;
+        }
+    }
+    if let Some(path) = histfile {
+        let timestamps = state.get_var("HISTTIMEFORMAT").is_some();
+        if let Err(e) = fs::write(&path, state.history.file_text(timestamps)) {
+            eprintln!(
+                "{}: {}: {}",
+                state.name,
+                path.display(),
+                redirect::describe(&e)
+            );
+        }
+    }
+    #[cfg(unix)]
+    close_exec_fds(state);
+    status
+}
+
+/// Flushes the shell's output and closes what `exec` opened, once nothing
+/// is left to write to it.
+#[cfg(unix)]
+fn close_exec_fds(state: &mut ShellState) {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    for fd in std::mem::take(&mut state.exec_fds) {
+        crate::sys::close_fd(fd);
+    }
+}
+
+/// Runs the file named by `ENV`, after expanding it, as POSIX has an
+/// interactive shell do on startup.
+fn run_env_file(state: &mut ShellState) {
+    let Some(env) = state.get_var("ENV").map(str::to_string) else {
+        return;
+    };
+    match expand_word(state, &env) {
+        Ok(path) if path.is_empty() => {}
+        Ok(path) => {
+            run_file(state, Path::new(&path), &FdTable::standard());
+        }
+        Err(e) => eprintln!("{}: {}", state.name, e),
+    }
+}
+
+/// Runs the commands in the file at `path` in the current shell, as `.`
+/// does, returning the status of the last one.
+pub fn run_file(state: &mut ShellState, path: &Path, fds: &FdTable) -> i32 {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: {}: {}",
+                state.name,
+                path.display(),
+                redirect::describe(&e)
+            );
+            return 1;
+        }
+    };
+    match parse_commands(state, &text, true) {
+        Ok(list) if list.items.is_empty() => 0,
+        Ok(list) => {
+            // its lines count from the top of the file
+            let line_offset = std::mem::replace(&mut state.line_offset, 0);
+            let status = run_list(state, &list, fds);
+            state.line_offset = line_offset;
+            status
+        }
+        Err(e) => {
+            let _ = writeln!(fds.stderr(), "{}: {}: {}", state.name, path.display(), e);
+            2
+        }
+    }
+}
+
+fn read_and_run(
+    state: &mut ShellState,
+    mut read_line: impl FnMut(&mut String) -> io::Result<usize>,
+    interactive: bool,
+) -> i32 {
+    let mut pending = String::new();
+    // lines read so far, and the line the pending command began on
+    let (mut lines, mut first_line) = (0, 1);
+    loop {
+        #[cfg(unix)]
+        {
+            run_pending_traps(state);
+            if interactive && pending.is_empty() {
+                notify_jobs(state);
+                state.sync_window_size();
+            }
+        }
+        let mut line = String::new();
+        #[cfg(unix)]
+        if interactive && io::stdin().is_terminal() {
+            use crate::editor::{self, Input};
+            match editor::read_line(state, &prompt::render(state, !pending.is_empty())) {
+                Ok(Input::Line(text)) => line = text,
+                Ok(Input::Interrupted) => {
+                    // the whole unfinished command goes, not just this line
+                    pending.clear();
+                    state.last_status = 130;
+                    continue;
+                }
+                Ok(Input::Eof) | Err(_) => {}
+            }
+        } else {
+            read_plain(
+                state,
+                &mut read_line,
+                &mut line,
+                interactive,
+                pending.is_empty(),
+            );
+        }
+        #[cfg(not(unix))]
+        read_plain(
+            state,
+            &mut read_line,
+            &mut line,
+            interactive,
+            pending.is_empty(),
+        );
+        if line.is_empty() {
+            if !pending.is_empty() {
+                // what the command still lacks at the end of the input
+                let comments = !interactive || state.options.interactive_comments;
+                if let Err(e) = parse_commands(state, &pending, comments) {
+                    eprintln!("{}: {}", state.name, e);
+                }
+                return 2;
+            }
+            return state.last_status;
+        }
+        if state.options.histexpand {
+            match state.history.expand(&line) {
+                Ok(Some(expanded)) => {
+                    // show what is actually about to run
+                    eprint!("{}", expanded);
+                    line = expanded;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("{}: {}", state.name, e);
+                    state.last_status = 1;
+                    pending.clear();
+                    continue;
+                }
+            }
+        }
+        lines += 1;
+        if pending.is_empty() {
+            first_line = lines;
+        }
+        pending.push_str(&line);
+        if !line.ends_with('\n') {
+            pending.push('\n');
+        }
+
+        let comments = !interactive || state.options.interactive_comments;
+        let parsed = parse_commands(state, &pending, comments);
+        if interactive && !parsed.as_ref().is_err_and(ParseError::is_incomplete) {
+            state.history.push(&pending);
+        }
+        match parsed {
+            Err(e) if e.is_incomplete() => continue,
+            Err(e) => {
+                // an interactive shell's lines aren't worth counting
+                let e = if interactive {
+                    e
+                } else {
+                    e.counted_from_line(first_line)
+                };
+                eprintln!("{}: {}", state.name, e);
+                state.last_status = 2;
+            }
+            Ok(list) if state.parse_only => println!("{:#?}", list),
+            Ok(list) => {
+                #[cfg(unix)]
+                crate::sys::clear_interrupted();
+                let exit_warned = state.exit_warned;
+                state.line_offset = first_line - 1;
+                run_commands(state, &list, &FdTable::standard(), Mode::Inherit);
+                // the warning only holds for the very next command
+                if exit_warned {
+                    state.exit_warned = false;
+                }
+                if state.flow == Some(Flow::Interrupt) {
+                    state.flow = None;
+                    state.last_status = 130;
+                }
+            }
+        }
+        pending.clear();
+        if let Some(Flow::Exit(code)) = state.flow {
+            return code;
+        }
+    }
+}
+
+/// Runs the commands in `text` in the current shell, as the command of a
+/// trap or a callback is run, returning the status of the last one. Only
+/// external commands start a process, so batches of builtins run here
+/// don't pay for one each.
+pub fn run_text(state: &mut ShellState, text: &str, fds: &FdTable) -> i32 {
+    match parse_commands(state, text, true) {
+        Ok(list) => run_list(state, &list, fds),
+        Err(e) => {
+            let _ = writeln!(fds.stderr(), "{}: {}", state.name, e);
+            2
+        }
+    }
+}
+
+/// Parses commands the shell is about to run, expanding aliases if that's
+/// turned on.
+fn parse_commands(state: &ShellState, text: &str, comments: bool) -> Result<List, ParseError> {
+    match (state.options.expand_aliases, comments) {
+        (true, _) => parser::parse_with_aliases(text, comments, &state.aliases, &state.name),
+        (false, true) => parser::parse(text),
+        (false, false) => parser::parse_without_comments(text),
+    }
+}
+
+/// Reads a line the plain way, after printing the prompt if interactive.
+fn read_plain(
+    state: &ShellState,
+    read_line: &mut impl FnMut(&mut String) -> io::Result<usize>,
+    line: &mut String,
+    interactive: bool,
+    first: bool,
+) {
+    if interactive {
+        print!("{}", prompt::displayed(&prompt::render(state, !first)));
+        let _ = io::stdout().flush();
+    }
+    if read_line(line).is_err() {
+        line.clear();
+    }
+}
+
+/// Runs the traps of the signals that arrived since the last call.
+#[cfg(unix)]
+fn run_pending_traps(state: &mut ShellState) {
+    for sig in crate::sys::take_trapped() {
+        if let Some(action) = state.traps.get(&sig).cloned() {
+            run_trap(state, &action);
+        }
+    }
+}
+
+/// Whether Ctrl-C interrupted an interactive shell's command line, which
+/// is then given up on.
+fn interrupted(state: &mut ShellState) -> bool {
+    #[cfg(unix)]
+    if state.interactive && crate::sys::interrupted() {
+        state.flow.get_or_insert(Flow::Interrupt);
+    }
+    state.flow == Some(Flow::Interrupt)
+}
+
+/// Gives a forked child the default SIGINT, so that Ctrl-C stops it the
+/// way it stops any command, unless `trap '' INT` ignores it.
+#[cfg(unix)]
+fn default_interrupt(state: &ShellState) {
+    use crate::sys;
+
+    if !state.traps.get(&sys::SIGINT).is_some_and(String::is_empty) {
+        sys::default_signal(sys::SIGINT);
+    }
+}
+
+/// Runs a trap's command, leaving `$?` as it was unless it exits.
+fn run_trap(state: &mut ShellState, action: &str) {
+    let status = state.last_status;
+    run_text(state, action, &FdTable::standard());
+    if !matches!(state.flow, Some(Flow::Exit(_))) {
+        state.last_status = status;
+    }
+}
+
+/// Prints the jobs that finished or stopped since the last call, then
+/// forgets the finished ones.
+#[cfg(unix)]
+fn notify_jobs(state: &mut ShellState) {
+    // no SIGCHLD since last time means no job changed
+    if !crate::sys::take_child_changed() {
+        return;
+    }
+    for id in state.jobs.poll() {
+        eprintln!("{}", state.jobs.format(id));
+    }
+    state.jobs.remove_done();
+}
+
+/// How [`run_commands`] treats the output of what it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Written wherever `fds` sends it, the shell's own stdout by default.
+    Inherit,
+    /// Collected into the outcome, as for `$(...)`. The commands run in a
+    /// subshell, so nothing they do reaches the caller.
+    Capture,
+}
+
+/// What running commands gave.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub status: i32,
+    /// The standard output, with [`Mode::Capture`].
+    pub output: Option<String>,
+}
+
+pub fn run_commands(state: &mut ShellState, list: &List, fds: &FdTable, mode: Mode) -> Outcome {
+    match mode {
+        Mode::Inherit => Outcome {
+            status: run_list(state, list, fds),
+            output: None,
+        },
+        Mode::Capture => {
+            let (status, output) = capture(state, list, fds);
+            Outcome {
+                status,
+                output: Some(output),
+            }
+        }
+    }
+}
+
+/// Runs `list` in a forked subshell with its stdout on a pipe, reading
+/// everything it writes.
+#[cfg(unix)]
+fn capture(state: &mut ShellState, list: &List, fds: &FdTable) -> (i32, String) {
+    use crate::sys::{self, Fork};
+    use std::io::Read;
+
+    let (mut reader, writer) = match redirect::pipe() {
+        Ok(pipe) => pipe,
+        Err(e) => {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: pipe: {}",
+                state.name,
+                redirect::describe(&e)
+            );
+            return (1, String::new());
+        }
+    };
+    let _ = io::stdout().flush();
+    let mut inner_fds = fds.clone();
+    inner_fds.set(1, FdTarget::File(Rc::new(writer)));
+    match sys::fork_process() {
+        Ok(Fork::Child) => {
+            sys::default_sigpipe();
+            default_interrupt(state);
+            drop(reader);
+            let mut status = run_list(state, list, &inner_fds);
+            if let Some(Flow::Exit(code)) = state.flow {
+                status = code;
+            }
+            let _ = inner_fds.stdout().flush();
+            process::exit(status);
+        }
+        Ok(Fork::Parent(pid)) => {
+            // the child's copy is the only writer left, so its exit ends the read
+            drop(inner_fds);
+            let mut output = Vec::new();
+            let _ = reader.read_to_end(&mut output);
+            let status = sys::wait_pid(pid).map_or(1, exit_code);
+            (status, String::from_utf8_lossy(&output).into_owned())
+        }
+        Err(e) => {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: fork: {}",
+                state.name,
+                redirect::describe(&e)
+            );
+            (1, String::new())
+        }
+    }
+}
+
+/// Without fork, `list` runs in a subshell copy of the state with its
+/// stdout spooled to a temporary file, which is read back afterwards.
+#[cfg(not(unix))]
+fn capture(state: &mut ShellState, list: &List, fds: &FdTable) -> (i32, String) {
+    let spool = env::temp_dir().join(format!("rust-cli-capture-{}", process::id()));
+    let file = match File::create(&spool) {
+        Ok(file) => file,
+        Err(e) => {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: {}: {}",
+                state.name,
+                spool.display(),
+                redirect::describe(&e)
+            );
+            return (1, String::new());
+        }
+    };
+    let mut inner_fds = fds.clone();
+    inner_fds.set(1, FdTarget::File(Rc::new(file)));
+    let status = in_subshell(state, |sub| run_list(sub, list, &inner_fds));
+    drop(inner_fds);
+    let output = fs::read(&spool).unwrap_or_default();
+    let _ = fs::remove_file(&spool);
+    (status, String::from_utf8_lossy(&output).into_owned())
+}
+
+/// The output of `$(text)` or `` `text` ``, without its trailing newlines.
+/// `$?` becomes its status.
+pub fn substitute(state: &mut ShellState, text: &str) -> Result<String, String> {
+    let list = parse_commands(state, text, true).map_err(|e| e.to_string())?;
+    // its lines count from the line of the command it's in
+    let line_offset = std::mem::replace(&mut state.line_offset, state.lineno.saturating_sub(1));
+    let outcome = run_commands(state, &list, &FdTable::standard(), Mode::Capture);
+    state.line_offset = line_offset;
+    state.last_status = outcome.status;
+    state.substitution_status = Some(outcome.status);
+    let mut output = outcome.output.unwrap_or_default();
+    output.truncate(output.trim_end_matches('\n').len());
+    Ok(output)
+}
+
+pub fn run_list(state: &mut ShellState, list: &List, fds: &FdTable) -> i32 {
+    let mut status = state.last_status;
+    for item in &list.items {
+        status = match item.background {
+            true => run_background(state, item, fds),
+            false => run_and_or(state, &item.and_or, fds),
+        };
+        #[cfg(unix)]
+        run_pending_traps(state);
+        if interrupted(state) || state.flow.is_some() {
+            break;
+        }
+    }
+    status
+}
+
+fn run_and_or(state: &mut ShellState, and_or: &AndOr, fds: &FdTable) -> i32 {
+    // only the command after the final `&&`/`||` can trigger `set -e`
+    let last = and_or.rest.len();
+    state.errexit_exempt += u32::from(last > 0);
+    let mut status = run_pipeline(state, &and_or.first, fds);
+    state.errexit_exempt -= u32::from(last > 0);
+    let mut ran_last = last == 0;
+    for (i, (connector, cmd)) in and_or.rest.iter().enumerate() {
+        if state.flow.is_some() {
+            break;
+        }
+        let run = match connector {
+            Connector::And => status == 0,
+            Connector::Or => status != 0,
+        };
+        if run {
+            ran_last = i + 1 == last;
+            state.errexit_exempt += u32::from(!ran_last);
+            status = run_pipeline(state, cmd, fds);
+            state.errexit_exempt -= u32::from(!ran_last);
+        }
+    }
+    let negated = and_or
+        .rest
+        .last()
+        .map_or(&and_or.first, |(_, last)| last)
+        .negated;
+    if ran_last && !negated && status != 0 && state.options.errexit && state.errexit_exempt == 0 {
+        state.flow.get_or_insert(Flow::Exit(status));
+    }
+    status
+}
+
+/// Forks `item` off as a job in its own process group and goes on without
+/// waiting for it. Without job control its stdin is `/dev/null`, so it
+/// can't steal the script's input.
+#[cfg(unix)]
+fn run_background(state: &mut ShellState, item: &ListItem, fds: &FdTable) -> i32 {
+    use crate::jobs::JobTable;
+    use crate::sys::{self, Fork};
+
+    let _ = io::stdout().flush();
+    match sys::fork_process() {
+        Ok(Fork::Child) => {
+            sys::set_process_group(0, 0);
+            sys::default_sigpipe();
+            sys::default_signal(sys::SIGTTOU);
+            let mut fds = fds.clone();
+            if !state.interactive {
+                if let Ok(null) = File::open("/dev/null") {
+                    fds.set(0, FdTarget::File(Rc::new(null)));
+                }
+            }
+            state.interactive = false;
+            state.job_control = false;
+            state.jobs = JobTable::default();
+            let mut status = run_and_or(state, &item.and_or, &fds);
+            if let Some(Flow::Exit(code)) = state.flow {
+                status = code;
+            }
+            let _ = io::stdout().flush();
+            process::exit(status);
+        }
+        Ok(Fork::Parent(pid)) => {
+            // set here too, so it holds before either side runs on
+            sys::set_process_group(pid, pid);
+            let id = state.jobs.add(pid, vec![pid], item.and_or.to_string());
+            state.last_background = Some(pid);
+            if state.interactive {
+                eprintln!("[{}] {}", id, pid);
+            }
+            0
+        }
+        Err(e) => {
+            let _ = writeln!(
+                fds.stderr(),
+                "{}: fork: {}",
+                state.name,
+                redirect::describe(&e)
+            );
+            1
+        }
+    }
+}
+
+/// Without fork there are no background jobs; the command runs to
+/// completion first.
+#[cfg(not(unix))]
+fn run_background(state: &mut ShellState, item: &ListItem, fds: &FdTable) -> i32 {
+    run_and_or(state, &item.and_or, fds);
+    0
+}
+
+fn run_pipeline(state: &mut ShellState, pipeline: &Pipeline, fds: &FdTable) -> i32 {
+    // a negated command failing is what was asked for, so `set -e` ignores
+    // it and anything in it
+    state.errexit_exempt += u32::from(pipeline.negated);
+    let start = pipeline.time.map(|_| timing::Start::now());
+    let status = match pipeline.commands.as_slice() {
+        [] => 0,
+        [cmd] => run_command(state, cmd, fds),
+        cmds => run_stages(state, cmds, fds),
+    };
+    if let (Some(timing), Some(start)) = (pipeline.time, start) {
+        report_time(state, timing, &start.elapsed(), fds);
+    }
+    state.errexit_exempt -= u32::from(pipeline.negated);
+    let status = match pipeline.negated {
+        true => i32::from(status == 0),
+        false => status,
+    };
+    state.last_status = status;
+    status
+}
+
+/// Writes what `time` reports to stderr. An empty `TIMEFORMAT` asks for
+/// no report at all.
+fn report_time(state: &ShellState, timing: Timing, times: &timing::Times, fds: &FdTable) {
+    let format = match timing {
+        Timing::Posix => timing::POSIX_FORMAT,
+        Timing::Format => state
+            .get_var("TIMEFORMAT")
+            .unwrap_or(timing::DEFAULT_FORMAT),
+    };
+    if !format.is_empty() {
+        let _ = writeln!(fds.stderr(), "{}", timing::format(format, times));
+    }
+}
+
+/// Forks every stage of a pipeline so they all run at once, each one's
+/// stdout piped into the next one's stdin. Being separate processes, a
+/// stage's `exit`, `cd` or assignments never reach the shell itself. The
+/// status is the last stage's.
+#[cfg(unix)]
+fn run_stages(state: &mut ShellState, cmds: &[Command], fds: &FdTable) -> i32 {
+    use crate::sys::{self, Fork};
+
+    let _ = io::stdout().flush();
+    let mut pids = Vec::new();
+    let mut next_input: Option<File> = None;
+    for (i, cmd) in cmds.iter().enumerate() {
+        let mut stage_fds = fds.clone();
+        if let Some(input) = next_input.take() {
+            stage_fds.set(0, FdTarget::File(Rc::new(input)));
+        }
+        if i + 1 < cmds.len() {
+            match redirect::pipe() {
+                Ok((reader, writer)) => {
+                    stage_fds.set(1, FdTarget::File(Rc::new(writer)));
+                    next_input = Some(reader);
+                }
+                Err(e) => {
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: pipe: {}",
+                        state.name,
+                        redirect::describe(&e)
+                    );
+                    break;
+                }
+            }
+        }
+        match sys::fork_process() {
+            Ok(Fork::Child) => {
+                sys::default_sigpipe();
+                default_interrupt(state);
+                // the next stage's end; keeping it would stop this stage
+                // from ever seeing its reader go away
+                drop(next_input);
+                let mut status = run_command(state, cmd, &stage_fds);
+                if let Some(Flow::Exit(code)) = state.flow {
+                    status = code;
+                }
+                let _ = io::stdout().flush();
+                process::exit(status);
+            }
+            Ok(Fork::Parent(pid)) => pids.push(pid),
+            Err(e) => {
+                let _ = writeln!(
+                    fds.stderr(),
+                    "{}: fork: {}",
+                    state.name,
+                    redirect::describe(&e)
+                );
+                break;
+            }
+        }
+        // dropping `stage_fds` closes the shell's copies of this stage's pipe ends
+    }
+
+    let mut status = 1;
+    for pid in pids {
+        status = sys::wait_pid(pid).map_or(1, exit_code);
+    }
+    status
+}
+
+/// Without fork, stages run one after another in this process, each in a
+/// subshell copy of the state and with its output spooled to a temporary
+/// file that becomes the next one's input.
+#[cfg(not(unix))]
+fn run_stages(state: &ShellState, cmds: &[Command], fds: &FdTable) -> i32 {
+    use std::fs::{self, OpenOptions};
+    use std::io::{Seek, SeekFrom};
+
+    let spool = env::temp_dir().join(format!("rust-cli-pipe-{}", process::id()));
+    let mut spooled = Vec::new();
+    let mut status = 0;
+    let mut input: Option<File> = None;
+    for (i, cmd) in cmds.iter().enumerate() {
+        let mut stage_fds = fds.clone();
+        if let Some(input) = input.take() {
+            stage_fds.set(0, FdTarget::File(Rc::new(input)));
+        }
+        let mut output = None;
+        if i + 1 < cmds.len() {
+            let path = spool.with_extension(i.to_string());
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path);
+            match file.and_then(|f| Ok((f.try_clone()?, f))) {
+                Ok((reader, writer)) => {
+                    stage_fds.set(1, FdTarget::File(Rc::new(writer)));
+                    output = Some(reader);
+                    spooled.push(path);
+                }
+                Err(e) => {
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: pipe: {}",
+                        state.name,
+                        redirect::describe(&e)
+                    );
+                    status = 1;
+                    break;
+                }
+            }
+        }
+        status = in_subshell(state, |sub| run_command(sub, cmd, &stage_fds));
+        if let Some(mut reader) = output {
+            let _ = reader.seek(SeekFrom::Start(0));
+            input = Some(reader);
+        }
+    }
+    // files can't be removed while open on every platform
+    drop(input);
+    for path in spooled {
+        let _ = fs::remove_file(path);
+    }
+    status
+}
+
+/// Runs the condition of an `if` or loop, where failure is expected and
+/// must not trigger `set -e`.
+fn run_condition(state: &mut ShellState, cond: &List, fds: &FdTable) -> i32 {
+    state.errexit_exempt += 1;
+    let status = run_list(state, cond, fds);
+    state.errexit_exempt -= 1;
+    status
+}
+
+fn run_command(state: &mut ShellState, cmd: &Command, fds: &FdTable) -> i32 {
+    let status = match cmd {
+        Command::Simple(simple) => run_simple(state, simple, fds),
+        Command::Compound(compound, redirects) => match redirected(state, fds, redirects) {
+            Some(fds) => run_compound(state, compound, &fds),
+            None => 1,
+        },
+        Command::Function(name, body) => {
+            state.functions.insert(name.clone(), Rc::clone(body));
+            0
+        }
+    };
+    state.last_status = status;
+    status
+}
+
+/// Runs the function `name`, if there is one, with `args` as the
+/// positional parameters until it's done.
+pub fn call_function(
+    state: &mut ShellState,
+    name: &str,
+    args: &[String],
+    fds: &FdTable,
+) -> Option<i32> {
+    let body = Rc::clone(state.functions.get(name)?);
+    Some(run_function(state, &body, args, fds))
+}
+
+/// Runs a function's `body` with `args` as the positional parameters.
+/// `return` leaves it early, and a `break` inside can't reach a loop
+/// around the call.
+fn run_function(state: &mut ShellState, body: &Command, args: &[String], fds: &FdTable) -> i32 {
+    let positional = std::mem::replace(&mut state.positional, args.to_vec());
+    let loop_depth = std::mem::take(&mut state.loop_depth);
+    state.function_depth += 1;
+    let mut status = run_command(state, body, fds);
+    state.function_depth -= 1;
+    state.loop_depth = loop_depth;
+    state.positional = positional;
+    if let Some(Flow::Return(code)) = state.flow {
+        state.flow = None;
+        status = code;
+    }
+    status
+}
+
+fn run_compound(state: &mut ShellState, cmd: &CompoundCommand, fds: &FdTable) -> i32 {
+    match cmd {
+        CompoundCommand::Subshell(body) => run_subshell(state, body, fds),
+        CompoundCommand::Group(body) => run_list(state, body, fds),
+        CompoundCommand::If {
+            branches,
+            otherwise,
+        } => {
+            for (cond, body) in branches {
+                let status = run_condition(state, cond, fds);
+                if state.flow.is_some() {
+                    return status;
+                }
+                if status == 0 {
+                    return run_list(state, body, fds);
+                }
+            }
+            match otherwise {
+                Some(body) => run_list(state, body, fds),
+                // no branch taken
+                None => 0,
+            }
+        }
+        CompoundCommand::While { until, cond, body } => run_loop(state, fds, body, |state, fds| {
+            let status = run_condition(state, cond, fds);
+            (status == 0) != *until
+        }),
+        CompoundCommand::For { var, words, body } => {
+            let values = match words.as_ref().map(|words| expand_words(state, words)) {
+                Some(Ok(values)) => values,
+                Some(Err(e)) => return expansion_failed(state, fds, &e),
+                None => Vec::new(),
+            };
+            let mut values = values.into_iter();
+            run_loop(state, fds, body, |state, fds| match values.next() {
+                Some(value) => match state.assign(var, value) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        let _ = writeln!(fds.stderr(), "{}: {}", state.name, e);
+                        false
+                    }
+                },
+                None => false,
+            })
+        }
+        CompoundCommand::Case { word, items } => run_case(state, word, items, fds),
+        CompoundCommand::Conditional(expr) => match conditional::eval(state, expr, fds) {
+            Ok(status) => status,
+            Err(e) => expansion_failed(state, fds, &e),
+        },
+    }
+}
+
+/// Runs `body` while `next` says to go on. The status is that of the last
+/// body run, or 0 if it never ran.
+fn run_loop(
+    state: &mut ShellState,
+    fds: &FdTable,
+    body: &List,
+    mut next: impl FnMut(&mut ShellState, &FdTable) -> bool,
+) -> i32 {
+    let mut status = 0;
+    state.loop_depth += 1;
+    loop {
+        let go_on = next(state, fds);
+        if !go_on || state.flow.is_some() {
+            break;
+        }
+        status = run_list(state, body, fds);
+        match state.flow {
+            Some(Flow::Break(n)) => {
+                state.flow = (n > 1).then_some(Flow::Break(n - 1));
+                break;
+            }
+            Some(Flow::Continue(n)) if n > 1 => {
+                state.flow = Some(Flow::Continue(n - 1));
+                break;
+            }
+            Some(Flow::Continue(_)) => state.flow = None,
+            Some(Flow::Exit(_) | Flow::Return(_) | Flow::Interrupt) => break,
+            None => {}
+        }
+    }
+    state.loop_depth -= 1;
+    status
+}
+
+fn run_case(state: &mut ShellState, word: &str, items: &[CaseItem], fds: &FdTable) -> i32 {
+    let text = match expand_word(state, word) {
+        Ok(text) => text,
+        Err(e) => return expansion_failed(state, fds, &e),
+    };
+    for item in items {
+        for pattern in &item.patterns {
+            let pattern = match expand_pattern(state, pattern) {
+                Ok(pattern) => pattern,
+                Err(e) => return expansion_failed(state, fds, &e),
+            };
+            if glob_match(&pattern, &text) {
+                return run_list(state, &item.body, fds);
+            }
+        }
+    }
+    // no pattern matched
+    0
+}
+
+/// Applies `redirects` on top of `fds`, reporting failures on stderr.
+fn redirected(state: &mut ShellState, fds: &FdTable, redirects: &[Redirect]) -> Option<FdTable> {
+    if redirects.is_empty() {
+        return Some(fds.clone());
+    }
+    let mut targets = Vec::new();
+    for r in redirects {
+        let target = match &r.body {
+            Some(body) if r.literal_body() => Ok(body.clone()),
+            Some(body) => expand_here_doc(state, body),
+            None => expand_word(state, &r.target),
+        };
+        match target {
+            Ok(target) => targets.push((r.clone(), target)),
+            Err(e) => {
+                expansion_failed(state, fds, &e);
+                return None;
+            }
+        }
+    }
+    match fds.with_redirects(&targets) {
+        Ok(fds) => Some(fds),
+        Err(e) => {
+            let _ = writeln!(fds.stderr(), "{}: {}", state.name, e);
+            None
+        }
+    }
+}
+
+fn run_subshell(state: &ShellState, body: &List, fds: &FdTable) -> i32 {
+    in_subshell(state, |sub| run_list(sub, body, fds))
+}
+
+/// Runs `f` against a copy of the state, so assignments, `cd` and `exit`
+/// inside don't reach the caller. The working directory is process-wide,
+/// so it is put back once `f` is done.
+fn in_subshell(state: &ShellState, f: impl FnOnce(&mut ShellState) -> i32) -> i32 {
+    let saved_dir = env::current_dir().ok();
+    let mut sub = state.clone();
+    let mut status = f(&mut sub);
+    if let Some(Flow::Exit(code)) = sub.flow {
+        status = code;
+    }
+    if let Some(dir) = saved_dir {
+        let _ = env::set_current_dir(dir);
+    }
+    status
+}
+
+fn run_simple(state: &mut ShellState, cmd: &SimpleCommand, fds: &FdTable) -> i32 {
+    state.substitution_status = None;
+    state.lineno = state.line_offset + cmd.line;
+    let args = match expand_words(state, &cmd.words) {
+        Ok(args) => args,
+        Err(e) => return expansion_failed(state, fds, &e),
+    };
+    // a substitution stopped by Ctrl-C takes its command with it
+    if interrupted(state) {
+        return state.substitution_status.unwrap_or(130);
+    }
+    // assigned left to right, so `A=1 B=$A cmd` gives `cmd` B=1; with a
+    // command they only last for its duration
+    let mut assignments: Vec<(&str, String)> = Vec::new();
+    let mut saved = Vec::new();
+    for a in &cmd.assignments {
+        let mut value = match expand_assignment(state, &a.value) {
+            Ok(value) => value,
+            Err(e) => {
+                if !args.is_empty() {
+                    restore_vars(state, saved);
+                }
+                return expansion_failed(state, fds, &e);
+            }
+        };
+        if let Some(subscript) = &a.subscript {
+            saved.push((a.name.as_str(), state.var(&a.name).cloned()));
+            if let Err(e) = assign_element(state, &a.name, subscript, a.append, value) {
+                let _ = writeln!(fds.stderr(), "{}: {}", state.name, e);
+                if !args.is_empty() {
+                    restore_vars(state, saved);
+                }
+                return assignment_failed(state, &args);
+            }
+            continue;
+        }
+        if a.append {
+            value = state.appended_value(&a.name, value);
+        }
+        let value = match state.assignment_value(&a.name, value) {
+            Ok(value) => value,
+            Err(e) => {
+                let _ = writeln!(fds.stderr(), "{}: {}", state.name, e);
+                if !args.is_empty() {
+                    restore_vars(state, saved);
+                }
+                return assignment_failed(state, &args);
+            }
+        };
+        saved.push((a.name.as_str(), state.var(&a.name).cloned()));
+        state.set_var(&a.name, value.clone());
+        assignments.push((a.name.as_str(), value));
+    }
+    let Some((name, rest)) = args.split_first() else {
+        // `x=$(cmd)` has the status of `cmd`
+        return match redirected(state, fds, &cmd.redirects) {
+            Some(_) => state.substitution_status.take().unwrap_or(0),
+            None => 1,
+        };
+    };
+    let status = match redirected(state, fds, &cmd.redirects) {
+        None => 1,
+        #[cfg(unix)]
+        Some(fds) if name == "exec" && rest.is_empty() => {
+            let redirected: Vec<i32> = cmd
+                .redirects
+                .iter()
+                .flat_map(|r| match r.op {
+                    RedirOp::OutBoth | RedirOp::AppendBoth => vec![1, 2],
+                    _ => vec![r.fd],
+                })
+                .collect();
+            match fds.make_permanent(&redirected) {
+                Ok(()) => {
+                    for fd in redirected.into_iter().filter(|&fd| fd > 2) {
+                        match crate::sys::fd_is_open(fd) {
+                            true => state.exec_fds.insert(fd),
+                            false => state.exec_fds.remove(&fd),
+                        };
+                    }
+                    0
+                }
+                Err(e) => {
+                    let _ = writeln!(
+                        fds.stderr(),
+                        "{}: exec: {}",
+                        state.name,
+                        redirect::describe(&e)
+                    );
+                    1
+                }
+            }
+        }
+        Some(fds) if state.functions.contains_key(name) => {
+            call_function(state, name, rest, &fds).unwrap_or(1)
+        }
+        Some(fds) => match builtins::find(name) {
+            Some(builtin) => {
+                let status = builtin(state, rest, &fds);
+                let _ = fds.stdout().flush();
+                status
+            }
+            None => run_external(state, name, rest, &assignments, &fds),
+        },
+    };
+    restore_vars(state, saved);
+    status
+}
+
+/// Reports an expansion that failed, like `${VAR:?}` on an unset `VAR`.
+/// A script gives up entirely; an interactive shell goes on with the next
+/// command.
+fn expansion_failed(state: &mut ShellState, fds: &FdTable, e: &str) -> i32 {
+    let _ = writeln!(fds.stderr(), "{}: {}", state.name, e);
+    if !state.interactive {
+        state.flow = Some(Flow::Exit(1));
+    }
+    1
+}
+
+/// The status of an assignment that failed, as to a readonly variable.
+/// In POSIX mode one with no command stops a script.
+fn assignment_failed(state: &mut ShellState, args: &[String]) -> i32 {
+    if state.options.posix && !state.interactive && args.is_empty() {
+        state.flow = Some(Flow::Exit(1));
+    }
+    1
+}
+
+/// `name[subscript]=value`, or with `append` `name[subscript]+=value`.
+fn assign_element(
+    state: &mut ShellState,
+    name: &str,
+    subscript: &str,
+    append: bool,
+    value: String,
+) -> Result<(), String> {
+    let subscript = expand_subscript(state, subscript)?;
+    let value = match state.element(name, &subscript)? {
+        Some(current) if append => current.to_string() + &value,
+        _ => value,
+    };
+    state.assign_element(name, &subscript, value)
+}
+
+/// Puts back variables saved before prefix assignments, latest first so
+/// a name assigned twice gets its original value.
+fn restore_vars(state: &mut ShellState, saved: Vec<(&str, Option<Variable>)>) {
+    for (name, var) in saved.into_iter().rev() {
+        state.restore_var(name, var);
+    }
+}
+
+/// Runs `command_not_found_handle`, if it's defined, for a command that
+/// isn't on `PATH`: in a subshell, as bash does, with the command's name
+/// and arguments for its own. Its status is the command's. A command
+/// missing inside the handler is just reported, rather than handled again.
+fn command_not_found(
+    state: &ShellState,
+    name: &str,
+    args: &[String],
+    fds: &FdTable,
+) -> Option<i32> {
+    const HANDLER: &str = "command_not_found_handle";
+    let body = Rc::clone(state.functions.get(HANDLER)?);
+    let args: Vec<String> = std::iter::once(name.to_string())
+        .chain(args.iter().cloned())
+        .collect();
+    Some(in_subshell(state, |sub| {
+        sub.functions.remove(HANDLER);
+        run_function(sub, &body, &args, fds)
+    }))
+}
+
+pub fn run_external(
+    state: &ShellState,
+    name: &str,
+    args: &[String],
+    assignments: &[(&str, String)],
+    fds: &FdTable,
+) -> i32 {
+    // a `PATH=...` or `PATHEXT=...` prefix applies to finding the command,
+    // too
+    let assigned = |var: &str| assignments.iter().find(|(k, _)| *k == var);
+    let search_var = |var: &str| match assigned(var) {
+        Some((_, value)) => Some(OsStr::new(value.as_str())),
+        None => state.search_var(var),
+    };
+    let found = match assigned("PATH").or(assigned("PATHEXT")) {
+        _ if name.contains('/') => Some(
+            executable_path(Path::new(name), search_var("PATHEXT"))
+                .unwrap_or_else(|| PathBuf::from(name)),
+        ),
+        Some(_) => find_in_path(name, search_var("PATH"), search_var("PATHEXT")),
+        None => state.find_command(name),
+    };
+    let Some(program) = found else {
+        if let Some(status) = command_not_found(state, name, args, fds) {
+            return status;
+        }
+        let _ = writeln!(fds.stderr(), "{}: {}: command not found", state.name, name);
+        return 127;
+    };
+    let result = (|| {
+        let mut cmd = process::Command::new(&program);
+        #[cfg(unix)]
+        unsafe {
+            use std::os::unix::process::CommandExt;
+            cmd.arg0(name);
+            // the shell ignores it for job control; the command shouldn't
+            cmd.pre_exec(|| {
+                crate::sys::default_signal(crate::sys::SIGTTOU);
+                Ok(())
+            });
+        }
+        cmd.args(args)
+            .env_clear()
+            .envs(state.exported_vars())
+            .envs(assignments.iter().map(|(k, v)| (*k, v.as_str())))
+            .stdin(fds.stdio(0)?)
+            .stdout(fds.stdio(1)?)
+            .stderr(fds.stdio(2)?)
+            .status()
+    })();
+    match result {
+        Ok(status) => exit_code(status),
+        Err(e) => {
+            let (problem, status) = match e.kind() {
+                // a name looked up on `PATH` was found, so only a path can be missing
+                io::ErrorKind::NotFound if !name.contains('/') => {
+                    ("command not found".to_string(), 127)
+                }
+                io::ErrorKind::NotFound => (redirect::describe(&e), 127),
+                _ if program.is_dir() => ("is a directory".to_string(), 126),
+                _ => (redirect::describe(&e), 126),
+            };
+            let _ = writeln!(fds.stderr(), "{}: {}: {}", state.name, name, problem);
+            status
+        }
+    }
+}
+
+pub fn exit_code(status: process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(sig) = status.signal() {
+            return 128 + sig;
+        }
+    }
+    status.code().unwrap_or(1)
+}
@@ -0,0 +1,1008 @@
+use crate::exec;
+use crate::glob::{glob, glob_match, has_glob_chars};
+use crate::lexer::split_quoted_line;
+use crate::parser::is_valid_name;
+use crate::path::expand_tilde;
+use crate::state::ShellState;
+
+/// Expands words into the final argument list, in bash's order: brace
+/// expansion, tilde, parameter and command expansion, field splitting, then
+/// pathname expansion and quote removal. So `{src,tests}/*.rs` becomes
+/// `src/*.rs` and `tests/*.rs` before either is globbed. POSIX mode has no
+/// brace expansion.
+pub fn expand_words(state: &mut ShellState, words: &[String]) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    for word in words {
+        let words = match state.options.posix {
+            true => vec![word.clone()],
+            false => expand_braces(word),
+        };
+        for word in words {
+            let requoted = requote(state, &word, true, assignment_value(&word))?;
+            fields.extend(
+                split_fields(&requoted)
+                    .iter()
+                    .flat_map(|field| expand_pathname(field)),
+            );
+        }
+    }
+    Ok(fields)
+}
+
+/// Expands a single word without field splitting, as for the value of an
+/// assignment.
+pub fn expand_word(state: &mut ShellState, word: &str) -> Result<String, String> {
+    Ok(split_quoted_line(&requote(state, word, false, None)?).concat())
+}
+
+/// Expands the value of a `NAME=value` assignment, where a tilde also
+/// expands after each `:`, as in `PATH=~/bin:~/local/bin`.
+pub fn expand_assignment(state: &mut ShellState, value: &str) -> Result<String, String> {
+    Ok(split_quoted_line(&requote(state, value, false, Some(0))?).concat())
+}
+
+/// Expands the body of a here-document whose delimiter isn't quoted.
+/// Parameters and commands are substituted as inside double quotes, with
+/// no splitting or globbing. A backslash only quotes `$`, `` ` `` and
+/// another backslash.
+pub fn expand_here_doc(state: &mut ShellState, body: &str) -> Result<String, String> {
+    let chars: Vec<char> = body.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => match chars.get(i + 1) {
+                Some(&c @ ('$' | '`' | '\\')) => {
+                    out.push(c);
+                    i += 2;
+                }
+                _ => {
+                    // ex: cat <<EOF with "a\"b"   -> the backslash stays
+                    out.push('\\');
+                    i += 1;
+                }
+            },
+            '$' if chars.get(i + 1) == Some(&'(') => {
+                let end = skip_parens(&chars, i + 1);
+                let inner: String = chars[i + 2..end.max(i + 3) - 1].iter().collect();
+                out.push_str(&exec::substitute(state, &inner)?);
+                i = end;
+            }
+            '`' => {
+                let end = skip_backquoted(&chars, i);
+                let inner = backquoted_command(&chars[i + 1..end.max(i + 2) - 1], false);
+                out.push_str(&exec::substitute(state, &inner)?);
+                i = end;
+            }
+            '$' => {
+                let (value, next) = parameter(state, &chars, i)?;
+                out.push_str(value.as_deref().unwrap_or("$"));
+                i = next;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Expands a word for use as a pattern, e.g. a `case` pattern. Quoted
+/// characters come out backslash-escaped so they only match themselves.
+pub fn expand_pattern(state: &mut ShellState, word: &str) -> Result<String, String> {
+    Ok(quotes_to_escapes(
+        &requote(state, word, false, None)?,
+        GLOB_CHARS,
+    ))
+}
+
+/// Expands a word for use as a regex, the right side of `=~`, with its
+/// quoted characters backslash-escaped like [`expand_pattern`] does.
+pub fn expand_regex(state: &mut ShellState, word: &str) -> Result<String, String> {
+    Ok(quotes_to_escapes(
+        &requote(state, word, false, None)?,
+        REGEX_CHARS,
+    ))
+}
+
+/// The characters special in a glob pattern, and those special in a regex.
+const GLOB_CHARS: &str = "*?[]\\";
+const REGEX_CHARS: &str = "*?[]\\.^$()|+{}";
+
+/// Where the value starts in an argument that looks like an assignment,
+/// e.g. `export X=~/dir`, whose tildes expand like an assignment's.
+fn assignment_value(word: &str) -> Option<usize> {
+    let (name, _) = word.split_once('=')?;
+    let valid = is_valid_name(name.strip_suffix('+').unwrap_or(name));
+    valid.then_some(name.len() + 1)
+}
+
+/// Substitutes expansions into `word` while keeping it valid input for
+/// [`split_quoted_line`], which then does field splitting and quote
+/// removal. Expanded text is escaped so it's taken literally; unquoted
+/// whitespace from an expansion is left bare when `split` is set so it
+/// separates fields. A tilde only expands at the start of the word, or,
+/// from `value_start` on, at the start of the assignment value and after
+/// each `:` in it.
+fn requote(
+    state: &mut ShellState,
+    word: &str,
+    split: bool,
+    value_start: Option<usize>,
+) -> Result<String, String> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut out = String::new();
+    let mut in_double = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let in_value = value_start.is_some_and(|v| i >= v);
+        let tilde_point = i == 0 || value_start == Some(i) || in_value && chars[i - 1] == ':';
+        if tilde_point && !in_double {
+            if let Some(end) = tilde_prefix_end(&chars[i..], in_value) {
+                let prefix: String = chars[i..i + end].iter().collect();
+                let home = state.home();
+                let expanded = expand_tilde(&prefix, home.as_deref());
+                push_escaped(&mut out, &expanded.to_string_lossy(), false);
+                i += end;
+                continue;
+            }
+        }
+        let ch = chars[i];
+        match ch {
+            '\'' if !in_double => {
+                // ex: echo '$HOME'              -> copied verbatim, no expansion
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&c| c == '\'')
+                    .map_or(chars.len(), |off| i + 1 + off + 1);
+                out.extend(&chars[i..end]);
+                i = end;
+            }
+            '"' => {
+                in_double = !in_double;
+                out.push(ch);
+                i += 1;
+            }
+            '\\' => {
+                match chars.get(i + 1) {
+                    // ex: echo "\$HOME"         -> literal $, and split_quoted_line
+                    //     would otherwise keep the backslash
+                    Some(&c @ ('$' | '`')) if in_double => out.push(c),
+                    Some(&c) => {
+                        out.push('\\');
+                        out.push(c);
+                    }
+                    None => out.push('\\'),
+                }
+                i += 2;
+            }
+            '$' if chars.get(i + 1) == Some(&'(') => {
+                let end = skip_parens(&chars, i + 1);
+                let inner: String = chars[i + 2..end.max(i + 3) - 1].iter().collect();
+                let output = exec::substitute(state, &inner)?;
+                match in_double {
+                    true => push_escaped(&mut out, &output, true),
+                    false => push_unquoted(&mut out, &output, split),
+                }
+                i = end;
+            }
+            '`' => {
+                let end = skip_backquoted(&chars, i);
+                let inner = backquoted_command(&chars[i + 1..end.max(i + 2) - 1], in_double);
+                let output = exec::substitute(state, &inner)?;
+                match in_double {
+                    true => push_escaped(&mut out, &output, true),
+                    false => push_unquoted(&mut out, &output, split),
+                }
+                i = end;
+            }
+            '$' if in_double && chars.get(i + 1) == Some(&'{') => {
+                let end = skip_braced(&chars, i + 1);
+                let body: String = chars[i + 2..end.max(i + 3) - 1].iter().collect();
+                match all_elements(state, &body).filter(|_| split) {
+                    // ex: "${arr[@]}"               -> a word per element
+                    Some(values) => {
+                        for (n, value) in values.iter().enumerate() {
+                            if n > 0 {
+                                out.push_str("\" \"");
+                            }
+                            push_escaped(&mut out, value, true);
+                        }
+                    }
+                    None => push_escaped(&mut out, &braced(state, &body)?, true),
+                }
+                i = end;
+            }
+            '$' => {
+                let (value, next) = parameter(state, &chars, i)?;
+                match value {
+                    Some(value) if in_double => push_escaped(&mut out, &value, true),
+                    Some(value) => push_unquoted(&mut out, &value, split),
+                    None => out.push('$'),
+                }
+                i = next;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Expands the first brace expression in `word`, `{a,b}` or a sequence
+/// like `{1..5}`, `{01..10}`, `{a..e}` or `{1..10..2}`, then the rest of
+/// every result. Braces that are quoted, part of `${...}`, or hold neither
+/// a comma nor a sequence are left as they are.
+fn expand_braces(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '\'' => i = skip_single(&chars, i),
+            '"' => i = skip_double(&chars, i),
+            '$' if chars.get(i + 1) == Some(&'{') => i = skip_braced(&chars, i + 1),
+            '$' if chars.get(i + 1) == Some(&'(') => i = skip_parens(&chars, i + 1),
+            '`' => i = skip_backquoted(&chars, i),
+            '{' => {
+                if let Some((alternatives, end)) = brace_alternatives(&chars, i) {
+                    let prefix: String = chars[..i].iter().collect();
+                    let suffix: String = chars[end..].iter().collect();
+                    return alternatives
+                        .iter()
+                        .flat_map(|alt| expand_braces(&format!("{}{}{}", prefix, alt, suffix)))
+                        .collect();
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    vec![word.to_string()]
+}
+
+/// The alternatives of the brace expression opening at `chars[start]` and
+/// the index past its `}`, if it is one.
+fn brace_alternatives(chars: &[char], start: usize) -> Option<(Vec<String>, usize)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    let mut i = start + 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '\'' => i = skip_single(chars, i) - 1,
+            '"' => i = skip_double(chars, i) - 1,
+            '$' if chars.get(i + 1) == Some(&'{') => i = skip_braced(chars, i + 1) - 1,
+            '$' if chars.get(i + 1) == Some(&'(') => i = skip_parens(chars, i + 1) - 1,
+            '`' => i = skip_backquoted(chars, i) - 1,
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            '}' => break,
+            ',' if depth == 0 => commas.push(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    if i >= chars.len() {
+        return None;
+    }
+    let end = i;
+    if commas.is_empty() {
+        let inner: String = chars[start + 1..end].iter().collect();
+        return brace_sequence(&inner).map(|seq| (seq, end + 1));
+    }
+    let mut bounds = vec![start];
+    bounds.extend(&commas);
+    bounds.push(end);
+    let alternatives = bounds
+        .windows(2)
+        .map(|w| chars[w[0] + 1..w[1]].iter().collect())
+        .collect();
+    Some((alternatives, end + 1))
+}
+
+/// Expands `x..y` or `x..y..step` between integers or single letters.
+fn brace_sequence(inner: &str) -> Option<Vec<String>> {
+    let parts: Vec<&str> = inner.split("..").collect();
+    let (from, to, step) = match parts[..] {
+        [from, to] => (from, to, 1),
+        [from, to, step] => (from, to, step.parse::<i64>().ok()?.unsigned_abs().max(1)),
+        _ => return None,
+    };
+    if let (Ok(a), Ok(b)) = (from.parse::<i64>(), to.parse::<i64>()) {
+        // `{01..10}` pads every number to the widest end
+        let padded = |s: &str| {
+            let digits = s.trim_start_matches('-');
+            digits.len() > 1 && digits.starts_with('0')
+        };
+        let width = if padded(from) || padded(to) {
+            from.len().max(to.len())
+        } else {
+            0
+        };
+        let values = range(a, b, step)?.map(|n| format!("{:0width$}", n, width = width));
+        return Some(values.collect());
+    }
+    let (mut a, mut b) = (from.chars(), to.chars());
+    match (a.next(), a.next(), b.next(), b.next()) {
+        (Some(a), None, Some(b), None) if a.is_ascii_alphabetic() && b.is_ascii_alphabetic() => {
+            let values = range(a as i64, b as i64, step)?.map(|n| (n as u8 as char).to_string());
+            Some(values.collect())
+        }
+        _ => None,
+    }
+}
+
+/// `from` to `to` inclusive, counting down if `to` is smaller, or `None`
+/// if there are too many numbers to count.
+fn range(from: i64, to: i64, step: u64) -> Option<impl Iterator<Item = i64>> {
+    let count = (from.abs_diff(to) / step).checked_add(1)?;
+    // wide enough that no step from either end can overflow
+    let step = if to < from {
+        -i128::from(step)
+    } else {
+        i128::from(step)
+    };
+    Some((0..count).map(move |k| (i128::from(from) + i128::from(k) * step) as i64))
+}
+
+fn skip_single(chars: &[char], i: usize) -> usize {
+    chars[i + 1..]
+        .iter()
+        .position(|&c| c == '\'')
+        .map_or(chars.len(), |off| i + 1 + off + 1)
+}
+
+fn skip_double(chars: &[char], mut i: usize) -> usize {
+    i += 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => return i + 1,
+            '$' if chars.get(i + 1) == Some(&'(') => i = skip_parens(chars, i + 1),
+            '`' => i = skip_backquoted(chars, i),
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// `i` is at the `(` of `$(`; returns the index past the matching `)`.
+fn skip_parens(chars: &[char], mut i: usize) -> usize {
+    let mut depth = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '\'' => i = skip_single(chars, i) - 1,
+            '"' => i = skip_double(chars, i) - 1,
+            '`' => i = skip_backquoted(chars, i) - 1,
+            '(' => depth += 1,
+            ')' if depth == 1 => return i + 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// `i` is at an opening `` ` ``; returns the index past the closing one.
+fn skip_backquoted(chars: &[char], mut i: usize) -> usize {
+    i += 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '`' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    chars.len()
+}
+
+/// The command inside `` `...` ``, where a backslash before `$`, `` ` ``
+/// or another backslash, or before `"` within double quotes, only quotes
+/// that character.
+fn backquoted_command(chars: &[char], in_double: bool) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars.get(i + 1) {
+            Some('$' | '`' | '\\') if chars[i] == '\\' => i += 1,
+            Some('"') if chars[i] == '\\' && in_double => i += 1,
+            _ => {}
+        }
+        out.push(chars[i]);
+        i += 1;
+    }
+    out
+}
+
+/// `i` is at the `{` of `${`; returns the index past the matching `}`.
+fn skip_braced(chars: &[char], mut i: usize) -> usize {
+    let mut depth = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' if depth == 1 => return i + 1,
+            '}' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// Splits requoted text on unquoted whitespace, keeping each field's
+/// quotes so pathname expansion can still tell quoted from unquoted.
+fn split_fields(text: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut cur = String::new();
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '\\' if !in_single => {
+                cur.push(ch);
+                if let Some(next) = chars.next() {
+                    cur.push(next);
+                }
+                continue;
+            }
+            c if c.is_ascii_whitespace() && !in_single && !in_double => {
+                if !cur.is_empty() {
+                    fields.push(std::mem::take(&mut cur));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        cur.push(ch);
+    }
+    if !cur.is_empty() {
+        fields.push(cur);
+    }
+    fields
+}
+
+/// Globs a field with unquoted `*`, `?` or `[`, giving the sorted matches,
+/// or the field itself (quotes removed) when nothing matches.
+fn expand_pathname(field: &str) -> Vec<String> {
+    let literal = split_quoted_line(field).concat();
+    let pattern = quotes_to_escapes(field, GLOB_CHARS);
+    if !has_glob_chars(&pattern) {
+        return vec![literal];
+    }
+    let mut matches = glob(&pattern);
+    if matches.is_empty() {
+        return vec![literal];
+    }
+    matches.sort();
+    matches
+}
+
+/// Removes quotes like [`split_quoted_line`] does for a single word, but
+/// keeps quoted characters that are among `special` escaped instead of
+/// bare.
+fn quotes_to_escapes(text: &str, special: &str) -> String {
+    let mut out = String::new();
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '\\' if !in_single => match chars.next() {
+                Some(c) if in_double && !matches!(c, '"' | '\\') => {
+                    out.push_str("\\\\");
+                    push_pattern_char(&mut out, c, true, special);
+                }
+                Some(c) => push_pattern_char(&mut out, c, true, special),
+                None => out.push_str("\\\\"),
+            },
+            c => push_pattern_char(&mut out, c, in_single || in_double, special),
+        }
+    }
+    out
+}
+
+fn push_pattern_char(out: &mut String, c: char, quoted: bool, special: &str) {
+    if quoted && special.contains(c) {
+        out.push('\\');
+    }
+    out.push(c);
+}
+
+/// A leading `~` or `~/...` is expanded, and `~:` in an assignment value;
+/// `~user` is left alone.
+fn tilde_prefix_end(chars: &[char], in_value: bool) -> Option<usize> {
+    if chars.first() != Some(&'~') {
+        return None;
+    }
+    match chars.get(1) {
+        None | Some('/') => Some(1),
+        Some(':') if in_value => Some(1),
+        _ => None,
+    }
+}
+
+/// Parses the parameter at `chars[i]` (a `$`). Returns its value, or `None`
+/// if the `$` doesn't start an expansion, plus the index after it.
+fn parameter(
+    state: &mut ShellState,
+    chars: &[char],
+    i: usize,
+) -> Result<(Option<String>, usize), String> {
+    Ok(match chars.get(i + 1) {
+        Some('{') => {
+            let end = skip_braced(chars, i + 1);
+            let body: String = chars[i + 2..end.max(i + 3) - 1].iter().collect();
+            (Some(braced(state, &body)?), end)
+        }
+        Some(&c) if c == '_' || c.is_ascii_alphabetic() => {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&c| !(c == '_' || c.is_ascii_alphanumeric()))
+                .map_or(chars.len(), |off| i + 1 + off);
+            let name: String = chars[i + 1..end].iter().collect();
+            (Some(lookup(state, &name).unwrap_or_default()), end)
+        }
+        Some(&c @ ('?' | '$' | '!' | '#' | '0'..='9')) => (
+            Some(lookup(state, &c.to_string()).unwrap_or_default()),
+            i + 2,
+        ),
+        _ => (None, i + 1),
+    })
+}
+
+/// Expands the inside of `${...}`: a name, optionally followed by an
+/// operator and a word that is itself expanded. `-` substitutes the word
+/// if the name is unset, `=` also assigns it, `+` substitutes it if the
+/// name is set, and `?` fails with the word as the message if it is unset;
+/// with a `:` in front, an empty value counts as unset too. `^` and `,`
+/// change case, see [`change_case`], and a leading `!` makes it indirect,
+/// see [`indirect`].
+fn braced(state: &mut ShellState, body: &str) -> Result<String, String> {
+    if let Some(rest) = body.strip_prefix('!').filter(|rest| !rest.is_empty()) {
+        return indirect(state, rest);
+    }
+    let Some(name_len) = name_len(body) else {
+        return Err(format!("${{{}}}: bad substitution", body));
+    };
+    let (name, rest) = body.split_at(name_len);
+    let (subscript, rest) = match split_subscript(rest) {
+        Some((subscript, rest)) => (Some(subscript), rest),
+        None => (None, rest),
+    };
+    let value = match subscript {
+        Some(subscript) => element(state, name, subscript)?,
+        None => lookup(state, name),
+    };
+    if rest.is_empty() {
+        return Ok(value.unwrap_or_default());
+    }
+    if rest.starts_with(['^', ',']) {
+        return change_case(state, &value.unwrap_or_default(), rest);
+    }
+    let (colon, rest) = match rest.strip_prefix(':') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let mut op = rest.chars();
+    let (Some(op), word) = (op.next(), op.as_str()) else {
+        return Err(format!("${{{}}}: bad substitution", body));
+    };
+    let unset = match &value {
+        None => true,
+        Some(value) => colon && value.is_empty(),
+    };
+    match op {
+        '-' if unset => expand_operand(state, word),
+        '+' if !unset => expand_operand(state, word),
+        '+' => Ok(String::new()),
+        '=' if unset => {
+            if !is_valid_name(name) || subscript.is_some() {
+                return Err(format!("${}: cannot assign in this way", name));
+            }
+            let word = expand_operand(state, word)?;
+            state.assign(name, word)?;
+            Ok(state.get_var(name).unwrap_or_default().to_string())
+        }
+        '?' if unset => {
+            let message = match (word.is_empty(), colon) {
+                (false, _) => expand_operand(state, word)?,
+                (true, true) => "parameter null or not set".to_string(),
+                (true, false) => "parameter not set".to_string(),
+            };
+            Err(format!("{}: {}", name, message))
+        }
+        '-' | '=' | '?' => Ok(value.unwrap_or_default()),
+        _ => Err(format!("${{{}}}: bad substitution", body)),
+    }
+}
+
+/// `${name^pattern}` uppercases the first character if it matches the
+/// pattern, `${name^^pattern}` every character that does, and `,` and `,,`
+/// lowercase the same way. No pattern matches any character.
+fn change_case(state: &mut ShellState, value: &str, ops: &str) -> Result<String, String> {
+    let upper = ops.starts_with('^');
+    let all = ops[1..].starts_with(&ops[..1]);
+    let pattern = &ops[1 + usize::from(all)..];
+    let pattern = match pattern.is_empty() {
+        true => "?".to_string(),
+        false => expand_pattern(state, pattern)?,
+    };
+    let mut out = String::new();
+    for (i, c) in value.chars().enumerate() {
+        if (all || i == 0) && glob_match(&pattern, c.encode_utf8(&mut [0; 4])) {
+            match upper {
+                true => out.extend(c.to_uppercase()),
+                false => out.extend(c.to_lowercase()),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Ok(out)
+}
+
+/// Splits `[subscript]` off the start of what follows a name in `${...}`.
+fn split_subscript(rest: &str) -> Option<(&str, &str)> {
+    let inner = rest.strip_prefix('[')?;
+    let mut depth = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' if depth == 0 => return Some((&inner[..i], &inner[i + 1..])),
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `${name[subscript]}`: an element of an array, or with `@` or `*` all of
+/// them joined by spaces. A variable that isn't an array is element 0.
+fn element(state: &mut ShellState, name: &str, subscript: &str) -> Result<Option<String>, String> {
+    if let "@" | "*" = subscript {
+        return Ok(state.var(name).map(|var| match &var.array {
+            Some(array) => array.values().join(" "),
+            None => var.value.clone(),
+        }));
+    }
+    let subscript = expand_subscript(state, subscript)?;
+    Ok(state.element(name, &subscript)?.map(str::to_string))
+}
+
+/// Expands an array subscript, which then is a key or an arithmetic
+/// expression for the index.
+pub fn expand_subscript(state: &mut ShellState, subscript: &str) -> Result<String, String> {
+    expand_operand(state, subscript)
+}
+
+/// The elements `"${name[@]}"` stands for, or the indices or keys
+/// `"${!name[@]}"` does, each of which becomes a word of its own, or
+/// `None` if `body` is something else. An empty array still leaves an
+/// empty word.
+fn all_elements(state: &ShellState, body: &str) -> Option<Vec<String>> {
+    let (keys, body) = match body.strip_prefix('!') {
+        Some(body) => (true, body),
+        None => (false, body),
+    };
+    let name = body
+        .strip_suffix("[@]")
+        .filter(|name| is_valid_name(name))?;
+    let values = match state.var(name) {
+        Some(var) => match (&var.array, keys) {
+            (Some(array), true) => array.keys(),
+            (Some(array), false) => array.values().into_iter().map(str::to_string).collect(),
+            (None, true) => vec!["0".to_string()],
+            (None, false) => vec![var.value.clone()],
+        },
+        None => Vec::new(),
+    };
+    Some(values)
+}
+
+/// How long the parameter name at the start of a `${...}` body is.
+fn name_len(body: &str) -> Option<usize> {
+    let end = |stop: fn(char) -> bool| body.find(stop).unwrap_or(body.len());
+    match body.chars().next()? {
+        '_' | 'a'..='z' | 'A'..='Z' => Some(end(|c| c != '_' && !c.is_ascii_alphanumeric())),
+        '0'..='9' => Some(end(|c| !c.is_ascii_digit())),
+        '?' | '$' | '!' | '#' => Some(1),
+        _ => None,
+    }
+}
+
+/// `${!name[@]}` and `${!name[*]}` list the indices or keys of an array,
+/// and `${!prefix*}` and `${!prefix@}` the variables whose names start
+/// with `prefix`; otherwise `${!name...}` expands as if the value of
+/// `name` had been written in its place.
+fn indirect(state: &mut ShellState, rest: &str) -> Result<String, String> {
+    if let Some(keys) = all_elements(state, &format!("!{}", rest.replace("[*]", "[@]"))) {
+        return Ok(keys.join(" "));
+    }
+    if let Some(prefix) = rest.strip_suffix(['*', '@']).filter(|p| is_valid_name(p)) {
+        let names: Vec<&str> = state
+            .vars()
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        return Ok(names.join(" "));
+    }
+    let Some(len) = name_len(rest) else {
+        return Err(format!("${{!{}}}: bad substitution", rest));
+    };
+    let (name, ops) = rest.split_at(len);
+    let Some(target) = lookup(state, name) else {
+        return Err(format!("{}: invalid indirect expansion", name));
+    };
+    if name_len(&target) != Some(target.len()) {
+        return Err(format!("{}: invalid variable name", target));
+    }
+    braced(state, &format!("{}{}", target, ops))
+}
+
+/// Expands the word after a `${name...}` operator. Unlike a word on the
+/// command line it can hold blanks, which are kept as they are.
+fn expand_operand(state: &mut ShellState, word: &str) -> Result<String, String> {
+    let mut escaped = String::new();
+    let (mut in_single, mut in_double, mut chars) = (false, false, word.chars());
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if !in_single => {
+                escaped.push(c);
+                escaped.extend(chars.next());
+                continue;
+            }
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            c if c.is_whitespace() && !in_single && !in_double => escaped.push('\\'),
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    expand_word(state, &escaped)
+}
+
+/// The value of a variable or special parameter, or `None` if unset.
+fn lookup(state: &ShellState, name: &str) -> Option<String> {
+    match name {
+        "?" => Some(state.last_status.to_string()),
+        "$" => Some(std::process::id().to_string()),
+        "!" => state.last_background.map(|pid| pid.to_string()),
+        "#" => Some(state.positional.len().to_string()),
+        "0" => Some(state.arg0.clone()),
+        "LINENO" => Some(state.lineno.to_string()),
+        "SECONDS" => Some(state.seconds().to_string()),
+        "RANDOM" => Some(state.random().to_string()),
+        _ if name.starts_with(|c: char| c.is_ascii_digit()) => {
+            let n: usize = name.parse().ok()?;
+            state.positional.get(n.checked_sub(1)?).cloned()
+        }
+        _ => state.get_var(name).map(str::to_string),
+    }
+}
+
+/// Escapes text so [`split_quoted_line`] yields it back unchanged, either
+/// inside double quotes or bare.
+fn push_escaped(out: &mut String, text: &str, in_double: bool) {
+    for c in text.chars() {
+        push_escaped_char(out, c, in_double);
+    }
+}
+
+fn push_escaped_char(out: &mut String, c: char, in_double: bool) {
+    let special = if in_double {
+        matches!(c, '"' | '\\')
+    } else {
+        matches!(c, '"' | '\'' | '\\') || c.is_ascii_whitespace()
+    };
+    if special {
+        out.push('\\');
+    }
+    out.push(c);
+}
+
+fn push_unquoted(out: &mut String, text: &str, split: bool) {
+    for c in text.chars() {
+        if split && c.is_ascii_whitespace() {
+            out.push(' ');
+        } else {
+            push_escaped_char(out, c, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> ShellState {
+        let mut state = ShellState::default();
+        state.set_var("HOME", "/home/me".to_string());
+        state.set_var("x", "a b".to_string());
+        state.set_var("empty", String::new());
+        state.set_var("word", "hello".to_string());
+        state
+    }
+
+    fn words(state: &mut ShellState, line: &str) -> Vec<String> {
+        let words: Vec<String> = line.split(' ').map(str::to_string).collect();
+        expand_words(state, &words).unwrap()
+    }
+
+    /// `text` expanded as if in double quotes.
+    fn word(state: &mut ShellState, text: &str) -> Result<String, String> {
+        expand_word(state, &format!("\"{}\"", text))
+    }
+
+    #[test]
+    fn braces_expand_before_anything_else() {
+        assert_eq!(expand_braces("a{b,c}d"), ["abd", "acd"]);
+        assert_eq!(expand_braces("{1..3}"), ["1", "2", "3"]);
+        assert_eq!(expand_braces("{01..10..3}"), ["01", "04", "07", "10"]);
+        assert_eq!(expand_braces("{c..a}"), ["c", "b", "a"]);
+        assert_eq!(expand_braces("{a,{b,c}}"), ["a", "b", "c"]);
+        assert_eq!(expand_braces("{a}"), ["{a}"]);
+        assert_eq!(expand_braces("'{a,b}' ${x,y}"), ["'{a,b}' ${x,y}"]);
+    }
+
+    #[test]
+    fn sequences_at_the_ends_of_i64_do_not_overflow() {
+        let (min, max) = (i64::MIN, i64::MAX);
+        let all = format!("{{{min}..{max}}}");
+        assert_eq!(expand_braces(&all), [all]);
+        let stepped = expand_braces(&format!("{{{min}..{max}..{max}}}"));
+        assert_eq!(
+            stepped,
+            [min.to_string(), "-1".to_string(), (max - 1).to_string()]
+        );
+        let down = expand_braces(&format!("{{{max}..{min}..{min}}}"));
+        assert_eq!(down, [max.to_string(), "-1".to_string()]);
+    }
+
+    #[test]
+    fn unquoted_expansions_are_split_into_fields() {
+        let mut state = state();
+        assert_eq!(words(&mut state, "$x \"$x\" '$x'"), ["a", "b", "a b", "$x"]);
+        assert_eq!(words(&mut state, "$empty \"$empty\""), [""]);
+        assert_eq!(words(&mut state, "pre$x"), ["prea", "b"]);
+    }
+
+    #[test]
+    fn tildes_expand_at_the_start_and_in_assignments() {
+        let mut state = state();
+        assert_eq!(
+            words(&mut state, "~ ~/bin a~ '~'"),
+            ["/home/me", "/home/me/bin", "a~", "~"]
+        );
+        assert_eq!(
+            expand_assignment(&mut state, "~/a:~/b").unwrap(),
+            "/home/me/a:/home/me/b"
+        );
+        assert_eq!(
+            words(&mut state, "PATH=~/bin:~/sbin"),
+            ["PATH=/home/me/bin:/home/me/sbin"]
+        );
+    }
+
+    #[test]
+    fn default_and_alternative_values() {
+        let mut state = state();
+        assert_eq!(
+            word(&mut state, "${unset-d} ${empty-d} ${empty:-d}"),
+            Ok("d  d".into())
+        );
+        assert_eq!(
+            word(&mut state, "${word+alt} ${unset+alt}."),
+            Ok("alt .".into())
+        );
+        assert_eq!(word(&mut state, "${unset:=set}"), Ok("set".into()));
+        assert_eq!(state.get_var("unset"), Some("set"));
+        assert_eq!(
+            word(&mut state, "${nope:?is missing}"),
+            Err("nope: is missing".into())
+        );
+        assert_eq!(
+            word(&mut state, "${nope?}"),
+            Err("nope: parameter not set".into())
+        );
+        assert!(word(&mut state, "${1:=x}").is_err());
+        assert!(word(&mut state, "${x!}").is_err());
+    }
+
+    #[test]
+    fn changes_case() {
+        let mut state = state();
+        let cased = word(&mut state, "${word^} ${word^^} ${word^^[lo]}");
+        assert_eq!(cased, Ok("Hello HELLO heLLO".into()));
+        state.set_var("up", "HELLO".to_string());
+        assert_eq!(word(&mut state, "${up,} ${up,,}"), Ok("hELLO hello".into()));
+    }
+
+    #[test]
+    fn indirection_and_name_listing() {
+        let mut state = state();
+        state.set_var("ref", "word".to_string());
+        assert_eq!(
+            word(&mut state, "${!ref} ${!ref^}"),
+            Ok("hello Hello".into())
+        );
+        assert_eq!(word(&mut state, "${!wo*}"), Ok("word".into()));
+        assert!(word(&mut state, "${!unset}").is_err());
+    }
+
+    #[test]
+    fn special_and_positional_parameters() {
+        let mut state = state();
+        state.last_status = 3;
+        state.arg0 = "sh".to_string();
+        state.positional = vec!["one".to_string(), "two".to_string()];
+        assert_eq!(
+            word(&mut state, "$? $0 $1 ${2} $# [$3]"),
+            Ok("3 sh one two 2 []".into())
+        );
+    }
+
+    #[test]
+    fn array_elements() {
+        let mut state = state();
+        state.set_array("arr", vec!["a".into(), "b c".into()]);
+        let elements = word(&mut state, "$arr ${arr[1]} ${arr[-1]} ${arr[1+1]:-none}");
+        assert_eq!(elements, Ok("a b c b c none".into()));
+        assert_eq!(
+            words(&mut state, "\"${arr[@]}\" ${arr[*]}"),
+            ["a", "b c", "a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn associative_elements_and_keys() {
+        let mut state = state();
+        state.make_array("map", true).unwrap();
+        state.assign_element("map", "a key", "1".into()).unwrap();
+        state.set_var("k", "a key".into());
+        assert_eq!(
+            word(&mut state, "${map[$k]} [${map[other]}]"),
+            Ok("1 []".into())
+        );
+        assert_eq!(words(&mut state, "\"${!map[@]}\""), ["a key"]);
+        state.set_array("arr", vec!["x".into(), "y".into()]);
+        state.set_var("i", "1".into());
+        assert_eq!(
+            word(&mut state, "${arr[$i]} ${!arr[*]}"),
+            Ok("y 0 1".into())
+        );
+    }
+
+    #[test]
+    fn patterns_and_regexes_escape_quoted_characters() {
+        let mut state = state();
+        assert_eq!(expand_pattern(&mut state, "*'*'\"?\"").unwrap(), r"*\*\?");
+        assert_eq!(expand_regex(&mut state, "^('a.b')$").unwrap(), r"^(a\.b)$");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn tilde_falls_back_to_the_windows_profile() {
+        let mut state = ShellState::default();
+        state.set_var("HOMEDRIVE", "D:".to_string());
+        state.set_var("HOMEPATH", r"\Users\me".to_string());
+        assert_eq!(words(&mut state, "~"), [r"D:\Users\me"]);
+        state.set_var("USERPROFILE", r"C:\Users\me".to_string());
+        assert_eq!(words(&mut state, "~"), [r"C:\Users\me"]);
+        state.set_var("HOME", "/home/me".to_string());
+        assert_eq!(words(&mut state, "~"), ["/home/me"]);
+    }
+}
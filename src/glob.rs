@@ -0,0 +1,353 @@
+//! Shell pattern matching, for `case`, the pattern expansions and
+//! pathname expansion.
+
+use std::fs;
+use std::path::Path;
+
+/// Matches `text` against a shell pattern with `*`, `?`, `[...]` and
+/// backslash escapes, as `case` and the pattern expansions do.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    Pattern::new(pattern).matches(text)
+}
+
+/// A shell pattern parsed once, to match against many names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern(Vec<Token>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Star,
+    Question,
+    Char(char),
+    /// A bracket expression: the ranges it lists, single characters being
+    /// ranges of one, and whether it was negated with `!` or `^`.
+    Bracket {
+        negate: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Pattern {
+        let pat: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < pat.len() {
+            let token = match pat[i] {
+                '*' => Token::Star,
+                '?' => Token::Question,
+                '[' => match parse_bracket(&pat, i) {
+                    Some((token, next)) => {
+                        tokens.push(token);
+                        i = next;
+                        continue;
+                    }
+                    // never closed, so just a `[`
+                    None => Token::Char('['),
+                },
+                '\\' if i + 1 < pat.len() => {
+                    i += 1;
+                    Token::Char(pat[i])
+                }
+                c => Token::Char(c),
+            };
+            tokens.push(token);
+            i += 1;
+        }
+        Pattern(tokens)
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        let pat = &self.0;
+        let text: Vec<char> = text.chars().collect();
+        let (mut p, mut t) = (0, 0);
+        // where to resume if the last `*` has to swallow one more char
+        let mut backtrack: Option<(usize, usize)> = None;
+        while t < text.len() {
+            let matched = match pat.get(p) {
+                Some(Token::Star) => {
+                    backtrack = Some((p + 1, t));
+                    p += 1;
+                    continue;
+                }
+                Some(Token::Question) => true,
+                Some(Token::Char(c)) => *c == text[t],
+                Some(Token::Bracket { negate, ranges }) => {
+                    let c = text[t];
+                    ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negate
+                }
+                None => false,
+            };
+            if matched {
+                p += 1;
+                t += 1;
+                continue;
+            }
+            match backtrack {
+                Some((bp, bt)) => {
+                    p = bp;
+                    t = bt + 1;
+                    backtrack = Some((bp, bt + 1));
+                }
+                None => return false,
+            }
+        }
+        pat[p..].iter().all(|token| *token == Token::Star)
+    }
+}
+
+/// Parses the bracket expression starting at `pat[start]`, returning it
+/// and the index after the closing `]`, or `None` if the bracket is never
+/// closed (so `[` is literal).
+fn parse_bracket(pat: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut i = start + 1;
+    let negate = matches!(pat.get(i), Some('!' | '^'));
+    if negate {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let mut lo = *pat.get(i)?;
+        if lo == ']' && !first {
+            return Some((Token::Bracket { negate, ranges }, i + 1));
+        }
+        first = false;
+        if lo == '\\' {
+            i += 1;
+            lo = *pat.get(i)?;
+        }
+        i += 1;
+        let mut hi = lo;
+        if pat.get(i) == Some(&'-') && pat.get(i + 1).is_some_and(|&n| n != ']') {
+            hi = pat[i + 1];
+            i += 2;
+            if hi == '\\' {
+                hi = *pat.get(i)?;
+                i += 1;
+            }
+        }
+        ranges.push((lo, hi));
+    }
+}
+
+/// Whether `pattern` has an unescaped `*`, `?` or `[`.
+pub fn has_glob_chars(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// The paths matching `pattern`, one `/`-separated component at a time.
+/// Names starting with `.` only match a component that starts with `.`.
+pub fn glob(pattern: &str) -> Vec<String> {
+    let (mut paths, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern),
+    };
+    let components: Vec<&str> = rest.split('/').collect();
+    for (n, component) in components.iter().enumerate() {
+        let last = n + 1 == components.len();
+        // parsed once for all the directories' entries
+        let pattern = Pattern::new(component);
+        let mut next = Vec::new();
+        for path in &paths {
+            if component.is_empty() {
+                // `a//b` or a trailing `/`: keep only directories
+                if Path::new(path).is_dir() {
+                    next.push(format!("{}/", path.trim_end_matches('/')));
+                }
+                continue;
+            }
+            if !has_glob_chars(component) {
+                let candidate = format!("{}{}", path, unescape(component));
+                let path = Path::new(&candidate);
+                let found = if last { path.exists() } else { path.is_dir() };
+                if found {
+                    next.push(join_component(candidate, last));
+                }
+                continue;
+            }
+            let dir = if path.is_empty() { "." } else { path.as_str() };
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if name.starts_with('.') && !component.starts_with('.') {
+                    continue;
+                }
+                if !pattern.matches(&name) {
+                    continue;
+                }
+                let candidate = format!("{}{}", path, name);
+                if last || Path::new(&candidate).is_dir() {
+                    next.push(join_component(candidate, last));
+                }
+            }
+        }
+        paths = next;
+    }
+    paths
+}
+
+/// Appends the separator for the next component unless this was the last.
+fn join_component(path: String, last: bool) -> String {
+    match last {
+        true => path,
+        false => path + "/",
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_matches_any_run_including_none() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("a*", "a"));
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", "main.rsx"));
+        assert!(glob_match("**", "x"));
+    }
+
+    #[test]
+    fn star_backtracks_to_span_repeated_text() {
+        assert!(glob_match("a*b*c", "abxbc"));
+        assert!(glob_match("*ab", "aaab"));
+        assert!(glob_match("*a*a*b", "aaaaaaaaab"));
+        assert!(!glob_match("*a*a*b", "aaaaaaaaaa"));
+        assert!(glob_match("a*", "abcabc"));
+        assert!(!glob_match("a*c", "abcab"));
+    }
+
+    #[test]
+    fn question_mark_matches_exactly_one_char() {
+        assert!(glob_match("?", "x"));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("?", "xy"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(glob_match("??", "é!"));
+    }
+
+    #[test]
+    fn empty_pattern_only_matches_empty_text() {
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn brackets_match_sets_and_ranges() {
+        assert!(glob_match("[abc]", "b"));
+        assert!(!glob_match("[abc]", "d"));
+        assert!(glob_match("[a-c]x", "bx"));
+        assert!(!glob_match("[a-c]", "B"));
+        assert!(glob_match("[0-9][0-9]", "42"));
+    }
+
+    #[test]
+    fn brackets_negate_with_bang_or_caret() {
+        assert!(glob_match("[!a]", "b"));
+        assert!(!glob_match("[!a]", "a"));
+        assert!(glob_match("[^a-c]", "d"));
+        assert!(!glob_match("[^a-c]", "b"));
+    }
+
+    #[test]
+    fn bracket_edge_cases() {
+        // a leading `]` is a member, as is a `-` next to the closing `]`
+        assert!(glob_match("[]a]", "]"));
+        assert!(glob_match("[!]]", "x"));
+        assert!(!glob_match("[!]]", "]"));
+        assert!(glob_match("[a-]", "-"));
+        // an escaped `]` doesn't close the bracket
+        assert!(glob_match(r"[\]]", "]"));
+        // a bracket that is never closed is just a `[`
+        assert!(glob_match("[ab", "[ab"));
+        assert!(!glob_match("[ab", "a"));
+        assert!(glob_match("[", "["));
+    }
+
+    #[test]
+    fn patterns_parse_once_into_tokens() {
+        let pattern = Pattern::new(r"a*[!x-z\]]?\[[b");
+        let ranges = vec![('x', 'z'), (']', ']')];
+        let tokens = [
+            Token::Char('a'),
+            Token::Star,
+            Token::Bracket {
+                negate: true,
+                ranges,
+            },
+            Token::Question,
+            Token::Char('['),
+            Token::Char('['),
+            Token::Char('b'),
+        ];
+        assert_eq!(pattern.0, tokens);
+        assert!(pattern.matches("abc?[[b") && !pattern.matches("ay?[[b"));
+    }
+
+    #[test]
+    fn backslash_makes_the_next_char_literal() {
+        assert!(glob_match(r"\*", "*"));
+        assert!(!glob_match(r"\*", "x"));
+        assert!(glob_match(r"a\?", "a?"));
+        assert!(!glob_match(r"a\?", "ab"));
+    }
+
+    #[test]
+    fn finds_unescaped_glob_chars() {
+        assert!(has_glob_chars("*.rs"));
+        assert!(has_glob_chars("a[b]"));
+        assert!(!has_glob_chars(r"\*.rs"));
+        assert!(!has_glob_chars("plain"));
+    }
+
+    #[test]
+    fn globs_each_path_component() {
+        let dir = std::env::temp_dir().join(format!("rust-cli-glob-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        for name in ["src/a.rs", "src/b.rs", "src/c.txt", "src/.hidden.rs"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let base = dir.to_str().unwrap();
+        let mut found = glob(&format!("{}/s*/*.rs", base));
+        found.sort();
+        assert_eq!(
+            found,
+            [format!("{}/src/a.rs", base), format!("{}/src/b.rs", base)]
+        );
+        assert_eq!(
+            glob(&format!("{}/src/.h*", base)),
+            [format!("{}/src/.hidden.rs", base)]
+        );
+        assert!(glob(&format!("{}/nope/*", base)).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,321 @@
+//! Command history and the `!` history expansion run on each input line
+//! before it is tokenized.
+
+use crate::lexer::{tokenize, Token};
+use std::fmt::Write as _;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub line: String,
+    /// When it was entered, in seconds since the epoch, if known.
+    pub time: Option<i64>,
+}
+
+impl History {
+    pub fn push(&mut self, line: &str) {
+        let line = line.trim_end_matches('\n');
+        if !line.trim().is_empty() {
+            let time = SystemTime::now().duration_since(UNIX_EPOCH).ok();
+            let time = time.map(|t| t.as_secs() as i64);
+            self.entries.push(Entry {
+                line: line.to_string(),
+                time,
+            });
+        }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Adds the entries of a history file, where a `#` line with the epoch
+    /// time before an entry says when it was entered.
+    pub fn load(&mut self, text: &str) {
+        let mut time = None;
+        for line in text.lines() {
+            match line.strip_prefix('#').and_then(|t| t.parse().ok()) {
+                Some(t) => time = Some(t),
+                None if line.trim().is_empty() => {}
+                None => self.entries.push(Entry {
+                    line: line.to_string(),
+                    time: time.take(),
+                }),
+            }
+        }
+    }
+
+    /// The contents of a history file, with each entry's time on a `#`
+    /// line before it if `timestamps` is set.
+    pub fn file_text(&self, timestamps: bool) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            if let (true, Some(time)) = (timestamps, entry.time) {
+                let _ = writeln!(text, "#{}", time);
+            }
+            let _ = writeln!(text, "{}", entry.line);
+        }
+        text
+    }
+
+    /// Expands history references in `line`: `!!`, `!N`, `!-N`, `!text`
+    /// and `!?text?` pick an event, optionally followed by a `:N`, `:^`,
+    /// `:$` or `:*` word designator, and `!$`, `!^` and `!*` are short for
+    /// words of the previous event. Like in bash, `!` is literal inside
+    /// single quotes, after a backslash, and before whitespace, `=`, `(` or
+    /// a closing `"`. Returns `None` if nothing was expanded.
+    pub fn expand(&self, line: &str) -> Result<Option<String>, String> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut out = String::new();
+        let mut changed = false;
+        let mut in_single = false;
+        let mut in_double = false;
+        let mut i = 0;
+        while i < chars.len() {
+            let ch = chars[i];
+            match ch {
+                '\\' if !in_single => {
+                    out.push(ch);
+                    if let Some(&next) = chars.get(i + 1) {
+                        out.push(next);
+                    }
+                    i += 2;
+                    continue;
+                }
+                '\'' if !in_double => in_single = !in_single,
+                '"' if !in_single => in_double = !in_double,
+                '!' if !in_single && !is_literal_bang(chars.get(i + 1), in_double) => {
+                    let (text, next) = self.reference(&chars, i)?;
+                    out.push_str(&text);
+                    changed = true;
+                    i = next;
+                    continue;
+                }
+                _ => {}
+            }
+            out.push(ch);
+            i += 1;
+        }
+        Ok(changed.then_some(out))
+    }
+
+    /// Expands the reference whose `!` is at `chars[i]`, returning its text
+    /// and the index after it.
+    fn reference(&self, chars: &[char], i: usize) -> Result<(String, usize), String> {
+        let mut j = i + 1;
+        let event = match chars.get(j) {
+            Some('!') => {
+                j += 1;
+                self.entries.last()
+            }
+            // `!$` and friends: a designator on the previous event
+            Some('$' | '^' | '*') => self.entries.last(),
+            Some('-') | Some('0'..='9') => {
+                let start = j;
+                j += 1;
+                while chars.get(j).is_some_and(|c| c.is_ascii_digit()) {
+                    j += 1;
+                }
+                let n: String = chars[start..j].iter().collect();
+                match n.parse::<isize>() {
+                    Ok(n) if n < 0 => self
+                        .entries
+                        .len()
+                        .checked_sub(n.unsigned_abs())
+                        .and_then(|k| self.entries.get(k)),
+                    Ok(n) if n > 0 => self.entries.get(n as usize - 1),
+                    _ => None,
+                }
+            }
+            Some('?') => {
+                let start = j + 1;
+                j = start;
+                while j < chars.len() && chars[j] != '?' && chars[j] != '\n' {
+                    j += 1;
+                }
+                let text: String = chars[start..j].iter().collect();
+                if chars.get(j) == Some(&'?') {
+                    j += 1;
+                }
+                self.entries.iter().rev().find(|e| e.line.contains(&text))
+            }
+            _ => {
+                let start = j;
+                while j < chars.len() && !ends_event_text(chars[j]) {
+                    j += 1;
+                }
+                let text: String = chars[start..j].iter().collect();
+                self.entries
+                    .iter()
+                    .rev()
+                    .find(|e| e.line.starts_with(&text))
+            }
+        };
+        let spec: String = chars[i..j].iter().collect();
+        let Some(event) = event.map(|e| &e.line) else {
+            return Err(format!("{}: event not found", spec));
+        };
+
+        let designator = match chars.get(j) {
+            Some(':') if chars.get(j + 1).is_some_and(|&c| is_designator(c)) => {
+                j += 2;
+                chars[j - 1]
+            }
+            Some(&c @ ('$' | '^' | '*')) if j == i + 1 => {
+                j += 1;
+                c
+            }
+            _ => return Ok((event.clone(), j)),
+        };
+        let words = event_words(event);
+        let picked = match designator {
+            '^' => words.get(1).cloned(),
+            '$' => words.last().cloned(),
+            '*' => Some(words.get(1..).unwrap_or_default().join(" ")),
+            _ => {
+                let start = j - 1;
+                while chars.get(j).is_some_and(|c| c.is_ascii_digit()) {
+                    j += 1;
+                }
+                let n: String = chars[start..j].iter().collect();
+                n.parse::<usize>().ok().and_then(|n| words.get(n).cloned())
+            }
+        };
+        match picked {
+            Some(text) => Ok((text, j)),
+            None => {
+                let spec: String = chars[i..j].iter().collect();
+                Err(format!("{}: bad word specifier", spec))
+            }
+        }
+    }
+}
+
+/// A `!` before these doesn't start a history reference.
+fn is_literal_bang(next: Option<&char>, in_double: bool) -> bool {
+    match next {
+        None => true,
+        Some(c) if c.is_whitespace() => true,
+        Some('=' | '(') => true,
+        Some('"') => in_double,
+        _ => false,
+    }
+}
+
+fn ends_event_text(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            ':' | ';' | '&' | '|' | '(' | ')' | '<' | '>' | '\'' | '"'
+        )
+}
+
+fn is_designator(c: char) -> bool {
+    c.is_ascii_digit() || matches!(c, '^' | '$' | '*')
+}
+
+/// The words of a history entry as the shell would tokenize it, with
+/// operators counting as words.
+fn event_words(event: &str) -> Vec<String> {
+    let Ok(tokens) = tokenize(event) else {
+        return event.split_whitespace().map(str::to_string).collect();
+    };
+    tokens
+        .iter()
+        .filter(|tok| **tok != Token::Newline)
+        .map(|tok| match tok {
+            Token::IoNumber(fd) => fd.to_string(),
+            tok => tok.describe().to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> History {
+        let mut history = History::default();
+        history.push("echo one two");
+        history.push("ls -l /tmp");
+        history
+    }
+
+    fn expand(line: &str) -> Option<String> {
+        history().expand(line).unwrap()
+    }
+
+    #[test]
+    fn file_times_round_trip() {
+        let mut history = History::default();
+        history.load("#100\necho a\necho b\n\n#200\necho c\n");
+        let times: Vec<_> = history.entries().iter().map(|e| e.time).collect();
+        assert_eq!(times, [Some(100), None, Some(200)]);
+        assert_eq!(
+            history.file_text(true),
+            "#100\necho a\necho b\n#200\necho c\n"
+        );
+        assert_eq!(history.file_text(false), "echo a\necho b\necho c\n");
+    }
+
+    #[test]
+    fn events() {
+        assert_eq!(expand("!!").as_deref(), Some("ls -l /tmp"));
+        assert_eq!(expand("!1").as_deref(), Some("echo one two"));
+        assert_eq!(expand("!-2").as_deref(), Some("echo one two"));
+        assert_eq!(expand("!ec x").as_deref(), Some("echo one two x"));
+        assert_eq!(expand("!?one?").as_deref(), Some("echo one two"));
+        assert_eq!(
+            history().expand("!vi"),
+            Err("!vi: event not found".to_string())
+        );
+    }
+
+    #[test]
+    fn word_designators() {
+        assert_eq!(expand("echo !$").as_deref(), Some("echo /tmp"));
+        assert_eq!(expand("echo !^").as_deref(), Some("echo -l"));
+        assert_eq!(expand("echo !*").as_deref(), Some("echo -l /tmp"));
+        assert_eq!(expand("echo !1:2").as_deref(), Some("echo two"));
+        assert_eq!(
+            history().expand("!!:5"),
+            Err("!!:5: bad word specifier".to_string())
+        );
+    }
+
+    #[test]
+    fn double_quotes_expand_but_single_quotes_do_not() {
+        assert_eq!(
+            expand("echo \"!!\"").as_deref(),
+            Some("echo \"ls -l /tmp\"")
+        );
+        assert_eq!(expand("echo '!!'"), None);
+        assert_eq!(
+            expand("echo \"'!!'\"").as_deref(),
+            Some("echo \"'ls -l /tmp'\"")
+        );
+    }
+
+    #[test]
+    fn literal_bangs() {
+        for line in [
+            "echo \\!!",
+            "echo ! x",
+            "x!=y",
+            "echo !(a)",
+            "echo \"hi!\"",
+            "echo hi!",
+        ] {
+            assert_eq!(expand(line), None, "{}", line);
+        }
+    }
+}
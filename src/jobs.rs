@@ -0,0 +1,368 @@
+//! The table of background and stopped jobs, and the `%` job specs the
+//! job-control builtins use to name them.
+
+use std::fmt::Write as _;
+
+pub type Pid = i32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Running,
+    /// Stopped by the given signal.
+    Stopped(i32),
+    /// Finished, with the status `$?` would get.
+    Done(i32),
+}
+
+impl JobStatus {
+    /// The status a builtin waiting on the job returns.
+    pub fn code(self) -> i32 {
+        match self {
+            JobStatus::Running => 0,
+            JobStatus::Stopped(sig) => 128 + sig,
+            JobStatus::Done(code) => code,
+        }
+    }
+
+    fn describe(self) -> String {
+        match self {
+            JobStatus::Running => "Running".to_string(),
+            JobStatus::Stopped(_) => "Stopped".to_string(),
+            JobStatus::Done(0) => "Done".to_string(),
+            // the signals whose numbers POSIX fixes
+            JobStatus::Done(129) => "Hangup".to_string(),
+            JobStatus::Done(130) => "Interrupt".to_string(),
+            JobStatus::Done(131) => "Quit".to_string(),
+            JobStatus::Done(137) => "Killed".to_string(),
+            JobStatus::Done(143) => "Terminated".to_string(),
+            JobStatus::Done(code) => format!("Exit {}", code),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: usize,
+    pub pgid: Pid,
+    /// The job's processes, in pipeline order.
+    pub pids: Vec<Pid>,
+    /// Those of `pids` not reaped yet.
+    pending: Vec<Pid>,
+    /// The status of the last process, once it has been reaped.
+    last: i32,
+    pub command: String,
+    pub status: JobStatus,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JobTable {
+    jobs: Vec<Job>,
+    /// Job ids from least to most recently started or stopped; the last
+    /// one is the current job (`%+`), the one before it the previous
+    /// (`%-`).
+    order: Vec<usize>,
+}
+
+impl JobTable {
+    /// Adds a running job and makes it current, returning its number.
+    pub fn add(&mut self, pgid: Pid, pids: Vec<Pid>, command: String) -> usize {
+        let id = self.jobs.iter().map(|j| j.id).max().unwrap_or(0) + 1;
+        self.jobs.push(Job {
+            id,
+            pgid,
+            pending: pids.clone(),
+            pids,
+            last: 0,
+            command,
+            status: JobStatus::Running,
+        });
+        self.order.push(id);
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.iter().find(|j| j.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    pub fn remove(&mut self, id: usize) {
+        self.jobs.retain(|j| j.id != id);
+        self.order.retain(|&o| o != id);
+    }
+
+    /// Forgets every finished job.
+    pub fn remove_done(&mut self) {
+        let done: Vec<usize> = self
+            .jobs
+            .iter()
+            .filter(|j| matches!(j.status, JobStatus::Done(_)))
+            .map(|j| j.id)
+            .collect();
+        for id in done {
+            self.remove(id);
+        }
+    }
+
+    /// The job running `pid`, for builtins that also take plain pids.
+    pub fn find_pid(&self, pid: Pid) -> Option<usize> {
+        self.jobs
+            .iter()
+            .find(|j| j.pids.contains(&pid))
+            .map(|j| j.id)
+    }
+
+    pub fn set_status(&mut self, id: usize, status: JobStatus) {
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+            job.status = status;
+        }
+    }
+
+    /// Makes `id` the current job.
+    pub fn touch(&mut self, id: usize) {
+        self.order.retain(|&o| o != id);
+        self.order.push(id);
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.order.last().copied()
+    }
+
+    /// The job before the current one, or the current one if it's alone.
+    fn previous(&self) -> Option<usize> {
+        match self.order.len() {
+            0 => None,
+            1 => self.current(),
+            n => Some(self.order[n - 2]),
+        }
+    }
+
+    /// Resolves a job spec: `%%`, `%+` or a bare `%` for the current job,
+    /// `%-` for the previous one, `%N` for job number N, `%?text` for the
+    /// job whose command contains `text` and `%text` for the one whose
+    /// command starts with it. A missing spec means the current job.
+    pub fn resolve(&self, spec: Option<&str>) -> Result<usize, String> {
+        let Some(spec) = spec else {
+            return self
+                .current()
+                .ok_or_else(|| "current: no such job".to_string());
+        };
+        let body = spec.strip_prefix('%').unwrap_or(spec);
+        let found = match body {
+            "" | "%" | "+" => self.current(),
+            "-" => self.previous(),
+            _ if body.bytes().all(|b| b.is_ascii_digit()) => {
+                body.parse().ok().filter(|&id| self.get(id).is_some())
+            }
+            _ => {
+                let matching: Vec<usize> = match body.strip_prefix('?') {
+                    Some(text) => self
+                        .jobs
+                        .iter()
+                        .filter(|j| j.command.contains(text))
+                        .map(|j| j.id)
+                        .collect(),
+                    None => self
+                        .jobs
+                        .iter()
+                        .filter(|j| j.command.starts_with(body))
+                        .map(|j| j.id)
+                        .collect(),
+                };
+                match matching[..] {
+                    [id] => Some(id),
+                    [] => None,
+                    _ => return Err(format!("{}: ambiguous job spec", spec)),
+                }
+            }
+        };
+        found.ok_or_else(|| format!("{}: no such job", spec))
+    }
+
+    /// The `jobs` line for `id`, e.g. `[1]+  Running    sleep 10 &`.
+    pub fn format(&self, id: usize) -> String {
+        let Some(job) = self.get(id) else {
+            return String::new();
+        };
+        let marker = if self.current() == Some(id) {
+            '+'
+        } else if self.order.len() > 1 && self.previous() == Some(id) {
+            '-'
+        } else {
+            ' '
+        };
+        let mut line = format!(
+            "[{}]{}  {:<24}{}",
+            id,
+            marker,
+            job.status.describe(),
+            job.command
+        );
+        if job.status == JobStatus::Running {
+            let _ = write!(line, " &");
+        }
+        line
+    }
+
+    /// Records a state change of `pid` reported by `waitpid`.
+    #[cfg(unix)]
+    fn record(&mut self, pid: Pid, status: std::process::ExitStatus) {
+        use std::os::unix::process::ExitStatusExt;
+
+        let Some(id) = self.find_pid(pid) else {
+            return;
+        };
+        if let Some(sig) = status.stopped_signal() {
+            self.set_status(id, JobStatus::Stopped(sig));
+            self.touch(id);
+            return;
+        }
+        self.reaped(id, pid, crate::exec::exit_code(status));
+    }
+
+    fn reaped(&mut self, id: usize, pid: Pid, code: i32) {
+        let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) else {
+            return;
+        };
+        job.pending.retain(|&p| p != pid);
+        if job.pids.last() == Some(&pid) {
+            job.last = code;
+        }
+        job.status = match job.pending.is_empty() {
+            true => JobStatus::Done(job.last),
+            false => JobStatus::Running,
+        };
+    }
+
+    /// Checks every unfinished job without blocking, returning the ids
+    /// whose status changed.
+    #[cfg(unix)]
+    pub fn poll(&mut self) -> Vec<usize> {
+        let mut changed = Vec::new();
+        let pending: Vec<(usize, Pid)> = self
+            .jobs
+            .iter()
+            .flat_map(|j| j.pending.iter().map(move |&pid| (j.id, pid)))
+            .collect();
+        for (id, pid) in pending {
+            let before = self.get(id).map(|j| j.status);
+            match crate::sys::try_wait_pid(pid) {
+                Ok(Some(status)) => self.record(pid, status),
+                Ok(None) => continue,
+                // someone else reaped it; nothing more will be learned
+                Err(_) => self.reaped(id, pid, 127),
+            }
+            if self.get(id).map(|j| j.status) != before && !changed.contains(&id) {
+                changed.push(id);
+            }
+        }
+        changed
+    }
+
+    /// Blocks until job `id` is no longer running, also returning when it
+    /// stops if `untraced` is set.
+    #[cfg(unix)]
+    pub fn wait_for(&mut self, id: usize, untraced: bool) -> JobStatus {
+        loop {
+            let Some(job) = self.get(id) else {
+                return JobStatus::Done(127);
+            };
+            let Some(&pid) = job.pending.first() else {
+                return job.status;
+            };
+            if untraced && matches!(job.status, JobStatus::Stopped(_)) {
+                return job.status;
+            }
+            let result = match untraced {
+                true => crate::sys::wait_pid_untraced(pid),
+                false => crate::sys::wait_pid(pid),
+            };
+            match result {
+                Ok(status) => self.record(pid, status),
+                Err(_) => self.reaped(id, pid, 127),
+            }
+        }
+    }
+
+    /// Blocks until one of the jobs `among`, or any job if it's empty,
+    /// finishes, returning its id. One that finished already but hasn't
+    /// been waited for counts first. `None` if there is nothing to wait for.
+    #[cfg(unix)]
+    pub fn wait_next(&mut self, among: &[usize]) -> Option<usize> {
+        let wanted = |job: &&Job| among.is_empty() || among.contains(&job.id);
+        loop {
+            let mut jobs = self.jobs.iter().filter(wanted);
+            if let Some(job) = jobs
+                .clone()
+                .find(|j| matches!(j.status, JobStatus::Done(_)))
+            {
+                return Some(job.id);
+            }
+            if jobs.all(|j| j.pending.is_empty()) {
+                return None;
+            }
+            match crate::sys::wait_any() {
+                Ok((pid, status)) => self.record(pid, status),
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> JobTable {
+        let mut jobs = JobTable::default();
+        jobs.add(100, vec![100], "sleep 10".to_string());
+        jobs.add(200, vec![200], "make all".to_string());
+        jobs.add(300, vec![300], "sleep 20".to_string());
+        jobs
+    }
+
+    #[test]
+    fn current_and_previous() {
+        let jobs = table();
+        for spec in [None, Some("%"), Some("%%"), Some("%+")] {
+            assert_eq!(jobs.resolve(spec), Ok(3));
+        }
+        assert_eq!(jobs.resolve(Some("%-")), Ok(2));
+    }
+
+    #[test]
+    fn touching_a_job_makes_it_current() {
+        let mut jobs = table();
+        jobs.touch(1);
+        assert_eq!(jobs.resolve(Some("%+")), Ok(1));
+        assert_eq!(jobs.resolve(Some("%-")), Ok(3));
+        assert!(jobs.format(1).starts_with("[1]+"));
+        assert!(jobs.format(3).starts_with("[3]-"));
+    }
+
+    #[test]
+    fn numbers_and_commands() {
+        let jobs = table();
+        assert_eq!(jobs.resolve(Some("%2")), Ok(2));
+        assert_eq!(jobs.resolve(Some("%4")), Err("%4: no such job".to_string()));
+        assert_eq!(jobs.resolve(Some("%make")), Ok(2));
+        assert_eq!(jobs.resolve(Some("%?20")), Ok(3));
+        assert_eq!(
+            jobs.resolve(Some("%sleep")),
+            Err("%sleep: ambiguous job spec".to_string())
+        );
+        assert_eq!(
+            jobs.resolve(Some("%vi")),
+            Err("%vi: no such job".to_string())
+        );
+    }
+
+    #[test]
+    fn empty_table() {
+        let jobs = JobTable::default();
+        assert_eq!(jobs.resolve(None), Err("current: no such job".to_string()));
+        assert_eq!(jobs.resolve(Some("%-")), Err("%-: no such job".to_string()));
+    }
+}
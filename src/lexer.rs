@@ -0,0 +1,736 @@
+use crate::parser::ParseError;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// A word exactly as typed; quotes and escapes are kept so the
+    /// expander can tell quoted text from unquoted text.
+    Word(String),
+    Semi,
+    /// `;;`, ending a `case` item.
+    DSemi,
+    Pipe,
+    /// `|&`, piping stderr along with stdout.
+    PipeAmp,
+    AndIf,
+    OrIf,
+    LParen,
+    RParen,
+    Less,
+    /// `<<`, starting a here-document.
+    DLess,
+    /// `<<-`, a here-document with the leading tabs of its lines removed.
+    DLessDash,
+    /// The delimiter after `<<` as typed, and the here-document's body,
+    /// the lines after the command up to the delimiter.
+    HereDoc(String, String),
+    Great,
+    DGreat,
+    /// `<&`
+    LessAnd,
+    /// `>&`
+    GreatAnd,
+    /// `&>`, redirecting stdout and stderr to the same file.
+    AndGreat,
+    /// `&>>`, appending stdout and stderr to the same file.
+    AndDGreat,
+    /// `&`, running the command before it in the background.
+    Amp,
+    /// The digits of `2>file`, directly in front of a redirection operator.
+    IoNumber(i32),
+    Newline,
+}
+
+impl Token {
+    pub fn describe(&self) -> &str {
+        match self {
+            Token::Word(w) => w,
+            Token::Semi => ";",
+            Token::DSemi => ";;",
+            Token::Pipe => "|",
+            Token::PipeAmp => "|&",
+            Token::AndIf => "&&",
+            Token::OrIf => "||",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::Less => "<",
+            Token::DLess => "<<",
+            Token::DLessDash => "<<-",
+            Token::HereDoc(delimiter, _) => delimiter,
+            Token::Great => ">",
+            Token::DGreat => ">>",
+            Token::LessAnd => "<&",
+            Token::GreatAnd => ">&",
+            Token::AndGreat => "&>",
+            Token::AndDGreat => "&>>",
+            Token::Amp => "&",
+            Token::IoNumber(_) => "number",
+            Token::Newline => "newline",
+        }
+    }
+}
+
+/// Where a token starts in the input, counting from 1. Lines joined by a
+/// backslash-newline count as one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            1 => write!(f, "column {}", self.column),
+            line => write!(f, "line {}, column {}", line, self.column),
+        }
+    }
+}
+
+/// Splits `line` into words and operators in one pass, taking the longest
+/// operator at each point, so `a&&b` is `a`, `&&`, `b` while quoted or
+/// escaped operator characters stay part of their word.
+pub fn tokenize(line: &str) -> Result<Vec<Token>, ParseError> {
+    Ok(scan(line, true)?.into_iter().map(|(tok, _)| tok).collect())
+}
+
+/// Like [`tokenize`], but giving where each token starts too, for error
+/// messages. Without `comments` a `#` is an ordinary character.
+pub fn tokenize_with_positions(
+    line: &str,
+    comments: bool,
+) -> Result<Vec<(Token, Position)>, ParseError> {
+    scan(line, comments)
+}
+
+fn scan(line: &str, comments: bool) -> Result<Vec<(Token, Position)>, ParseError> {
+    let chars = join_continuations(line)?;
+    let mut tokens = Vec::new();
+    let mut starts = Vec::new();
+    let mut i = 0;
+    // where the tokens pushed by the last step start
+    let mut start = 0;
+    // the delimiters whose bodies start after the next newline, and
+    // whether their tabs are removed
+    let mut here_docs = Vec::new();
+
+    while i < chars.len() {
+        starts.resize(tokens.len(), start);
+        start = i;
+        let ch = chars[i];
+        match ch {
+            ' ' | '\t' | '\r' => i += 1,
+            '#' if comments => {
+                // ex: echo a # note             -> only ever reached at the start of a word
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '\n' => {
+                tokens.push(Token::Newline);
+                i += 1;
+                for (index, strip_tabs) in here_docs.drain(..) {
+                    let Token::Word(delimiter) = &tokens[index] else {
+                        continue;
+                    };
+                    let end = split_quoted_line(delimiter).concat();
+                    let (body, next) = here_doc_body(&chars, i, &end, strip_tabs)?;
+                    tokens[index] = Token::HereDoc(delimiter.clone(), body);
+                    i = next;
+                }
+            }
+            ';' if chars.get(i + 1) == Some(&';') => {
+                tokens.push(Token::DSemi);
+                i += 2;
+            }
+            ';' => {
+                tokens.push(Token::Semi);
+                i += 1;
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '<' if chars.get(i + 1) == Some(&'<') && chars.get(i + 2) == Some(&'-') => {
+                tokens.push(Token::DLessDash);
+                i += 3;
+            }
+            '<' if chars.get(i + 1) == Some(&'<') => {
+                tokens.push(Token::DLess);
+                i += 2;
+            }
+            '<' if chars.get(i + 1) == Some(&'&') => {
+                tokens.push(Token::LessAnd);
+                i += 2;
+            }
+            '<' => {
+                tokens.push(Token::Less);
+                i += 1;
+            }
+            '>' if chars.get(i + 1) == Some(&'>') => {
+                tokens.push(Token::DGreat);
+                i += 2;
+            }
+            '>' if chars.get(i + 1) == Some(&'&') => {
+                tokens.push(Token::GreatAnd);
+                i += 2;
+            }
+            '>' => {
+                tokens.push(Token::Great);
+                i += 1;
+            }
+            '&' if chars.get(i + 1) == Some(&'&') => {
+                tokens.push(Token::AndIf);
+                i += 2;
+            }
+            '&' if chars.get(i + 1) == Some(&'>') && chars.get(i + 2) == Some(&'>') => {
+                tokens.push(Token::AndDGreat);
+                i += 3;
+            }
+            '&' if chars.get(i + 1) == Some(&'>') => {
+                tokens.push(Token::AndGreat);
+                i += 2;
+            }
+            '&' => {
+                tokens.push(Token::Amp);
+                i += 1;
+            }
+            '|' if chars.get(i + 1) == Some(&'|') => {
+                tokens.push(Token::OrIf);
+                i += 2;
+            }
+            '|' if chars.get(i + 1) == Some(&'&') => {
+                tokens.push(Token::PipeAmp);
+                i += 2;
+            }
+            '|' => {
+                tokens.push(Token::Pipe);
+                i += 1;
+            }
+            _ => {
+                let start = i;
+                i = scan_word(&chars, i)?;
+                let word: String = chars[start..i].iter().collect();
+                let before_redirect = matches!(chars.get(i), Some('<' | '>'));
+                match word.parse::<i32>() {
+                    Ok(fd) if before_redirect && word.bytes().all(|b| b.is_ascii_digit()) => {
+                        tokens.push(Token::IoNumber(fd))
+                    }
+                    _ => tokens.push(Token::Word(word)),
+                }
+                match tokens.iter().rev().nth(1) {
+                    Some(Token::DLess) => here_docs.push((tokens.len() - 1, false)),
+                    Some(Token::DLessDash) => here_docs.push((tokens.len() - 1, true)),
+                    _ => {}
+                }
+            }
+        }
+    }
+    if !here_docs.is_empty() {
+        // the delimiter's line is still to come
+        return Err(ParseError::UnexpectedEof);
+    }
+    starts.resize(tokens.len(), start);
+    Ok(tokens.into_iter().zip(positions(&chars, starts)).collect())
+}
+
+/// Reads a here-document's body from the line starting at `i` up to a
+/// line that is just `delimiter`, returning it and the index past that
+/// line. With `strip_tabs` the lines' leading tabs go first.
+fn here_doc_body(
+    chars: &[char],
+    mut i: usize,
+    delimiter: &str,
+    strip_tabs: bool,
+) -> Result<(String, usize), ParseError> {
+    let mut body = String::new();
+    while i < chars.len() {
+        let end = chars[i..]
+            .iter()
+            .position(|&c| c == '\n')
+            .map_or(chars.len(), |off| i + off);
+        let mut line = &chars[i..end];
+        if strip_tabs {
+            while let ['\t', rest @ ..] = line {
+                line = rest;
+            }
+        }
+        let next = (end + 1).min(chars.len());
+        if line.iter().copied().eq(delimiter.chars()) {
+            return Ok((body, next));
+        }
+        body.extend(line);
+        body.push('\n');
+        i = next;
+    }
+    Err(ParseError::UnexpectedEof)
+}
+
+/// The line and column of each of the increasing `offsets` into `chars`.
+fn positions(chars: &[char], offsets: Vec<usize>) -> Vec<Position> {
+    let (mut line, mut line_start, mut seen) = (1, 0, 0);
+    let mut out = Vec::with_capacity(offsets.len());
+    for offset in offsets {
+        for (j, &c) in chars[seen..offset].iter().enumerate() {
+            if c == '\n' {
+                line += 1;
+                line_start = seen + j + 1;
+            }
+        }
+        seen = offset;
+        out.push(Position {
+            line,
+            column: offset - line_start + 1,
+        });
+    }
+    out
+}
+
+/// Drops backslash-newline pairs outside single quotes. A trailing one
+/// means the command continues on the next line.
+fn join_continuations(line: &str) -> Result<Vec<char>, ParseError> {
+    let mut out = Vec::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    let mut in_single = false;
+    let mut in_double = false;
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' if !in_double => in_single = !in_single,
+            '"' if !in_single => in_double = !in_double,
+            '\\' if !in_single => match chars.next() {
+                Some('\n') if chars.peek().is_none() => return Err(ParseError::UnexpectedEof),
+                Some('\n') => continue,
+                Some(next) => {
+                    out.push(ch);
+                    out.push(next);
+                    continue;
+                }
+                None => {}
+            },
+            _ => {}
+        }
+        out.push(ch);
+    }
+    Ok(out)
+}
+
+fn is_word_break(chars: &[char], i: usize) -> bool {
+    matches!(
+        chars[i],
+        ' ' | '\t' | '\r' | '\n' | ';' | '(' | ')' | '<' | '>' | '|' | '&'
+    )
+}
+
+/// Returns the index just past the word starting at `i`.
+fn scan_word(chars: &[char], mut i: usize) -> Result<usize, ParseError> {
+    while i < chars.len() && !is_word_break(chars, i) {
+        match chars[i] {
+            // ex: echo a\ b                -> the escaped char never ends the word
+            '\\' => i += 2,
+            '\'' => i = scan_single(chars, i)?,
+            '"' => i = scan_double(chars, i)?,
+            '$' if chars.get(i + 1) == Some(&'{') => i = scan_braced(chars, i + 1)?,
+            '$' if chars.get(i + 1) == Some(&'(') => i = scan_parens(chars, i + 1)?,
+            '`' => i = scan_backquoted(chars, i)?,
+            _ => i += 1,
+        }
+    }
+    Ok(i.min(chars.len()))
+}
+
+/// `i` is at the opening `'`; returns the index past the closing one.
+fn scan_single(chars: &[char], i: usize) -> Result<usize, ParseError> {
+    match chars[i + 1..].iter().position(|&c| c == '\'') {
+        Some(off) => Ok(i + 1 + off + 1),
+        None => Err(ParseError::Unmatched('\'')),
+    }
+}
+
+/// `i` is at the opening `"`; returns the index past the closing one.
+fn scan_double(chars: &[char], mut i: usize) -> Result<usize, ParseError> {
+    i += 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '"' => return Ok(i + 1),
+            '$' if chars.get(i + 1) == Some(&'{') => i = scan_braced(chars, i + 1)?,
+            '$' if chars.get(i + 1) == Some(&'(') => i = scan_parens(chars, i + 1)?,
+            '`' => i = scan_backquoted(chars, i)?,
+            _ => i += 1,
+        }
+    }
+    Err(ParseError::Unmatched('"'))
+}
+
+/// `i` is at the `{` of `${`; returns the index past the matching `}`.
+fn scan_braced(chars: &[char], mut i: usize) -> Result<usize, ParseError> {
+    i += 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '\'' => i = scan_single(chars, i)?,
+            '"' => i = scan_double(chars, i)?,
+            '$' if chars.get(i + 1) == Some(&'{') => i = scan_braced(chars, i + 1)?,
+            '$' if chars.get(i + 1) == Some(&'(') => i = scan_parens(chars, i + 1)?,
+            '`' => i = scan_backquoted(chars, i)?,
+            '}' => return Ok(i + 1),
+            _ => i += 1,
+        }
+    }
+    Err(ParseError::Unmatched('}'))
+}
+
+/// `i` is at the `(` of `$(`; returns the index past the matching `)`.
+fn scan_parens(chars: &[char], mut i: usize) -> Result<usize, ParseError> {
+    let mut depth = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '\'' => i = scan_single(chars, i)?,
+            '"' => i = scan_double(chars, i)?,
+            '`' => i = scan_backquoted(chars, i)?,
+            '$' if chars.get(i + 1) == Some(&'{') => i = scan_braced(chars, i + 1)?,
+            '(' => {
+                depth += 1;
+                i += 1;
+            }
+            ')' if depth == 1 => return Ok(i + 1),
+            ')' => {
+                depth -= 1;
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    Err(ParseError::Unmatched(')'))
+}
+
+/// `i` is at an opening `` ` ``; returns the index past the closing one.
+fn scan_backquoted(chars: &[char], mut i: usize) -> Result<usize, ParseError> {
+    i += 1;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '`' => return Ok(i + 1),
+            _ => i += 1,
+        }
+    }
+    Err(ParseError::Unmatched('`'))
+}
+
+/// Splits `line` into words at runs of unquoted whitespace, spaces, tabs
+/// and newlines alike, removing the quotes and escapes.
+pub fn split_quoted_line(line: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut cur = String::new();
+
+    let mut in_single = false;
+    let mut in_double = false;
+    let mut esc = false; // backslash escape (context-sensitive)
+    let mut quoted = false; // current word had quotes, so keep it even if empty
+
+    for ch in line.chars() {
+        if in_double {
+            // --- inside "double quotes" ---
+
+            if esc {
+                // ex: echo "he\(here)llo"        -> \"  => push '"'
+                //     echo "path\\(here)tmp"     -> \\  => push '\'
+                //     echo "x\y"                 -> \y  => push '\' and 'y'
+                match ch {
+                    '"' | '\\' => cur.push(ch), //e.g. echo "\\n" it will come here for initial \ and for rest,
+                    // it will go through next match ch
+                    other => {
+                        cur.push('\\'); //it will come here for echo "\n" will push both
+                        cur.push(other);
+                    }
+                }
+                esc = false;
+                continue;
+            }
+
+            match ch {
+                '\\' => {
+                    // ex: echo "a\(here)b"       -> start escape inside "
+                    esc = true
+                }
+                '"' => {
+                    // ex: echo "hello"(here)     -> end "
+                    in_double = false
+                }
+                c => {
+                    // ex: echo "he(re)llo world" -> take literally (spaces included)
+                    cur.push(c)
+                }
+            }
+            continue;
+        }
+
+        if in_single {
+            // --- inside 'single quotes' ---
+
+            match ch {
+                '\'' => {
+                    // ex: echo 'hello'(here)     -> end '
+                    in_single = false
+                }
+                c => {
+                    // ex: echo 'he(re)llo world' -> take literally (no escapes)
+                    cur.push(c)
+                }
+            }
+            continue;
+        }
+
+        // --- outside quotes (normal) ---
+
+        if esc {
+            // ex: echo a\(here) b               -> escape makes next char literal (incl. space)
+            cur.push(ch);
+            esc = false;
+            continue;
+        }
+
+        match ch {
+            '\'' => {
+                // ex: echo '(here)hello'         -> start '
+                in_single = true;
+                quoted = true;
+            }
+            '"' => {
+                // ex: echo "(here)hello"         -> start "
+                in_double = true;
+                quoted = true;
+            }
+            '\\' => {
+                // ex: echo a\(here) b            -> begin escape (space/quote/etc. next)
+                esc = true
+            }
+            c if c.is_ascii_whitespace() => {
+                //split on whitespace (collapse runs)
+                if !cur.is_empty() || quoted {
+                    parts.push(std::mem::take(&mut cur));
+                }
+                quoted = false;
+            }
+            c => {
+                // ex: echo he(re)llo             -> normal char outside quotes
+                cur.push(c)
+            }
+        }
+    }
+
+    // trailing backslash outside quotes → keep it literally
+    // ex: echo foo\                        -> becomes "foo\"
+    if esc {
+        cur.push('\\');
+    }
+
+    if !cur.is_empty() || quoted {
+        parts.push(cur);
+    }
+
+    parts
+}
+
+/// Quotes `word` so the shell reads it back as that same word: as is if
+/// nothing in it is special, with backslashes before the special
+/// characters otherwise, or in single quotes if it has control characters,
+/// which a backslash can't protect.
+pub fn quote(word: &str) -> String {
+    let safe = |c: char| c.is_alphanumeric() || "%+,-./:=@_^".contains(c);
+    if word.is_empty() {
+        return "''".to_string();
+    }
+    if word.chars().any(char::is_control) {
+        return format!("'{}'", word.replace('\'', "'\\''"));
+    }
+    let mut out = String::new();
+    for (i, c) in word.chars().enumerate() {
+        // `~` only expands at the start, and `#` only starts a comment there
+        let special = !safe(c) && (i == 0 || !matches!(c, '~' | '#'));
+        if special {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str) -> Token {
+        Token::Word(text.to_string())
+    }
+
+    #[test]
+    fn takes_the_longest_operator() {
+        let tokens = tokenize("a&&b||c;;d|&e&>f&>>g").unwrap();
+        assert_eq!(
+            tokens,
+            [
+                word("a"),
+                Token::AndIf,
+                word("b"),
+                Token::OrIf,
+                word("c"),
+                Token::DSemi,
+                word("d"),
+                Token::PipeAmp,
+                word("e"),
+                Token::AndGreat,
+                word("f"),
+                Token::AndDGreat,
+                word("g"),
+            ]
+        );
+    }
+
+    #[test]
+    fn redirections_glued_to_words_split_off() {
+        let tokens = tokenize("echo hi>out").unwrap();
+        assert_eq!(
+            tokens,
+            [word("echo"), word("hi"), Token::Great, word("out")]
+        );
+        assert_eq!(
+            tokenize("cat<in").unwrap(),
+            [word("cat"), Token::Less, word("in")]
+        );
+        // digits that are part of a word aren't a file descriptor
+        assert_eq!(
+            tokenize("cmd2>err").unwrap(),
+            [word("cmd2"), Token::Great, word("err")]
+        );
+        assert_eq!(
+            tokenize("cmd 2>err 2>&1").unwrap(),
+            [
+                word("cmd"),
+                Token::IoNumber(2),
+                Token::Great,
+                word("err"),
+                Token::IoNumber(2),
+                Token::GreatAnd,
+                word("1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn here_document_bodies_come_from_the_following_lines() {
+        let doc = |delimiter: &str, body: &str| Token::HereDoc(delimiter.into(), body.into());
+        assert_eq!(
+            tokenize("cat <<A x <<-'B'\none\nA\n\ttwo\n\tB\necho").unwrap(),
+            [
+                word("cat"),
+                Token::DLess,
+                doc("A", "one\n"),
+                word("x"),
+                Token::DLessDash,
+                doc("'B'", "two\n"),
+                Token::Newline,
+                word("echo"),
+            ]
+        );
+        // no delimiter yet, so more lines are wanted
+        assert!(tokenize("cat <<A\none\n").unwrap_err().is_incomplete());
+    }
+
+    #[test]
+    fn quoted_operators_stay_in_the_word() {
+        assert_eq!(
+            tokenize(r#"echo 'a;b' "c|d" e\&f"#).unwrap(),
+            [word("echo"), word("'a;b'"), word("\"c|d\""), word(r"e\&f"),]
+        );
+        assert_eq!(
+            tokenize("echo $(a; b) `c|d`").unwrap(),
+            [word("echo"), word("$(a; b)"), word("`c|d`"),]
+        );
+    }
+
+    #[test]
+    fn operators_next_to_quotes() {
+        assert_eq!(
+            tokenize(r#"echo a&&echo "b|c"&"#).unwrap(),
+            [
+                word("echo"),
+                word("a"),
+                Token::AndIf,
+                word("echo"),
+                word("\"b|c\""),
+                Token::Amp,
+            ]
+        );
+        assert_eq!(
+            tokenize("'x'|\"y\";'z'").unwrap(),
+            [
+                word("'x'"),
+                Token::Pipe,
+                word("\"y\""),
+                Token::Semi,
+                word("'z'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_on_unquoted_whitespace_only() {
+        assert_eq!(split_quoted_line("a \t b\n c"), ["a", "b", "c"]);
+        assert_eq!(split_quoted_line("'a \t b' \"c\td\""), ["a \t b", "c\td"]);
+        assert_eq!(split_quoted_line(r"a\ b '' x"), ["a b", "", "x"]);
+        assert_eq!(split_quoted_line(r#""\"\\\n""#), [r#""\\n"#]);
+    }
+
+    #[test]
+    fn long_quoted_word_is_kept_whole() {
+        let long = "x".repeat(1 << 22);
+        let start = std::time::Instant::now();
+        let tokens = tokenize(&format!("echo \"{}\" '{}'", long, long)).unwrap();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(split_quoted_line(&format!("\"{}\"", long)), [long.as_str()]);
+        // scanning char by char once: quadratic would take hours here
+        assert!(start.elapsed().as_secs() < 10, "took {:?}", start.elapsed());
+    }
+
+    #[test]
+    fn unterminated_quotes_are_reported() {
+        assert_eq!(tokenize("echo 'abc"), Err(ParseError::Unmatched('\'')));
+        assert_eq!(tokenize("echo \"abc"), Err(ParseError::Unmatched('"')));
+        assert_eq!(tokenize("echo ${abc"), Err(ParseError::Unmatched('}')));
+    }
+
+    #[test]
+    fn positions_count_lines_and_columns() {
+        let tokens = tokenize_with_positions("echo a\n  fi", true).unwrap();
+        let positions: Vec<Position> = tokens.into_iter().map(|(_, at)| at).collect();
+        assert_eq!(positions[1], Position { line: 1, column: 6 });
+        assert_eq!(positions[3], Position { line: 2, column: 3 });
+        assert_eq!(positions[3].to_string(), "line 2, column 3");
+    }
+
+    #[test]
+    fn printf_q_quoting_splits_back_to_the_word() {
+        for word in [
+            "plain", "", "a b", "it's", "\"q\"", "a\\b", "$HOME", "~x", "a\tb\n", "*?[]",
+        ] {
+            assert_eq!(split_quoted_line(&quote(word)), [word], "{:?}", quote(word));
+        }
+    }
+
+    #[test]
+    fn comments_only_start_at_a_word() {
+        assert_eq!(
+            tokenize("echo a#b # note").unwrap(),
+            [word("echo"), word("a#b")]
+        );
+        let tokens = tokenize_with_positions("echo # note", false).unwrap();
+        let words: Vec<Token> = tokens.into_iter().map(|(tok, _)| tok).collect();
+        assert_eq!(words, [word("echo"), word("#"), word("note")]);
+    }
+}
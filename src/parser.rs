@@ -0,0 +1,1242 @@
+use crate::alias;
+use crate::lexer::{tokenize_with_positions, Position, Token};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use thiserror::Error;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("syntax error: unexpected end of file")]
+    UnexpectedEof,
+    /// A quote or `${` still open at the end of the input.
+    #[error("unexpected EOF while looking for matching `{0}'")]
+    Unmatched(char),
+    #[error("syntax error near unexpected token `{0}' ({1})")]
+    Unexpected(String, Position),
+    /// Compound commands or `[[ ]]` groups nested deeper than
+    /// [`MAX_NESTING`].
+    #[error("syntax error: commands nested too deeply ({0})")]
+    TooDeep(Position),
+}
+
+impl ParseError {
+    /// Whether more input could still make a command of it, as when it
+    /// ends inside quotes or before a compound command's closing word.
+    pub fn is_incomplete(&self) -> bool {
+        matches!(self, ParseError::UnexpectedEof | ParseError::Unmatched(_))
+    }
+
+    /// The error with its position counted from the start of the input
+    /// rather than of the command, which began on line `first`.
+    pub fn counted_from_line(self, first: usize) -> ParseError {
+        match self {
+            ParseError::Unexpected(token, mut at) => {
+                at.line += first - 1;
+                ParseError::Unexpected(token, at)
+            }
+            ParseError::TooDeep(mut at) => {
+                at.line += first - 1;
+                ParseError::TooDeep(at)
+            }
+            e => e,
+        }
+    }
+}
+
+/// Commands separated by `;`, `&` or newlines.
+#[derive(Debug, Clone, Default)]
+pub struct List {
+    pub items: Vec<ListItem>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListItem {
+    pub and_or: AndOr,
+    /// Ended by `&`, so it runs as a background job.
+    pub background: bool,
+}
+
+/// A chain like `a && b || c`, evaluated left to right.
+#[derive(Debug, Clone)]
+pub struct AndOr {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+}
+
+/// Commands joined by `|`, each one's stdout feeding the next one's stdin.
+#[derive(Debug, Clone)]
+pub struct Pipeline {
+    /// `time cmd`: how long it took goes to stderr afterwards.
+    pub time: Option<Timing>,
+    /// `! cmd`: the status is inverted.
+    pub negated: bool,
+    pub commands: Vec<Command>,
+}
+
+/// How `time` reports on its pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    /// As `TIMEFORMAT` says, or bash's default format.
+    Format,
+    /// `time -p`: the POSIX format, whatever `TIMEFORMAT` says.
+    Posix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Simple(SimpleCommand),
+    /// A compound command and the redirections that apply to all of it.
+    Compound(CompoundCommand, Vec<Redirect>),
+    /// `name() compound`: defines a function that runs the compound
+    /// command, redirections and all, each time it's called.
+    Function(String, Rc<Command>),
+}
+
+#[derive(Debug, Clone)]
+pub enum CompoundCommand {
+    /// `( list )`, run in an isolated copy of the shell state.
+    Subshell(List),
+    /// `{ list; }`, run in the current shell as a unit.
+    Group(List),
+    /// `if`/`elif` conditions with their bodies, then the `else` body.
+    If {
+        branches: Vec<(List, List)>,
+        otherwise: Option<List>,
+    },
+    /// `while cond; do body; done`, or `until` when `until` is set.
+    While {
+        until: bool,
+        cond: List,
+        body: List,
+    },
+    /// `for var in words; do body; done`; `words` is `None` without `in`.
+    For {
+        var: String,
+        words: Option<Vec<String>>,
+        body: List,
+    },
+    Case {
+        word: String,
+        items: Vec<CaseItem>,
+    },
+    /// `[[ expression ]]`.
+    Conditional(CondExpr),
+}
+
+/// The expression of a `[[ ... ]]` command. Its words are still
+/// unexpanded.
+#[derive(Debug, Clone)]
+pub enum CondExpr {
+    /// `-f file` and the other tests on one operand.
+    Unary(String, String),
+    /// `a == b` and the other tests on two operands, the operator between.
+    Binary(String, String, String),
+    /// A lone word, true unless it expands to nothing.
+    Word(String),
+    Not(Box<CondExpr>),
+    And(Box<CondExpr>, Box<CondExpr>),
+    Or(Box<CondExpr>, Box<CondExpr>),
+    /// `( expression )`.
+    Group(Box<CondExpr>),
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseItem {
+    /// Alternatives separated by `|`, still unexpanded.
+    pub patterns: Vec<String>,
+    pub body: List,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SimpleCommand {
+    /// The line of the input the command starts on, for `$LINENO`.
+    pub line: usize,
+    /// Leading `NAME=value` words.
+    pub assignments: Vec<Assignment>,
+    pub words: Vec<String>,
+    pub redirects: Vec<Redirect>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Assignment {
+    pub name: String,
+    /// `name[subscript]=value` sets one element of an array; the subscript
+    /// is still unexpanded.
+    pub subscript: Option<String>,
+    /// Still unexpanded.
+    pub value: String,
+    /// `NAME+=value`, appending to the current value.
+    pub append: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirOp {
+    /// `<`
+    In,
+    /// `>`
+    Out,
+    /// `>>`
+    Append,
+    /// `<&N`: make the fd a copy of input fd N (`-` closes it).
+    DupIn,
+    /// `>&N`: make the fd a copy of output fd N (`-` closes it).
+    DupOut,
+    /// `&>`: send both stdout and stderr to the file.
+    OutBoth,
+    /// `&>>`: append both stdout and stderr to the file.
+    AppendBoth,
+    /// `<<` or `<<-`: read the here-document in the redirection's body.
+    HereDoc,
+}
+
+#[derive(Debug, Clone)]
+pub struct Redirect {
+    pub fd: i32,
+    pub op: RedirOp,
+    /// The target word, still unexpanded; a here-document's delimiter as
+    /// typed.
+    pub target: String,
+    /// A here-document's text, unexpanded, with `<<-`'s tabs removed.
+    pub body: Option<String>,
+}
+
+impl Redirect {
+    /// Whether a here-document's body is taken literally, as it is when
+    /// any of its delimiter is quoted.
+    pub fn literal_body(&self) -> bool {
+        self.target.contains(['\'', '"', '\\'])
+    }
+}
+
+pub fn parse(line: &str) -> Result<List, ParseError> {
+    parse_tokens(tokenize_with_positions(line, true)?)
+}
+
+/// Parses interactive input with `shopt -u interactive_comments`, where
+/// `#` doesn't start a comment.
+pub fn parse_without_comments(line: &str) -> Result<List, ParseError> {
+    parse_tokens(tokenize_with_positions(line, false)?)
+}
+
+/// Parses `line` with the words in command position that name one of
+/// `aliases` expanded first, and with `#` comments if `comments` is set.
+/// `shell` names the shell in a warning about aliases nested too deeply.
+pub fn parse_with_aliases(
+    line: &str,
+    comments: bool,
+    aliases: &BTreeMap<String, String>,
+    shell: &str,
+) -> Result<List, ParseError> {
+    parse_tokens(alias::expand(
+        tokenize_with_positions(line, comments)?,
+        aliases,
+        shell,
+    ))
+}
+
+/// Parses tokens, each with where it starts for error messages.
+fn parse_tokens(tokens: Vec<(Token, Position)>) -> Result<List, ParseError> {
+    let (tokens, positions) = tokens.into_iter().unzip();
+    let mut parser = Parser {
+        tokens,
+        positions,
+        pos: 0,
+        depth: 0,
+    };
+    let list = parser.list()?;
+    match parser.peek() {
+        None => Ok(list),
+        Some(_) => Err(parser.unexpected()),
+    }
+}
+
+/// How deep compound commands may nest, so that a pathological input
+/// gives an error rather than overflowing the stack of the recursive
+/// parser.
+const MAX_NESTING: usize = 500;
+
+struct Parser {
+    tokens: Vec<Token>,
+    positions: Vec<Position>,
+    pos: usize,
+    /// How many compound commands and `[[ ]]` groups enclose the token at
+    /// hand.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    /// Takes the next token, which must be a word.
+    fn word(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Word(w)) => {
+                let word = w.clone();
+                self.pos += 1;
+                Ok(word)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Token::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    /// The error for the token at hand, which can't go where it is.
+    fn unexpected(&self) -> ParseError {
+        match (self.peek(), self.positions.get(self.pos)) {
+            (Some(tok), Some(&at)) => ParseError::Unexpected(tok.describe().to_string(), at),
+            _ => ParseError::UnexpectedEof,
+        }
+    }
+
+    /// Runs `parse` one level deeper, failing at the token just taken,
+    /// which opened the level, if that's too deep.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if self.depth == MAX_NESTING {
+            return Err(ParseError::TooDeep(self.positions[self.pos - 1]));
+        }
+        self.depth += 1;
+        let parsed = parse(self);
+        self.depth -= 1;
+        parsed
+    }
+
+    /// Parses and-or chains until a token that can't start a command.
+    fn list(&mut self) -> Result<List, ParseError> {
+        let mut list = List::default();
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                Some(Token::Word(w)) if is_list_terminator(w) => return Ok(list),
+                Some(Token::Word(_))
+                | Some(Token::LParen)
+                | Some(Token::Less)
+                | Some(Token::DLess)
+                | Some(Token::DLessDash)
+                | Some(Token::Great)
+                | Some(Token::DGreat)
+                | Some(Token::LessAnd)
+                | Some(Token::GreatAnd)
+                | Some(Token::AndGreat)
+                | Some(Token::AndDGreat)
+                | Some(Token::IoNumber(_)) => {}
+                _ => return Ok(list),
+            }
+            let and_or = self.and_or()?;
+            let background = self.peek() == Some(&Token::Amp);
+            list.items.push(ListItem { and_or, background });
+            match self.peek() {
+                Some(Token::Semi) | Some(Token::Newline) | Some(Token::Amp) => {
+                    self.pos += 1;
+                }
+                _ => return Ok(list),
+            }
+        }
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.peek() {
+                Some(Token::AndIf) => Connector::And,
+                Some(Token::OrIf) => Connector::Or,
+                _ => break,
+            };
+            self.pos += 1;
+            self.skip_newlines();
+            rest.push((connector, self.pipeline()?));
+        }
+        Ok(AndOr { first, rest })
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, ParseError> {
+        let mut time = self.time_prefix();
+        let negated = matches!(self.peek(), Some(Token::Word(w)) if w == "!");
+        if negated {
+            self.pos += 1;
+            time = time.or_else(|| self.time_prefix());
+        }
+        // a bare `time` times nothing
+        let end = matches!(
+            self.peek(),
+            None | Some(Token::Newline | Token::Semi | Token::Amp | Token::AndIf | Token::OrIf)
+        );
+        if time.is_some() && end {
+            return Ok(Pipeline {
+                time,
+                negated,
+                commands: Vec::new(),
+            });
+        }
+        let mut commands = vec![self.command()?];
+        while let Some(pipe @ (Token::Pipe | Token::PipeAmp)) = self.peek() {
+            if *pipe == Token::PipeAmp {
+                // `a |& b` is `a 2>&1 | b`, after a's own redirections
+                let both = Redirect {
+                    fd: 2,
+                    op: RedirOp::DupOut,
+                    target: "1".to_string(),
+                    body: None,
+                };
+                if let Some(cmd) = commands.last_mut() {
+                    match cmd {
+                        Command::Simple(simple) => simple.redirects.push(both),
+                        Command::Compound(_, redirects) => redirects.push(both),
+                        // defining a function writes nothing
+                        Command::Function(..) => {}
+                    }
+                }
+            }
+            self.pos += 1;
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+        Ok(Pipeline {
+            time,
+            negated,
+            commands,
+        })
+    }
+
+    /// `time` or `time -p` in front of a pipeline.
+    fn time_prefix(&mut self) -> Option<Timing> {
+        if !matches!(self.peek(), Some(Token::Word(w)) if w == "time") {
+            return None;
+        }
+        self.pos += 1;
+        if matches!(self.peek(), Some(Token::Word(w)) if w == "-p") {
+            self.pos += 1;
+            return Some(Timing::Posix);
+        }
+        Some(Timing::Format)
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        let compound = match self.peek() {
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let body = self.compound_body()?;
+                self.expect(&Token::RParen)?;
+                CompoundCommand::Subshell(body)
+            }
+            Some(Token::Word(w)) => match w.as_str() {
+                "{" => {
+                    self.pos += 1;
+                    let body = self.compound_body()?;
+                    self.expect_word("}")?;
+                    CompoundCommand::Group(body)
+                }
+                "if" => self.if_command()?,
+                "while" | "until" => {
+                    let until = w == "until";
+                    self.pos += 1;
+                    let cond = self.compound_body()?;
+                    let body = self.do_group()?;
+                    CompoundCommand::While { until, cond, body }
+                }
+                "for" => self.for_command()?,
+                "case" => self.case_command()?,
+                "[[" => self.conditional()?,
+                _ if self.function_ahead() => return self.function(),
+                _ => return Ok(Command::Simple(self.simple_command()?)),
+            },
+            Some(_) => return Ok(Command::Simple(self.simple_command()?)),
+            None => return Err(ParseError::UnexpectedEof),
+        };
+        Ok(Command::Compound(compound, self.redirects()?))
+    }
+
+    /// Whether the tokens at hand are `name ( )`, starting a function.
+    fn function_ahead(&self) -> bool {
+        let name = matches!(self.peek(), Some(Token::Word(w)) if is_valid_name(w));
+        let parens = self.tokens.get(self.pos + 1..self.pos + 3);
+        name && parens == Some(&[Token::LParen, Token::RParen][..])
+    }
+
+    /// `name() compound`, with newlines allowed before the body.
+    fn function(&mut self) -> Result<Command, ParseError> {
+        let name = self.word()?;
+        self.pos += 2;
+        self.skip_newlines();
+        let compound = match self.peek() {
+            Some(Token::LParen) => true,
+            Some(Token::Word(w)) => {
+                matches!(
+                    w.as_str(),
+                    "{" | "if" | "while" | "until" | "for" | "case" | "[["
+                )
+            }
+            _ => false,
+        };
+        if !compound {
+            return Err(self.unexpected());
+        }
+        Ok(Command::Function(name, Rc::new(self.command()?)))
+    }
+
+    fn if_command(&mut self) -> Result<CompoundCommand, ParseError> {
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+        // at `if`, then at each `elif`
+        loop {
+            self.pos += 1;
+            let cond = self.compound_body()?;
+            self.expect_word("then")?;
+            let body = self.compound_body()?;
+            branches.push((cond, body));
+            match self.peek() {
+                Some(Token::Word(w)) if w == "elif" => continue,
+                Some(Token::Word(w)) if w == "else" => {
+                    self.pos += 1;
+                    otherwise = Some(self.compound_body()?);
+                    break;
+                }
+                _ => break,
+            }
+        }
+        self.expect_word("fi")?;
+        Ok(CompoundCommand::If {
+            branches,
+            otherwise,
+        })
+    }
+
+    /// `do list done`, the body of a loop.
+    fn do_group(&mut self) -> Result<List, ParseError> {
+        self.expect_word("do")?;
+        let body = self.compound_body()?;
+        self.expect_word("done")?;
+        Ok(body)
+    }
+
+    fn for_command(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1;
+        let var = match self.peek() {
+            Some(Token::Word(w)) if is_valid_name(w) => self.word()?,
+            _ => return Err(self.unexpected()),
+        };
+        self.skip_newlines();
+        let mut words = None;
+        if matches!(self.peek(), Some(Token::Word(w)) if w == "in") {
+            self.pos += 1;
+            let mut list = Vec::new();
+            while let Some(Token::Word(w)) = self.peek() {
+                list.push(w.clone());
+                self.pos += 1;
+            }
+            words = Some(list);
+        }
+        if matches!(self.peek(), Some(Token::Semi)) {
+            self.pos += 1;
+        }
+        self.skip_newlines();
+        let body = self.do_group()?;
+        Ok(CompoundCommand::For { var, words, body })
+    }
+
+    fn case_command(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1;
+        let word = self.word()?;
+        self.skip_newlines();
+        self.expect_word("in")?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_newlines();
+            if matches!(self.peek(), Some(Token::Word(w)) if w == "esac") {
+                break;
+            }
+            if self.peek() == Some(&Token::LParen) {
+                self.pos += 1;
+            }
+            let mut patterns = Vec::new();
+            loop {
+                patterns.push(self.word()?);
+                if self.peek() != Some(&Token::Pipe) {
+                    break;
+                }
+                self.pos += 1;
+            }
+            self.expect(&Token::RParen)?;
+            let body = self.nested(Self::list)?;
+            items.push(CaseItem { patterns, body });
+            if self.peek() != Some(&Token::DSemi) {
+                break;
+            }
+            self.pos += 1;
+        }
+        self.skip_newlines();
+        self.expect_word("esac")?;
+        Ok(CompoundCommand::Case { word, items })
+    }
+
+    fn conditional(&mut self) -> Result<CompoundCommand, ParseError> {
+        self.pos += 1;
+        let expr = self.cond_or()?;
+        self.expect_word("]]")?;
+        Ok(CompoundCommand::Conditional(expr))
+    }
+
+    fn cond_or(&mut self) -> Result<CondExpr, ParseError> {
+        let mut expr = self.cond_and()?;
+        while self.peek() == Some(&Token::OrIf) {
+            self.pos += 1;
+            self.skip_newlines();
+            expr = CondExpr::Or(Box::new(expr), Box::new(self.cond_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn cond_and(&mut self) -> Result<CondExpr, ParseError> {
+        let mut expr = self.cond_not()?;
+        while self.peek() == Some(&Token::AndIf) {
+            self.pos += 1;
+            self.skip_newlines();
+            expr = CondExpr::And(Box::new(expr), Box::new(self.cond_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn cond_not(&mut self) -> Result<CondExpr, ParseError> {
+        if matches!(self.peek(), Some(Token::Word(w)) if w == "!") {
+            self.pos += 1;
+            return Ok(CondExpr::Not(Box::new(self.nested(Self::cond_not)?)));
+        }
+        self.cond_primary()
+    }
+
+    fn cond_primary(&mut self) -> Result<CondExpr, ParseError> {
+        self.skip_newlines();
+        let word = match self.peek() {
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let expr = self.nested(Self::cond_or)?;
+                self.skip_newlines();
+                self.expect(&Token::RParen)?;
+                return Ok(CondExpr::Group(Box::new(expr)));
+            }
+            Some(Token::Word(w)) if w != "]]" => w.clone(),
+            _ => return Err(self.unexpected()),
+        };
+        self.pos += 1;
+        let op = match self.peek() {
+            Some(Token::Word(w)) if is_cond_binary(w) => w.clone(),
+            Some(Token::Less) => "<".to_string(),
+            Some(Token::Great) => ">".to_string(),
+            _ if is_cond_unary(&word) => {
+                let operand = self.cond_operand()?;
+                return Ok(CondExpr::Unary(word, operand));
+            }
+            _ => return Ok(CondExpr::Word(word)),
+        };
+        self.pos += 1;
+        let rhs = match op.as_str() {
+            "=~" => self.cond_regex()?,
+            _ => self.cond_operand()?,
+        };
+        Ok(CondExpr::Binary(word, op, rhs))
+    }
+
+    /// The word an operator in `[[ ... ]]` applies to.
+    fn cond_operand(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Word(w)) if w != "]]" => {
+                let word = w.clone();
+                self.pos += 1;
+                Ok(word)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// The regex after `=~`, which may have parentheses and `|` in it:
+    /// the tokens up to `]]`, `&&` or `||` outside parentheses are joined
+    /// back into one word.
+    fn cond_regex(&mut self) -> Result<String, ParseError> {
+        let mut regex = String::new();
+        let mut depth = 0;
+        loop {
+            match self.peek() {
+                Some(Token::Word(w)) if w == "]]" && depth == 0 => break,
+                Some(Token::AndIf | Token::OrIf) if depth == 0 => break,
+                Some(Token::RParen) if depth == 0 => break,
+                Some(Token::Newline | Token::IoNumber(_)) | None => break,
+                Some(tok) => {
+                    match tok {
+                        Token::LParen => depth += 1,
+                        Token::RParen => depth -= 1,
+                        _ => {}
+                    }
+                    regex.push_str(tok.describe());
+                    self.pos += 1;
+                }
+            }
+        }
+        if regex.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(regex)
+    }
+
+    /// A list that must contain at least one command.
+    fn compound_body(&mut self) -> Result<List, ParseError> {
+        let body = self.nested(Self::list)?;
+        if body.items.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(body)
+    }
+
+    fn expect(&mut self, tok: &Token) -> Result<(), ParseError> {
+        if self.peek() == Some(tok) {
+            self.pos += 1;
+            return Ok(());
+        }
+        Err(self.unexpected())
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(Token::Word(w)) if w == word => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let line = self.positions.get(self.pos).map_or(1, |at| at.line);
+        let mut cmd = SimpleCommand {
+            line,
+            ..SimpleCommand::default()
+        };
+        loop {
+            if let Some(redirect) = self.redirect()? {
+                cmd.redirects.push(redirect);
+                continue;
+            }
+            let Some(Token::Word(word)) = self.peek() else {
+                break;
+            };
+            let word = word.clone();
+            self.pos += 1;
+            match split_assignment(&word) {
+                Some(assignment) if cmd.words.is_empty() => cmd.assignments.push(assignment),
+                _ => cmd.words.push(word),
+            }
+        }
+        if cmd.words.is_empty() && cmd.assignments.is_empty() && cmd.redirects.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(cmd)
+    }
+
+    /// Redirections following a compound command.
+    fn redirects(&mut self) -> Result<Vec<Redirect>, ParseError> {
+        let mut redirects = Vec::new();
+        while let Some(redirect) = self.redirect()? {
+            redirects.push(redirect);
+        }
+        Ok(redirects)
+    }
+
+    fn redirect(&mut self) -> Result<Option<Redirect>, ParseError> {
+        let start = self.pos;
+        let fd = match self.peek() {
+            Some(Token::IoNumber(fd)) => {
+                let fd = *fd;
+                self.pos += 1;
+                Some(fd)
+            }
+            _ => None,
+        };
+        let op = match self.peek() {
+            Some(Token::Less) => RedirOp::In,
+            Some(Token::DLess | Token::DLessDash) => RedirOp::HereDoc,
+            Some(Token::Great) => RedirOp::Out,
+            Some(Token::DGreat) => RedirOp::Append,
+            Some(Token::LessAnd) => RedirOp::DupIn,
+            Some(Token::GreatAnd) => RedirOp::DupOut,
+            Some(Token::AndGreat) => RedirOp::OutBoth,
+            Some(Token::AndDGreat) => RedirOp::AppendBoth,
+            _ => {
+                self.pos = start;
+                return Ok(None);
+            }
+        };
+        self.pos += 1;
+        let (target, body) = match (op, self.peek()) {
+            (RedirOp::HereDoc, Some(Token::HereDoc(delimiter, body))) => {
+                let here_doc = (delimiter.clone(), Some(body.clone()));
+                self.pos += 1;
+                here_doc
+            }
+            (RedirOp::HereDoc, _) => return Err(self.unexpected()),
+            _ => (self.word()?, None),
+        };
+        let input = matches!(op, RedirOp::In | RedirOp::DupIn | RedirOp::HereDoc);
+        let fd = fd.unwrap_or(if input { 0 } else { 1 });
+        Ok(Some(Redirect {
+            fd,
+            op,
+            target,
+            body,
+        }))
+    }
+}
+
+/// The reserved words, special only where a command name could be.
+pub const KEYWORDS: &[&str] = &[
+    "!", "[[", "]]", "{", "}", "case", "do", "done", "elif", "else", "esac", "fi", "for",
+    "function", "if", "in", "then", "time", "until", "while",
+];
+
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.contains(&word)
+}
+
+/// The operators of `[[ ... ]]` tests on one operand.
+fn is_cond_unary(word: &str) -> bool {
+    matches!(
+        word,
+        "-a" | "-b"
+            | "-c"
+            | "-d"
+            | "-e"
+            | "-f"
+            | "-g"
+            | "-h"
+            | "-k"
+            | "-L"
+            | "-n"
+            | "-p"
+            | "-r"
+            | "-s"
+            | "-S"
+            | "-t"
+            | "-u"
+            | "-v"
+            | "-w"
+            | "-x"
+            | "-z"
+    )
+}
+
+/// The operators of `[[ ... ]]` tests on two operands, but for `<` and
+/// `>`, which are tokens of their own.
+fn is_cond_binary(word: &str) -> bool {
+    matches!(
+        word,
+        "=" | "=="
+            | "!="
+            | "=~"
+            | "-eq"
+            | "-ne"
+            | "-lt"
+            | "-le"
+            | "-gt"
+            | "-ge"
+            | "-nt"
+            | "-ot"
+            | "-ef"
+    )
+}
+
+/// Reserved words that close a compound command's body.
+fn is_list_terminator(word: &str) -> bool {
+    matches!(
+        word,
+        "}" | "then" | "elif" | "else" | "fi" | "do" | "done" | "esac"
+    )
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() => {}
+        _ => return false,
+    }
+    chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+/// Splits `NAME=value` or `NAME+=value` into its parts; anything else
+/// isn't an assignment.
+pub fn split_assignment(word: &str) -> Option<Assignment> {
+    let name_len = word.find(|c: char| c != '_' && !c.is_ascii_alphanumeric())?;
+    let (name, rest) = word.split_at(name_len);
+    // the subscript runs to the matching `]`, so it can hold a `=` itself
+    let (subscript, rest) = match rest.strip_prefix('[') {
+        Some(inner) => {
+            let mut depth = 0;
+            let end = inner.find(|c| {
+                match c {
+                    '[' => depth += 1,
+                    ']' if depth == 0 => return true,
+                    ']' => depth -= 1,
+                    _ => {}
+                }
+                false
+            })?;
+            (Some(inner[..end].to_string()), &inner[end + 1..])
+        }
+        None => (None, rest),
+    };
+    let (append, value) = match rest.strip_prefix('+') {
+        Some(rest) => (true, rest.strip_prefix('=')?),
+        None => (false, rest.strip_prefix('=')?),
+    };
+    is_valid_name(name).then(|| Assignment {
+        name: name.to_string(),
+        subscript,
+        value: value.to_string(),
+        append,
+    })
+}
+
+// The AST printed back as shell text, as `jobs` shows a job's command.
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                f.write_str(if self.items[i - 1].background {
+                    " "
+                } else {
+                    "; "
+                })?;
+            }
+            write!(f, "{}", item.and_or)?;
+            if item.background {
+                f.write_str(" &")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AndOr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (connector, pipeline) in &self.rest {
+            let op = match connector {
+                Connector::And => "&&",
+                Connector::Or => "||",
+            };
+            write!(f, " {} {}", op, pipeline)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.time {
+            Some(Timing::Format) => f.write_str("time ")?,
+            Some(Timing::Posix) => f.write_str("time -p ")?,
+            None => {}
+        }
+        if self.negated {
+            f.write_str("! ")?;
+        }
+        for (i, cmd) in self.commands.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" | ")?;
+            }
+            write!(f, "{}", cmd)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Simple(simple) => write!(f, "{}", simple),
+            Command::Compound(compound, redirects) => {
+                write!(f, "{}", compound)?;
+                for redirect in redirects {
+                    write!(f, " {}", redirect)?;
+                }
+                Ok(())
+            }
+            Command::Function(name, body) => write!(f, "{}() {}", name, body),
+        }
+    }
+}
+
+impl fmt::Display for CompoundCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompoundCommand::Subshell(body) => write!(f, "( {} )", body),
+            CompoundCommand::Group(body) => write!(f, "{{ {}; }}", body),
+            CompoundCommand::If {
+                branches,
+                otherwise,
+            } => {
+                for (i, (cond, body)) in branches.iter().enumerate() {
+                    let keyword = if i == 0 { "if" } else { "elif" };
+                    write!(f, "{} {}; then {}; ", keyword, cond, body)?;
+                }
+                if let Some(body) = otherwise {
+                    write!(f, "else {}; ", body)?;
+                }
+                f.write_str("fi")
+            }
+            CompoundCommand::While { until, cond, body } => {
+                let keyword = if *until { "until" } else { "while" };
+                write!(f, "{} {}; do {}; done", keyword, cond, body)
+            }
+            CompoundCommand::For { var, words, body } => {
+                write!(f, "for {}", var)?;
+                if let Some(words) = words {
+                    f.write_str(" in")?;
+                    for word in words {
+                        write!(f, " {}", word)?;
+                    }
+                }
+                write!(f, "; do {}; done", body)
+            }
+            CompoundCommand::Case { word, items } => {
+                write!(f, "case {} in", word)?;
+                for item in items {
+                    write!(f, " {}) {};;", item.patterns.join(" | "), item.body)?;
+                }
+                f.write_str(" esac")
+            }
+            CompoundCommand::Conditional(expr) => write!(f, "[[ {} ]]", expr),
+        }
+    }
+}
+
+impl fmt::Display for CondExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CondExpr::Unary(op, word) => write!(f, "{} {}", op, word),
+            CondExpr::Binary(lhs, op, rhs) => write!(f, "{} {} {}", lhs, op, rhs),
+            CondExpr::Word(word) => f.write_str(word),
+            CondExpr::Not(expr) => write!(f, "! {}", expr),
+            CondExpr::And(lhs, rhs) => write!(f, "{} && {}", lhs, rhs),
+            CondExpr::Or(lhs, rhs) => write!(f, "{} || {}", lhs, rhs),
+            CondExpr::Group(expr) => write!(f, "( {} )", expr),
+        }
+    }
+}
+
+impl fmt::Display for SimpleCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let assignments = self.assignments.iter().map(|a| {
+            let op = if a.append { "+=" } else { "=" };
+            match &a.subscript {
+                Some(subscript) => format!("{}[{}]{}{}", a.name, subscript, op, a.value),
+                None => format!("{}{}{}", a.name, op, a.value),
+            }
+        });
+        let redirects = self.redirects.iter().map(|r| r.to_string());
+        let parts: Vec<String> = assignments
+            .chain(self.words.iter().cloned())
+            .chain(redirects)
+            .collect();
+        f.write_str(&parts.join(" "))
+    }
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (op, default_fd) = match self.op {
+            RedirOp::In => ("<", 0),
+            RedirOp::Out => (">", 1),
+            RedirOp::Append => (">>", 1),
+            RedirOp::DupIn => ("<&", 0),
+            RedirOp::DupOut => (">&", 1),
+            RedirOp::OutBoth => ("&>", 1),
+            RedirOp::AppendBoth => ("&>>", 1),
+            RedirOp::HereDoc => ("<<", 0),
+        };
+        if self.fd != default_fd {
+            write!(f, "{}", self.fd)?;
+        }
+        match self.op {
+            RedirOp::DupIn | RedirOp::DupOut | RedirOp::HereDoc => {
+                write!(f, "{}{}", op, self.target)
+            }
+            _ => write!(f, "{} {}", op, self.target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `line` parsed and printed back as shell text.
+    fn reparsed(line: &str) -> String {
+        parse(line).unwrap().to_string()
+    }
+
+    fn error(line: &str) -> String {
+        parse(line).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_lists_and_pipelines() {
+        assert_eq!(reparsed("a | b && c || d; e & f"), "a | b && c || d; e & f");
+        assert_eq!(reparsed("a\n\nb"), "a; b");
+        assert_eq!(reparsed("x=1 y+=2 cmd arg"), "x=1 y+=2 cmd arg");
+    }
+
+    #[test]
+    fn parses_compound_commands() {
+        assert_eq!(reparsed("(a; b) > out"), "( a; b ) > out");
+        assert_eq!(reparsed("{ a; b; }"), "{ a; b; }");
+        assert_eq!(
+            reparsed("if a; then b; elif c; then d; else e; fi"),
+            "if a; then b; elif c; then d; else e; fi"
+        );
+        assert_eq!(reparsed("while a; do b; done"), "while a; do b; done");
+        assert_eq!(reparsed("until a\ndo b\ndone"), "until a; do b; done");
+        assert_eq!(
+            reparsed("for x in 1 2; do echo $x; done"),
+            "for x in 1 2; do echo $x; done"
+        );
+        assert_eq!(
+            reparsed("case $x in a|b) c;; *) d;; esac"),
+            "case $x in a | b) c;; *) d;; esac"
+        );
+        assert_eq!(
+            reparsed("[[ -n $x && ( a == b* ) ]]"),
+            "[[ -n $x && ( a == b* ) ]]"
+        );
+        assert_eq!(reparsed("f() { a; } > out"), "f() { a; } > out");
+        assert_eq!(reparsed("f ()\n(a)"), "f() ( a )");
+    }
+
+    #[test]
+    fn time_prefixes_a_whole_pipeline() {
+        assert_eq!(
+            reparsed("time -p a | b && time ! c"),
+            "time -p a | b && time ! c"
+        );
+        assert_eq!(reparsed("! time c; time"), "time ! c; time ");
+        assert_eq!(reparsed("echo time -p"), "echo time -p");
+        let list = parse("time -p a | b").unwrap();
+        let pipeline = &list.items[0].and_or.first;
+        assert_eq!(
+            (pipeline.time, pipeline.commands.len()),
+            (Some(Timing::Posix), 2)
+        );
+    }
+
+    #[test]
+    fn parses_redirections() {
+        assert_eq!(
+            reparsed("cmd <in >out 2>>log 2>&1 &>both"),
+            "cmd < in > out 2>> log 2>&1 &> both"
+        );
+    }
+
+    #[test]
+    fn keywords_are_only_special_as_command_names() {
+        assert_eq!(reparsed("echo if then fi"), "echo if then fi");
+    }
+
+    #[test]
+    fn unfinished_input_is_incomplete() {
+        for line in ["if a; then b", "while a", "(a", "a &&", "a |", "case x in"] {
+            assert!(parse(line).unwrap_err().is_incomplete(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn syntax_errors_name_the_token_and_its_position() {
+        assert_eq!(
+            error("echo a )"),
+            "syntax error near unexpected token `)' (column 8)"
+        );
+        assert_eq!(
+            error("fi"),
+            "syntax error near unexpected token `fi' (column 1)"
+        );
+        assert_eq!(
+            error("| a"),
+            "syntax error near unexpected token `|' (column 1)"
+        );
+        assert_eq!(
+            error("f() echo"),
+            "syntax error near unexpected token `echo' (column 5)"
+        );
+        let e = parse("if a\nthen b\nfi fi")
+            .unwrap_err()
+            .counted_from_line(3);
+        assert_eq!(
+            e.to_string(),
+            "syntax error near unexpected token `fi' (line 5, column 4)"
+        );
+    }
+
+    #[test]
+    fn double_semicolon_outside_case_is_an_error() {
+        assert_eq!(
+            error("echo a;; echo b"),
+            "syntax error near unexpected token `;;' (column 7)"
+        );
+        assert_eq!(
+            reparsed("case x in x) echo a;; esac"),
+            "case x in x) echo a;; esac"
+        );
+    }
+
+    #[test]
+    fn splits_assignments() {
+        let a = split_assignment("x+=1").unwrap();
+        assert_eq!(
+            (a.name.as_str(), a.value.as_str(), a.append),
+            ("x", "1", true)
+        );
+        assert!(split_assignment("1x=1").is_none());
+        assert!(split_assignment("=x").is_none());
+        let a = split_assignment("map[a=b]+=c=d").unwrap();
+        assert_eq!(
+            (a.name.as_str(), a.subscript.as_deref()),
+            ("map", Some("a=b"))
+        );
+        assert_eq!((a.value.as_str(), a.append), ("c=d", true));
+        assert!(split_assignment("x[1=2").is_none() && split_assignment("x[1]").is_none());
+        assert_eq!(reparsed("a[$i]=1 b[k]+=2"), "a[$i]=1 b[k]+=2");
+        assert!(is_valid_name("_a1") && !is_valid_name("a-b") && !is_valid_name(""));
+    }
+
+    #[test]
+    fn nesting_stops_at_the_limit() {
+        // debug builds need more stack for this than a test thread has
+        let nested = std::thread::Builder::new().stack_size(64 << 20).spawn(|| {
+            let groups = |n| format!("{}echo hi;{}", "{ ".repeat(n), " }".repeat(n));
+            assert!(parse(&groups(MAX_NESTING)).is_ok());
+            assert_eq!(
+                error(&groups(MAX_NESTING + 1)),
+                "syntax error: commands nested too deeply (column 1001)"
+            );
+            let subshells = format!("{}a{}", "(".repeat(100_000), ")".repeat(100_000));
+            assert!(matches!(parse(&subshells), Err(ParseError::TooDeep(_))));
+            let negations = format!("[[ {}x ]]", "! ".repeat(100_000));
+            assert!(matches!(parse(&negations), Err(ParseError::TooDeep(_))));
+        });
+        nested.unwrap().join().unwrap();
+    }
+}
@@ -0,0 +1,308 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::{env, ffi::OsStr, fs};
+
+/// Remembers where commands were found, like bash's `hash` table. Entries
+/// only hold for the `PATH` and `PATHEXT` they were found with, so the
+/// shell clears it whenever either changes.
+#[derive(Debug, Clone, Default)]
+pub struct PathCache {
+    found: RefCell<HashMap<String, PathBuf>>,
+}
+
+impl PathCache {
+    /// Looks `name` up in `path`, reusing an earlier answer as long as the
+    /// file is still executable.
+    pub fn find(
+        &self,
+        name: &str,
+        path: Option<&OsStr>,
+        pathext: Option<&OsStr>,
+    ) -> Option<PathBuf> {
+        if let Some(found) = self.found.borrow().get(name) {
+            if is_executable(found, pathext) {
+                return Some(found.clone());
+            }
+        }
+        let found = find_in_path(name, path, pathext)?;
+        // a hit in `.` stops being right as soon as the shell changes directory
+        if found.is_absolute() {
+            self.found
+                .borrow_mut()
+                .insert(name.to_string(), found.clone());
+        }
+        Some(found)
+    }
+
+    pub fn clear(&self) {
+        self.found.borrow_mut().clear();
+    }
+}
+
+/// The directories of `path` in the order lookups search them. An empty
+/// entry (`:/bin`, `/bin::/usr/bin`, `/bin:`) is the current directory.
+fn directories(path: Option<&OsStr>) -> impl Iterator<Item = PathBuf> + '_ {
+    path.into_iter().flat_map(env::split_paths).map(|dir| {
+        if dir.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            dir
+        }
+    })
+}
+
+/// Every executable called `name` in the directories of `path`, first
+/// the one a lookup runs. Entries that are files or don't exist just have
+/// nothing in them, so the search goes on to the next. On Windows
+/// `pathext` is the shell's `PATHEXT`, the extensions that make a file
+/// executable.
+pub fn executables_named<'a>(
+    name: &'a str,
+    path: Option<&'a OsStr>,
+    pathext: Option<&'a OsStr>,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    directories(path).filter_map(move |dir| executable_path(&dir.join(name), pathext))
+}
+
+/// The first executable called `name` in the directories of `path`.
+pub fn find_in_path(name: &str, path: Option<&OsStr>, pathext: Option<&OsStr>) -> Option<PathBuf> {
+    executables_named(name, path, pathext).next()
+}
+
+/// The first regular file called `name` in the directories of `path`,
+/// executable or not, for `source` to read.
+pub fn file_in_path(name: &str, path: Option<&OsStr>) -> Option<PathBuf> {
+    directories(path)
+        .map(|dir| dir.join(name))
+        .find(|file| file.is_file())
+}
+
+/// The names of the executables on `path` that start with `prefix`, for
+/// completing command names.
+#[cfg(unix)]
+pub fn commands_starting_with(prefix: &str, path: Option<&OsStr>) -> Vec<String> {
+    // the editor only runs on Unix, where extensions don't matter
+    let pathext = None;
+    let mut names = Vec::new();
+    for dir in directories(path) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if name.starts_with(prefix) && is_executable(&entry.path(), pathext) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Whether `p` is a file the shell can run.
+pub fn is_executable(p: &Path, pathext: Option<&OsStr>) -> bool {
+    executable_path(p, pathext).is_some()
+}
+
+/// The file running `p` runs: `p` itself if it's executable, or on
+/// Windows, where `grep` runs `grep.exe`, `p` with one of the extensions
+/// `pathext` lists added.
+pub fn executable_path(p: &Path, pathext: Option<&OsStr>) -> Option<PathBuf> {
+    #[cfg(unix)]
+    {
+        let _ = pathext;
+        is_executable_unix(p).then(|| p.to_path_buf())
+    }
+    #[cfg(windows)]
+    executable_path_windows(p, pathext)
+}
+#[cfg(unix)]
+fn is_executable_unix(p: &Path) -> bool {
+    match fs::metadata(p) {
+        Ok(md) => md.is_file() && (md.permissions().mode() & 0o111) != 0,
+        Err(_) => false,
+    }
+}
+/// The extensions of executables when `PATHEXT` doesn't say.
+#[cfg(any(windows, test))]
+const DEFAULT_EXTENSIONS: [&str; 4] = ["exe", "com", "bat", "cmd"];
+
+/// The extensions of executables that `pathext` lists, as in
+/// `.COM;.EXE;.PS1`, lowercased and without their dots. Unset or empty,
+/// it means [`DEFAULT_EXTENSIONS`].
+#[cfg(any(windows, test))]
+fn allowed_extensions(pathext: Option<&OsStr>) -> Vec<String> {
+    let listed: Vec<String> = pathext
+        .and_then(OsStr::to_str)
+        .unwrap_or_default()
+        .split(';')
+        .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect();
+    match listed.is_empty() {
+        true => DEFAULT_EXTENSIONS
+            .iter()
+            .map(|ext| ext.to_string())
+            .collect(),
+        false => listed,
+    }
+}
+
+#[cfg(windows)]
+fn is_regular_file(p: &Path) -> bool {
+    match fs::metadata(p) {
+        Ok(md) => md.is_file(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(windows)]
+fn lower_ext(p: &Path) -> Option<String> {
+    match p.extension() {
+        Some(os) => match os.to_str() {
+            Some(s) => Some(s.to_ascii_lowercase()), // extensions are ASCII
+            None => None,
+        },
+        None => None,
+    }
+}
+
+#[cfg(windows)]
+fn executable_path_windows(p: &Path, pathext: Option<&OsStr>) -> Option<PathBuf> {
+    let allowed = allowed_extensions(pathext);
+    //path already has an allowed extension
+    if lower_ext(p).is_some_and(|ext| allowed.contains(&ext)) {
+        return is_regular_file(p).then(|| p.to_path_buf());
+    }
+
+    //try each allowed extension after the whole name, so `python3.11`
+    //finds `python3.11.exe`
+    for ext in &allowed {
+        let mut name = p.as_os_str().to_owned();
+        name.push(".");
+        name.push(ext);
+        let path_buf = PathBuf::from(name);
+        if is_regular_file(&path_buf) {
+            return Some(path_buf);
+        }
+    }
+    None
+}
+/// Expands a leading `~` or `~/...` against `home`; `~user` is left as is.
+pub fn expand_tilde(p: &str, home: Option<&str>) -> PathBuf {
+    if let Some(rest) = p.strip_prefix("~") {
+        if let Some(home) = home {
+            if rest.is_empty() {
+                return PathBuf::from(home);
+            }
+            if let Some(rest) = rest.strip_prefix('/') {
+                return Path::new(home).join(rest);
+            }
+        }
+    }
+    PathBuf::from(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rust-cli-path-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A fresh directory holding an executable `tool` and a plain file.
+    #[cfg(unix)]
+    fn bin_dir(name: &str) -> PathBuf {
+        let dir = scratch_dir(name);
+        fs::write(dir.join("tool"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(dir.join("tool"), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(dir.join("plain"), "").unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bad_path_entries_do_not_stop_the_search() {
+        let dir = bin_dir("malformed");
+        // a file where a directory should be, then one that doesn't exist
+        let entries = [dir.join("plain"), dir.join("gone"), dir.clone()];
+        let path = env::join_paths(entries).unwrap();
+        assert_eq!(
+            find_in_path("tool", Some(&path), None),
+            Some(dir.join("tool"))
+        );
+        assert_eq!(find_in_path("plain", Some(&path), None), None);
+        assert_eq!(find_in_path("tool", None, None), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn lookups_and_completion_agree_on_what_runs() {
+        let first = bin_dir("agree-first");
+        let second = bin_dir("agree-second");
+        // a directory and a plain file named like an executable don't count
+        fs::create_dir(first.join("tooldir")).unwrap();
+        fs::write(second.join("toolkit"), "").unwrap();
+        let path = env::join_paths([&first, &second]).unwrap();
+        let all: Vec<PathBuf> = executables_named("tool", Some(&path), None).collect();
+        assert_eq!(all, [first.join("tool"), second.join("tool")]);
+        assert_eq!(
+            find_in_path("tool", Some(&path), None).as_ref(),
+            all.first()
+        );
+        assert_eq!(commands_starting_with("too", Some(&path)), ["tool", "tool"]);
+        assert_eq!(executables_named("plain", Some(&path), None).count(), 0);
+        assert!(commands_starting_with("pla", Some(&path)).is_empty());
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn bare_names_resolve_to_the_file_with_its_extension() {
+        let dir = scratch_dir("extensions");
+        for file in ["tool.bat", "py3.11.exe", "notes.txt"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let path = env::join_paths([&dir]).unwrap();
+        assert_eq!(
+            find_in_path("tool", Some(&path), None),
+            Some(dir.join("tool.bat"))
+        );
+        assert_eq!(
+            find_in_path("tool.bat", Some(&path), None),
+            Some(dir.join("tool.bat"))
+        );
+        assert_eq!(
+            find_in_path("py3.11", Some(&path), None),
+            Some(dir.join("py3.11.exe"))
+        );
+        assert_eq!(find_in_path("notes", Some(&path), None), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn command_names_match_whatever_their_case() {
+        let dir = scratch_dir("case");
+        fs::write(dir.join("Grep.exe"), "").unwrap();
+        let path = env::join_paths([&dir]).unwrap();
+        assert!(find_in_path("GREP", Some(&path), None).is_some());
+    }
+
+    #[test]
+    fn pathext_lists_the_extensions_of_executables() {
+        let listed = allowed_extensions(Some(OsStr::new(".COM;.Exe; .ps1;;")));
+        assert_eq!(listed, ["com", "exe", "ps1"]);
+        assert_eq!(allowed_extensions(None), DEFAULT_EXTENSIONS);
+        assert_eq!(
+            allowed_extensions(Some(OsStr::new(";"))),
+            DEFAULT_EXTENSIONS
+        );
+    }
+}
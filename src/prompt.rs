@@ -0,0 +1,182 @@
+//! The prompts shown before each command (`PS1`) and each continuation
+//! line (`PS2`), with bash's backslash escapes.
+
+use crate::state::ShellState;
+use std::env;
+
+/// Where `\[` put the start of text that takes no room on the terminal,
+/// such as a colour escape; as in readline.
+const HIDDEN_START: char = '\x01';
+/// Where `\]` ended it.
+const HIDDEN_END: char = '\x02';
+
+/// The prompt for a new command, or for the next line of an unfinished
+/// one if `continuation` is set. Unset `PS1`/`PS2` give `$ ` and `> `.
+/// Text between `\[` and `\]` is still marked; see [`displayed`] and
+/// [`width`].
+pub fn render(state: &ShellState, continuation: bool) -> String {
+    let template = match continuation {
+        true => state.get_var("PS2").unwrap_or("> "),
+        false => state.get_var("PS1").unwrap_or("$ "),
+    };
+    expand(state, template)
+}
+
+/// Replaces `\w` (the working directory, `~` for `HOME`), `\W` (just its
+/// last component), `\u` (the user),
+/// `\h` (the host name up to the first `.`), `\$` (`#` for root, else `$`),
+/// `\n`, `\a`, `\e` and `\\`, and marks where `\[` and `\]` are.
+/// Anything else after a backslash is kept.
+fn expand(state: &ShellState, template: &str) -> String {
+    let mut out = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('w') => out.push_str(&working_dir(state)),
+            Some('W') => out.push_str(&base_dir(state)),
+            Some('u') => {
+                let user = state.get_var("USER").or_else(|| state.get_var("LOGNAME"));
+                out.push_str(user.unwrap_or_default());
+            }
+            Some('h') => out.push_str(host_name().split('.').next().unwrap_or_default()),
+            Some('$') => out.push(if is_root() { '#' } else { '$' }),
+            Some('n') => out.push('\n'),
+            Some('a') => out.push('\x07'),
+            Some('e') => out.push('\x1b'),
+            Some('\\') => out.push('\\'),
+            Some('[') => out.push(HIDDEN_START),
+            Some(']') => out.push(HIDDEN_END),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// A rendered prompt as it is written out, without the `\[ \]` marks.
+pub fn displayed(prompt: &str) -> String {
+    prompt
+        .chars()
+        .filter(|&c| c != HIDDEN_START && c != HIDDEN_END)
+        .collect()
+}
+
+/// The columns the last line of a rendered prompt takes up: its
+/// characters, less those between `\[` and `\]`.
+pub fn width(prompt: &str) -> usize {
+    let last = prompt.rsplit('\n').next().unwrap_or_default();
+    let mut hidden = false;
+    let mut width = 0;
+    for c in last.chars() {
+        match c {
+            HIDDEN_START => hidden = true,
+            HIDDEN_END => hidden = false,
+            _ if !hidden => width += 1,
+            _ => {}
+        }
+    }
+    width
+}
+
+/// The working directory for `\w`. With `PROMPT_DIRTRIM` set to a number
+/// only that many trailing components are kept, after a `...`; otherwise
+/// one longer than half the terminal is cut down to what fits, so typing
+/// still has room on narrow terminals.
+fn working_dir(state: &ShellState) -> String {
+    let dir = current_dir(state);
+    let (root, path) = match dir.strip_prefix('~') {
+        Some(rest) => ("~", rest),
+        None => ("", &dir[..]),
+    };
+    let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    let trim = state
+        .get_var("PROMPT_DIRTRIM")
+        .and_then(|n| n.parse::<usize>().ok());
+    let keep = match trim {
+        Some(0) => return dir,
+        Some(n) => n,
+        None if dir.chars().count() <= state.columns() / 2 => return dir,
+        None => {
+            let max = state.columns() / 2;
+            let mut len = root.len() + 4;
+            let mut keep = 0;
+            for part in parts.iter().rev() {
+                len += part.chars().count() + 1;
+                if keep > 0 && len > max {
+                    break;
+                }
+                keep += 1;
+            }
+            keep
+        }
+    };
+    if keep >= parts.len() {
+        return dir;
+    }
+    format!("{}/.../{}", root, parts[parts.len() - keep..].join("/"))
+}
+
+/// The working directory for `\W`: its last component, or `~` or `/`.
+fn base_dir(state: &ShellState) -> String {
+    let dir = current_dir(state);
+    match dir.rsplit('/').next() {
+        Some("") | None => dir,
+        Some(base) => base.to_string(),
+    }
+}
+
+/// The working directory, with `~` for `HOME`.
+fn current_dir(state: &ShellState) -> String {
+    let Ok(dir) = env::current_dir() else {
+        return String::new();
+    };
+    let dir = dir.to_string_lossy().into_owned();
+    match state
+        .get_var("HOME")
+        .filter(|home| !home.is_empty() && *home != "/")
+    {
+        Some(home) if dir == home => "~".to_string(),
+        Some(home) if dir.starts_with(&format!("{}/", home)) => format!("~{}", &dir[home.len()..]),
+        _ => dir,
+    }
+}
+
+fn host_name() -> String {
+    #[cfg(unix)]
+    return crate::sys::host_name();
+    #[cfg(not(unix))]
+    return env::var("COMPUTERNAME").unwrap_or_default();
+}
+
+fn is_root() -> bool {
+    #[cfg(unix)]
+    return crate::sys::is_root();
+    #[cfg(not(unix))]
+    return false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_text_takes_no_columns() {
+        let mut state = ShellState::default();
+        state.set_var("USER", "me".to_string());
+        let prompt = expand(&state, r"\[\e[1;32m\]\u\[\e[0m\]> ");
+        assert_eq!(displayed(&prompt), "\x1b[1;32mme\x1b[0m> ");
+        assert_eq!(width(&prompt), 4);
+        assert_eq!(
+            width(&expand(&state, r"first line\n\[\e[7m\]>\[\e[0m\] ")),
+            2
+        );
+        assert_eq!(width(&expand(&state, r"\e[1m> ")), 6);
+    }
+}
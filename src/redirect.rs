@@ -0,0 +1,309 @@
+use crate::parser::{RedirOp, Redirect};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::process::{self, Stdio};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What a file descriptor number refers to while a command runs.
+#[derive(Debug, Clone)]
+pub enum FdTarget {
+    /// One of the shell process's own standard streams (0, 1 or 2).
+    Inherit(i32),
+    File(Rc<File>),
+    /// Closed with `N>&-`, even if the shell process has N open.
+    Closed,
+}
+
+/// The file descriptors visible to a command. Builtins write through it
+/// and external commands get their stdio from it, so redirections apply
+/// to both the same way.
+#[derive(Debug, Clone)]
+pub struct FdTable {
+    fds: BTreeMap<i32, FdTarget>,
+}
+
+impl FdTable {
+    pub fn standard() -> Self {
+        let fds = (0..3).map(|fd| (fd, FdTarget::Inherit(fd))).collect();
+        FdTable { fds }
+    }
+
+    pub fn set(&mut self, fd: i32, target: FdTarget) {
+        self.fds.insert(fd, target);
+    }
+
+    /// What `fd` refers to, or `None` if it isn't open. Fds above 2 the
+    /// table doesn't mention are the shell's own, as opened by `exec 3>log`.
+    fn get(&self, fd: i32) -> Option<FdTarget> {
+        match self.fds.get(&fd) {
+            Some(FdTarget::Closed) => None,
+            Some(target) => Some(target.clone()),
+            #[cfg(unix)]
+            None if fd > 2 => shell_fd(fd).map(|file| FdTarget::File(Rc::new(file))),
+            None => None,
+        }
+    }
+
+    pub fn writer(&self, fd: i32) -> FdWriter {
+        FdWriter(self.get(fd))
+    }
+
+    pub fn reader(&self, fd: i32) -> FdReader {
+        FdReader(self.get(fd))
+    }
+
+    pub fn stdout(&self) -> FdWriter {
+        self.writer(1)
+    }
+
+    pub fn stderr(&self) -> FdWriter {
+        self.writer(2)
+    }
+
+    /// Stdout through a buffer, for builtins that list many lines; what's
+    /// buffered is written out when it's dropped at the end of the builtin.
+    pub fn buffered_stdout(&self) -> BufWriter<FdWriter> {
+        BufWriter::new(self.stdout())
+    }
+
+    /// Converts `fd` into something a child process can use.
+    pub fn stdio(&self, fd: i32) -> io::Result<Stdio> {
+        Ok(match self.get(fd) {
+            None | Some(FdTarget::Closed) => Stdio::null(),
+            Some(FdTarget::Inherit(n)) if n == fd => Stdio::inherit(),
+            Some(FdTarget::Inherit(0)) => Stdio::inherit(),
+            Some(FdTarget::Inherit(1)) => io::stdout().into(),
+            Some(FdTarget::Inherit(_)) => io::stderr().into(),
+            Some(FdTarget::File(file)) => file.try_clone()?.into(),
+        })
+    }
+
+    /// Returns a copy of the table with `redirects` applied in order.
+    pub fn with_redirects(&self, redirects: &[(Redirect, String)]) -> Result<FdTable, String> {
+        let mut table = self.clone();
+        for (redirect, target) in redirects {
+            let mut options = OpenOptions::new();
+            match redirect.op {
+                RedirOp::In => options.read(true),
+                RedirOp::Out => options.write(true).create(true).truncate(true),
+                RedirOp::Append => options.append(true).create(true),
+                RedirOp::OutBoth | RedirOp::AppendBoth => {
+                    match redirect.op {
+                        RedirOp::OutBoth => options.write(true).create(true).truncate(true),
+                        _ => options.append(true).create(true),
+                    };
+                    let file = Rc::new(open(&options, target)?);
+                    table.set(1, FdTarget::File(file.clone()));
+                    table.set(2, FdTarget::File(file));
+                    continue;
+                }
+                RedirOp::DupIn | RedirOp::DupOut => {
+                    table.duplicate(redirect, target)?;
+                    continue;
+                }
+                RedirOp::HereDoc => {
+                    let file = here_doc(target).map_err(|e| {
+                        format!(
+                            "cannot create temp file for here-document: {}",
+                            describe(&e)
+                        )
+                    })?;
+                    table.set(redirect.fd, FdTarget::File(Rc::new(file)));
+                    continue;
+                }
+            };
+            let file = open(&options, target)?;
+            table.set(redirect.fd, FdTarget::File(Rc::new(file)));
+        }
+        Ok(table)
+    }
+
+    /// Applies `N>&M`/`N<&M`: fd N becomes a copy of M, or is closed for
+    /// `-`. `>&file` with no fd sends both stdout and stderr to the file.
+    fn duplicate(&mut self, redirect: &Redirect, target: &str) -> Result<(), String> {
+        if target == "-" {
+            self.set(redirect.fd, FdTarget::Closed);
+            return Ok(());
+        }
+        match target.parse::<i32>() {
+            Ok(source) => match self.get(source) {
+                Some(fd) => {
+                    self.set(redirect.fd, fd);
+                    Ok(())
+                }
+                None => Err(format!("{}: Bad file descriptor", source)),
+            },
+            Err(_) if redirect.op == RedirOp::DupOut && redirect.fd == 1 => {
+                let mut options = OpenOptions::new();
+                let file = Rc::new(open(
+                    options.write(true).create(true).truncate(true),
+                    target,
+                )?);
+                self.set(1, FdTarget::File(file.clone()));
+                self.set(2, FdTarget::File(file));
+                Ok(())
+            }
+            Err(_) => Err(format!("{}: ambiguous redirect", target)),
+        }
+    }
+
+    /// Makes the shell process's own `fds` what this table says, so they stay
+    /// redirected for every later command, as `exec >out` or `exec 3>&-` do.
+    #[cfg(unix)]
+    pub fn make_permanent(&self, fds: &[i32]) -> io::Result<()> {
+        use std::os::fd::AsRawFd;
+
+        io::stdout().flush()?;
+        io::stderr().flush()?;
+        for &fd in fds {
+            match self.get(fd) {
+                Some(FdTarget::Inherit(n)) => crate::sys::dup_fd(n, fd)?,
+                Some(FdTarget::File(file)) => crate::sys::dup_fd(file.as_raw_fd(), fd)?,
+                None | Some(FdTarget::Closed) => crate::sys::close_fd(fd),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Opens a redirection's file. On unix it's moved above the fds scripts
+/// name, so `exec 3>log` can't end up handing out the file's own fd.
+fn open(options: &OpenOptions, target: &str) -> Result<File, String> {
+    let file = options
+        .open(target)
+        .map_err(|e| format!("{}: {}", target, describe(&e)))?;
+    #[cfg(unix)]
+    let file = crate::sys::move_fd_above(file, 10);
+    Ok(file)
+}
+
+/// A file holding `text`, to be read from the start, for a here-document.
+/// It has no name once it's open, so nothing is left behind.
+fn here_doc(text: &str) -> io::Result<File> {
+    static CREATED: AtomicUsize = AtomicUsize::new(0);
+    let n = CREATED.fetch_add(1, Ordering::Relaxed);
+    let path = env::temp_dir().join(format!("sh-heredoc-{}-{}", process::id(), n));
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create_new(true);
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        // FILE_FLAG_DELETE_ON_CLOSE, as an open file can't be removed there
+        options.custom_flags(0x0400_0000);
+    }
+    let mut file = options.open(&path)?;
+    #[cfg(unix)]
+    {
+        std::fs::remove_file(&path)?;
+        file = crate::sys::move_fd_above(file, 10);
+    }
+    file.write_all(text.as_bytes())?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
+
+/// A copy of the shell process's `fd`, if it has one open.
+#[cfg(unix)]
+fn shell_fd(fd: i32) -> Option<File> {
+    use std::os::fd::BorrowedFd;
+
+    if !crate::sys::fd_is_open(fd) {
+        return None;
+    }
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+    fd.try_clone_to_owned().ok().map(File::from)
+}
+
+/// Creates a pipe, returning its read and write ends. Both are closed on
+/// exec, so only the processes they're handed to keep them open.
+#[cfg(unix)]
+pub fn pipe() -> io::Result<(File, File)> {
+    use std::os::fd::OwnedFd;
+    let (reader, writer) = io::pipe()?;
+    Ok((OwnedFd::from(reader).into(), OwnedFd::from(writer).into()))
+}
+
+/// The message part of an I/O error, without the `(os error N)` suffix.
+pub fn describe(e: &io::Error) -> String {
+    match e.kind() {
+        io::ErrorKind::NotFound => "No such file or directory".to_string(),
+        io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
+        _ => {
+            let msg = e.to_string();
+            match msg.find(" (os error") {
+                Some(idx) => msg[..idx].to_string(),
+                None => msg,
+            }
+        }
+    }
+}
+
+pub struct FdWriter(Option<FdTarget>);
+
+impl Write for FdWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &self.0 {
+            Some(FdTarget::Inherit(0) | FdTarget::Closed) | None => Err(bad_fd()),
+            Some(FdTarget::Inherit(1)) => io::stdout().write(buf),
+            Some(FdTarget::Inherit(_)) => io::stderr().write(buf),
+            Some(FdTarget::File(file)) => (&**file).write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &self.0 {
+            Some(FdTarget::Inherit(1)) => io::stdout().flush(),
+            Some(FdTarget::Inherit(_)) => io::stderr().flush(),
+            Some(FdTarget::File(file)) => (&**file).flush(),
+            Some(FdTarget::Closed) | None => Ok(()),
+        }
+    }
+}
+
+pub struct FdReader(Option<FdTarget>);
+
+impl FdReader {
+    /// Reads up to and including the next newline, one byte at a time so
+    /// nothing past the line is consumed from a shared file.
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        if let Some(FdTarget::Inherit(0)) = self.0 {
+            let mut line = String::new();
+            return match io::stdin().read_line(&mut line)? {
+                0 => Ok(None),
+                _ => Ok(Some(line)),
+            };
+        }
+        let mut bytes = Vec::new();
+        let mut byte = [0u8];
+        loop {
+            match self.read(&mut byte)? {
+                0 if bytes.is_empty() => return Ok(None),
+                0 => break,
+                _ => {
+                    bytes.push(byte[0]);
+                    if byte[0] == b'\n' {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+}
+
+impl Read for FdReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &self.0 {
+            Some(FdTarget::Inherit(0)) => io::stdin().read(buf),
+            Some(FdTarget::File(file)) => (&**file).read(buf),
+            _ => Err(bad_fd()),
+        }
+    }
+}
+
+fn bad_fd() -> io::Error {
+    io::Error::other("Bad file descriptor")
+}
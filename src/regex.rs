@@ -0,0 +1,452 @@
+//! POSIX extended regular expressions, for `[[ string =~ regex ]]`: `.`,
+//! brackets, `^`, `$`, groups, `|` and the `*`, `+`, `?` and `{n,m}`
+//! repeats. Matching finds the leftmost match, and the longest one there,
+//! as `regexec` does.
+
+/// A compiled regex: a little program for a backtracking matcher.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Inst>,
+    groups: usize,
+}
+
+/// The most a `{n,m}` bound may be, so a regex can't grow without limit.
+const MAX_REPEAT: u32 = 255;
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    /// Records the position in capture slot `n`.
+    Save(usize),
+    /// Tries the first target, then the second.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+    named: Vec<fn(char) -> bool>,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let found = self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi)
+            || self.named.iter().any(|class| class(c));
+        found != self.negated
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Group(usize, Box<Node>),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat(Box<Node>, u32, Option<u32>),
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            groups: 0,
+        };
+        let node = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err("unmatched ( or \\(".to_string());
+        }
+        let mut program = Vec::new();
+        compile(&node, &mut program);
+        program.push(Inst::Match);
+        Ok(Regex {
+            program,
+            groups: parser.groups,
+        })
+    }
+
+    /// The first match in `text`, with what each group matched; the whole
+    /// match comes first. A group that took no part is `None`.
+    pub fn captures(&self, text: &str) -> Option<Vec<Option<String>>> {
+        let chars: Vec<char> = text.chars().collect();
+        for start in 0..=chars.len() {
+            if let Some(slots) = self.longest_at(&chars, start) {
+                let span = |i: usize| match (slots[2 * i], slots[2 * i + 1]) {
+                    (Some(from), Some(to)) => Some(chars[from..to].iter().collect()),
+                    _ => None,
+                };
+                return Some((0..=self.groups).map(span).collect());
+            }
+        }
+        None
+    }
+
+    /// The capture slots of the longest match starting at `start`.
+    fn longest_at(&self, text: &[char], start: usize) -> Option<Vec<Option<usize>>> {
+        let slots = 2 * (self.groups + 1);
+        let mut initial = vec![None; slots];
+        initial[0] = Some(start);
+        // a state reached a second time can't get anywhere new
+        let mut seen = vec![false; self.program.len() * (text.len() + 1)];
+        let mut stack = vec![(0, start, initial)];
+        let mut best: Option<Vec<Option<usize>>> = None;
+        while let Some((mut pc, mut pos, mut saved)) = stack.pop() {
+            loop {
+                let state = pc * (text.len() + 1) + pos;
+                if seen[state] {
+                    break;
+                }
+                seen[state] = true;
+                match &self.program[pc] {
+                    Inst::Char(c) if text.get(pos) == Some(c) => pos += 1,
+                    Inst::Any if pos < text.len() => pos += 1,
+                    Inst::Class(class) if text.get(pos).is_some_and(|&c| class.matches(c)) => {
+                        pos += 1
+                    }
+                    Inst::Start if pos == 0 => {}
+                    Inst::End if pos == text.len() => {}
+                    Inst::Save(slot) => saved[*slot] = Some(pos),
+                    Inst::Split(first, second) => {
+                        stack.push((*second, pos, saved.clone()));
+                        pc = *first;
+                        continue;
+                    }
+                    Inst::Jump(target) => {
+                        pc = *target;
+                        continue;
+                    }
+                    Inst::Match => {
+                        if best.as_ref().is_none_or(|b| b[1] < Some(pos)) {
+                            saved[1] = Some(pos);
+                            best = Some(saved);
+                        }
+                        break;
+                    }
+                    _ => break,
+                }
+                pc += 1;
+            }
+        }
+        best
+    }
+}
+
+fn compile(node: &Node, program: &mut Vec<Inst>) {
+    match node {
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Group(n, inner) => {
+            program.push(Inst::Save(2 * n));
+            compile(inner, program);
+            program.push(Inst::Save(2 * n + 1));
+        }
+        Node::Concat(nodes) => nodes.iter().for_each(|node| compile(node, program)),
+        Node::Alt(branches) => {
+            let mut jumps = Vec::new();
+            for (i, branch) in branches.iter().enumerate() {
+                if i + 1 == branches.len() {
+                    compile(branch, program);
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(branch, program);
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                program[split] = Inst::Split(split + 1, program.len());
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat(inner, min, max) => {
+            for _ in 0..*min {
+                compile(inner, program);
+            }
+            match max {
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    compile(inner, program);
+                    program.push(Inst::Jump(split));
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        compile(inner, program);
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.concatenation()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.concatenation()?);
+        }
+        Ok(match branches.len() {
+            1 => branches.remove(0),
+            _ => Node::Alt(branches),
+        })
+    }
+
+    fn concatenation(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.repeats(atom)?);
+        }
+        Ok(Node::Concat(nodes))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.chars[self.pos];
+        self.pos += 1;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '[' => Node::Class(self.bracket()?),
+            '(' => {
+                self.groups += 1;
+                let n = self.groups;
+                let inner = self.alternation()?;
+                if self.peek() != Some(')') {
+                    return Err("unmatched ( or \\(".to_string());
+                }
+                self.pos += 1;
+                Node::Group(n, Box::new(inner))
+            }
+            '\\' => match self.peek() {
+                Some(escaped) => {
+                    self.pos += 1;
+                    Node::Char(escaped)
+                }
+                None => return Err("trailing backslash (\\)".to_string()),
+            },
+            '*' | '+' | '?' => return Err("repetition-operator operand invalid".to_string()),
+            c => Node::Char(c),
+        })
+    }
+
+    fn repeats(&mut self, mut node: Node) -> Result<Node, String> {
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.bounds()? {
+                    Some(bounds) => {
+                        node = Node::Repeat(Box::new(node), bounds.0, bounds.1);
+                        continue;
+                    }
+                    None => return Ok(node),
+                },
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            node = Node::Repeat(Box::new(node), min, max);
+        }
+    }
+
+    /// Reads `{n}`, `{n,}` or `{n,m}`, leaving a `{` that starts none of
+    /// them to be taken literally.
+    fn bounds(&mut self) -> Result<Option<(u32, Option<u32>)>, String> {
+        let rest: String = self.chars[self.pos + 1..].iter().collect();
+        let Some(close) = rest.find('}') else {
+            return Ok(None);
+        };
+        let body = &rest[..close];
+        let number = |text: &str| text.parse::<u32>().ok().filter(|&n| n <= MAX_REPEAT);
+        let bounds = match body.split_once(',') {
+            None => number(body).map(|n| (n, Some(n))),
+            Some((min, "")) => number(min).map(|n| (n, None)),
+            Some((min, max)) => number(min).zip(number(max)).map(|(lo, hi)| (lo, Some(hi))),
+        };
+        let Some(bounds) = bounds else {
+            return Ok(None);
+        };
+        if bounds.1.is_some_and(|max| max < bounds.0) {
+            return Err("invalid repetition count(s)".to_string());
+        }
+        self.pos += 1 + body.chars().count() + 1;
+        Ok(Some(bounds))
+    }
+
+    /// Reads a bracket expression after its `[`: ranges, `[:class:]`
+    /// names, a leading `^` to negate and a leading `]` taken literally.
+    fn bracket(&mut self) -> Result<Class, String> {
+        let unterminated = || "brackets ([ ]) not balanced".to_string();
+        let mut class = Class {
+            negated: false,
+            ranges: Vec::new(),
+            named: Vec::new(),
+        };
+        if self.peek() == Some('^') {
+            class.negated = true;
+            self.pos += 1;
+        }
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or_else(unterminated)?;
+            self.pos += 1;
+            if c == ']' && !first {
+                return Ok(class);
+            }
+            first = false;
+            if c == '[' && self.peek() == Some(':') {
+                let rest: String = self.chars[self.pos + 1..].iter().collect();
+                let end = rest.find(":]").ok_or_else(unterminated)?;
+                let named = named_class(&rest[..end])
+                    .ok_or_else(|| "invalid character class".to_string())?;
+                class.named.push(named);
+                self.pos += 1 + rest[..end].chars().count() + 2;
+                continue;
+            }
+            let is_range = self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']');
+            if is_range && self.pos + 1 < self.chars.len() {
+                let hi = self.chars[self.pos + 1];
+                if hi < c {
+                    return Err("invalid character range".to_string());
+                }
+                class.ranges.push((c, hi));
+                self.pos += 2;
+            } else {
+                class.ranges.push((c, c));
+            }
+        }
+    }
+}
+
+fn named_class(name: &str) -> Option<fn(char) -> bool> {
+    Some(match name {
+        "alpha" => |c: char| c.is_alphabetic(),
+        "digit" => |c: char| c.is_ascii_digit(),
+        "alnum" => |c: char| c.is_alphanumeric(),
+        "upper" => |c: char| c.is_uppercase(),
+        "lower" => |c: char| c.is_lowercase(),
+        "space" => |c: char| c.is_whitespace(),
+        "blank" => |c: char| c == ' ' || c == '\t',
+        "punct" => |c: char| c.is_ascii_punctuation(),
+        "xdigit" => |c: char| c.is_ascii_hexdigit(),
+        "cntrl" => |c: char| c.is_control(),
+        "print" => |c: char| !c.is_control(),
+        "graph" => |c: char| !c.is_control() && !c.is_whitespace(),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<Vec<Option<String>>> {
+        Regex::new(pattern).unwrap().captures(text)
+    }
+
+    fn whole(pattern: &str, text: &str) -> Option<String> {
+        find(pattern, text).map(|groups| groups[0].clone().unwrap())
+    }
+
+    #[test]
+    fn finds_the_leftmost_longest_match() {
+        assert_eq!(whole("b+", "abbbc"), Some("bbb".into()));
+        assert_eq!(whole("a|ab", "xab"), Some("ab".into()));
+        assert_eq!(whole("x*", "abc"), Some("".into()));
+        assert_eq!(whole("c", "ab"), None);
+    }
+
+    #[test]
+    fn anchors_and_dot() {
+        assert_eq!(whole("^a.c$", "abc"), Some("abc".into()));
+        assert_eq!(whole("^b", "abc"), None);
+        assert_eq!(whole("c$", "abc"), Some("c".into()));
+        assert_eq!(whole(r"a\.c", "abc"), None);
+    }
+
+    #[test]
+    fn repeats() {
+        assert_eq!(whole("ab?c", "ac"), Some("ac".into()));
+        assert_eq!(whole("a{2}", "aaaa"), Some("aa".into()));
+        assert_eq!(whole("a{2,}", "aaaa"), Some("aaaa".into()));
+        assert_eq!(whole("a{1,3}", "aaaa"), Some("aaa".into()));
+        assert_eq!(whole("(ab)+", "ababx"), Some("abab".into()));
+        // a `{` that starts no bound is literal
+        assert_eq!(whole("a{x", "a{x"), Some("a{x".into()));
+    }
+
+    #[test]
+    fn brackets() {
+        assert_eq!(whole("[a-c]+", "xcabz"), Some("cab".into()));
+        assert_eq!(whole("[^a-c]+", "abxyc"), Some("xy".into()));
+        assert_eq!(whole("[]x]+", "a]x]"), Some("]x]".into()));
+        assert_eq!(whole("[[:digit:]]+", "ab123"), Some("123".into()));
+        assert_eq!(whole("[a-]+", "b-a-"), Some("-a-".into()));
+    }
+
+    #[test]
+    fn groups_capture_what_they_matched() {
+        let groups = find("^(a)(b)(x)?", "abc").unwrap();
+        assert_eq!(
+            groups,
+            [Some("ab".into()), Some("a".into()), Some("b".into()), None]
+        );
+        let groups = find("(a|b)*c", "abac").unwrap();
+        assert_eq!(groups[1], Some("a".into()));
+    }
+
+    #[test]
+    fn rejects_malformed_patterns() {
+        for pattern in [
+            "(a",
+            "a)",
+            "[a",
+            "*a",
+            "a{3,1}",
+            "[[:nope:]]",
+            "[z-a]",
+            "a\\",
+        ] {
+            assert!(Regex::new(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,541 @@
+use crate::arith;
+use crate::history::History;
+use crate::jobs::{JobTable, Pid};
+use crate::parser::Command;
+use crate::path::PathCache;
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Default)]
+pub struct Variable {
+    pub value: String,
+    /// The elements of an array variable, whose `value` goes unused.
+    pub array: Option<Array>,
+    pub exported: bool,
+    /// `readonly`/`declare -r`: assignments fail.
+    pub readonly: bool,
+    /// `declare -i`: assigned values are evaluated as arithmetic.
+    pub integer: bool,
+}
+
+impl Variable {
+    /// What `$name` expands to: the value, or an array's element 0.
+    pub fn scalar(&self) -> &str {
+        match &self.array {
+            Some(Array::Indexed(elements)) => elements.get(&0).map_or("", String::as_str),
+            Some(Array::Associative(elements)) => elements.get("0").map_or("", String::as_str),
+            None => &self.value,
+        }
+    }
+}
+
+/// The elements of an array.
+#[derive(Debug, Clone)]
+pub enum Array {
+    /// By index, which may have gaps.
+    Indexed(BTreeMap<usize, String>),
+    /// `declare -A`: by string key. Like bash, it keeps no order, so its
+    /// keys and values list in whatever order the hash map has them.
+    Associative(HashMap<String, String>),
+}
+
+impl Array {
+    /// The element `subscript`: an expression for the index, or the key of
+    /// an associative array, already expanded.
+    pub fn get(&self, state: &ShellState, subscript: &str) -> Result<Option<&str>, String> {
+        match self {
+            Array::Indexed(elements) => {
+                let mut index = arith::eval(state, subscript)?;
+                if index < 0 {
+                    // counting back from just past the last element
+                    let len = elements.keys().next_back().map_or(0, |&last| last + 1);
+                    index += len as i64;
+                    if index < 0 {
+                        return Err(format!("{}: bad array subscript", subscript));
+                    }
+                }
+                Ok(elements.get(&(index as usize)).map(String::as_str))
+            }
+            Array::Associative(elements) => Ok(elements.get(subscript).map(String::as_str)),
+        }
+    }
+
+    /// Every element, in order.
+    pub fn values(&self) -> Vec<&str> {
+        match self {
+            Array::Indexed(elements) => elements.values().map(String::as_str).collect(),
+            Array::Associative(elements) => elements.values().map(String::as_str).collect(),
+        }
+    }
+
+    /// The indices or keys of the elements, in the order of [`Array::values`].
+    pub fn keys(&self) -> Vec<String> {
+        match self {
+            Array::Indexed(elements) => elements.keys().map(usize::to_string).collect(),
+            Array::Associative(elements) => elements.keys().cloned().collect(),
+        }
+    }
+}
+
+/// Pending control flow that unwinds the executor, e.g. `exit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Exit(i32),
+    /// `break N`: leave the N innermost loops.
+    Break(u32),
+    /// `continue N`: resume the Nth innermost loop.
+    Continue(u32),
+    /// `return N`: leave the function being run with status N.
+    Return(i32),
+    /// Ctrl-C in an interactive shell: give up on the rest of the line.
+    Interrupt,
+}
+
+/// Options toggled with `set` and `shopt`.
+#[derive(Debug, Clone)]
+pub struct ShellOptions {
+    /// `set -e`: exit when a command fails outside a condition.
+    pub errexit: bool,
+    /// `set -H`: `!` history expansion, on by default when interactive.
+    pub histexpand: bool,
+    /// `set -o posix` or `--posix`, also on when run as `sh`: bash-only
+    /// features like brace expansion, `!` history expansion and `echo`'s
+    /// options are off, and a failed assignment stops a script.
+    pub posix: bool,
+    /// `shopt interactive_comments`: `#` starts a comment at the prompt.
+    /// Scripts always have comments.
+    pub interactive_comments: bool,
+    /// `shopt expand_aliases`: aliases are expanded, by default only when
+    /// interactive or in POSIX mode.
+    pub expand_aliases: bool,
+    /// `set -o vi`: the line editor takes vi's keys; `set -o emacs`, the
+    /// default, puts back emacs's.
+    pub vi: bool,
+}
+
+impl Default for ShellOptions {
+    fn default() -> Self {
+        ShellOptions {
+            errexit: false,
+            histexpand: false,
+            posix: false,
+            interactive_comments: true,
+            expand_aliases: false,
+            vi: false,
+        }
+    }
+}
+
+/// Everything a command can change. Subshells run against a clone, so
+/// nothing they do leaks back into the parent.
+#[derive(Debug, Clone, Default)]
+pub struct ShellState {
+    /// What the shell calls itself in error messages, from `argv[0]`.
+    pub name: String,
+    /// `$0`: how the shell was run, the script it is running, or the name
+    /// given after `-c`.
+    pub arg0: String,
+    /// `$1` onwards.
+    pub positional: Vec<String>,
+    vars: HashMap<String, Variable>,
+    pub last_status: i32,
+    pub flow: Option<Flow>,
+    /// How many loops enclose the command being run.
+    pub loop_depth: u32,
+    pub options: ShellOptions,
+    /// Nonzero while running a command whose failure `set -e` ignores,
+    /// like an `if` condition.
+    pub errexit_exempt: u32,
+    /// Reading commands from a terminal user rather than a script.
+    pub interactive: bool,
+    /// Interactive with stdin a terminal, decided once the shell starts
+    /// reading: only then are jobs handed the terminal and SIGTTOU
+    /// ignored. Piped input and scripts still run background jobs.
+    pub job_control: bool,
+    /// `--norc`: an interactive shell skips the `ENV` file at startup.
+    pub norc: bool,
+    /// `--parse-only`: commands are printed as the parser sees them, for
+    /// debugging it, rather than run.
+    pub parse_only: bool,
+    /// The line the commands being run started on, less one, so that
+    /// their own line numbers add up to the script's.
+    pub line_offset: usize,
+    /// `$LINENO`: the line of the simple command being run.
+    pub lineno: usize,
+    /// When `SECONDS` was last set, and to what, or else when the shell
+    /// started.
+    pub seconds_set: Option<(Instant, u64)>,
+    /// The state of the generator behind `$RANDOM`.
+    pub random_seed: Cell<u64>,
+    /// The last command was an `exit` that warned about jobs left, so
+    /// another one goes ahead.
+    pub exit_warned: bool,
+    pub jobs: JobTable,
+    /// The pid of the last background job, for `$!`.
+    pub last_background: Option<Pid>,
+    pub history: History,
+    /// The `OPTIND` that `getopts` last left and how far into that
+    /// argument it got, for groups of options like `-ab`.
+    pub getopts_position: (usize, usize),
+    /// The commands `trap` set, by signal number, 0 being `EXIT`. An empty
+    /// one means the signal is ignored.
+    pub traps: BTreeMap<i32, String>,
+    /// The fds above 2 that `exec` left open on the shell itself, closed
+    /// on the way out.
+    pub exec_fds: BTreeSet<i32>,
+    pub aliases: BTreeMap<String, String>,
+    /// The functions defined with `name() { ...; }`, by name.
+    pub functions: HashMap<String, Rc<Command>>,
+    /// How many function calls are running, so `return` knows it has one
+    /// to leave.
+    pub function_depth: u32,
+    /// The status of the last command substitution while a command's words
+    /// are expanded, which becomes the status of one without a name.
+    pub substitution_status: Option<i32>,
+    /// The terminal size last put in `COLUMNS` and `LINES`.
+    window_size: Option<(usize, usize)>,
+    commands: PathCache,
+}
+
+impl ShellState {
+    pub fn from_env() -> Self {
+        let mut state = ShellState::default();
+        for (name, value) in env::vars_os() {
+            if let (Ok(name), Ok(value)) = (name.into_string(), value.into_string()) {
+                let var = Variable {
+                    value,
+                    exported: true,
+                    ..Variable::default()
+                };
+                state.vars.insert(name, var);
+            }
+        }
+        state.seconds_set = Some((Instant::now(), 0));
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        state
+            .random_seed
+            .set(now.as_nanos() as u64 ^ u64::from(std::process::id()));
+        state
+    }
+
+    /// `$RANDOM`: the next number from 0 to 32767 of a linear congruential
+    /// generator, so the same seed always gives the same ones.
+    pub fn random(&self) -> u16 {
+        let seed = self
+            .random_seed
+            .get()
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1);
+        self.random_seed.set(seed);
+        (seed >> 49) as u16
+    }
+
+    /// `$SECONDS`: whole seconds since the shell started, counting on from
+    /// any value assigned since.
+    pub fn seconds(&self) -> u64 {
+        self.seconds_set
+            .map_or(0, |(at, value)| value + at.elapsed().as_secs())
+    }
+
+    pub fn get_var(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(Variable::scalar)
+    }
+
+    /// The directory `~` stands for: `HOME`, or on Windows, where that is
+    /// rarely set, `USERPROFILE` or else `HOMEDRIVE` and `HOMEPATH` joined.
+    pub fn home(&self) -> Option<String> {
+        if let Some(home) = self.get_var("HOME") {
+            return Some(home.to_string());
+        }
+        #[cfg(windows)]
+        {
+            if let Some(profile) = self.get_var("USERPROFILE") {
+                return Some(profile.to_string());
+            }
+            let drive = self.get_var("HOMEDRIVE");
+            if let (Some(drive), Some(path)) = (drive, self.get_var("HOMEPATH")) {
+                return Some(format!("{}{}", drive, path));
+            }
+        }
+        None
+    }
+
+    /// Sets a variable, keeping its exported flag if it already exists.
+    /// An array has its element 0 set.
+    pub fn set_var(&mut self, name: &str, value: String) {
+        self.var_changed(name);
+        match name {
+            "SECONDS" => {
+                self.seconds_set = Some((Instant::now(), value.trim().parse().unwrap_or(0)));
+            }
+            "RANDOM" => self.random_seed.set(value.trim().parse().unwrap_or(0)),
+            _ => {}
+        }
+        match self.vars.get_mut(name) {
+            Some(Variable {
+                array: Some(Array::Indexed(elements)),
+                ..
+            }) => {
+                elements.insert(0, value);
+            }
+            Some(Variable {
+                array: Some(Array::Associative(elements)),
+                ..
+            }) => {
+                elements.insert("0".to_string(), value);
+            }
+            Some(var) => var.value = value,
+            None => {
+                let var = Variable {
+                    value,
+                    ..Variable::default()
+                };
+                self.vars.insert(name.to_string(), var);
+            }
+        }
+    }
+
+    /// Sets a variable the way a user assignment does, refusing readonly
+    /// variables and evaluating the value of integer ones.
+    pub fn assign(&mut self, name: &str, value: String) -> Result<(), String> {
+        let value = self.assignment_value(name, value)?;
+        self.set_var(name, value);
+        Ok(())
+    }
+
+    /// The value assigning `value` to `name` would store, checked against
+    /// the variable's attributes.
+    pub fn assignment_value(&self, name: &str, value: String) -> Result<String, String> {
+        match self.vars.get(name) {
+            Some(var) if var.readonly => Err(format!("{}: readonly variable", name)),
+            Some(var) if var.integer => Ok(arith::eval(self, &value)?.to_string()),
+            _ => Ok(value),
+        }
+    }
+
+    /// Makes `name` an indexed array of `values`, replacing what it held.
+    pub fn set_array(&mut self, name: &str, values: Vec<String>) {
+        let var = self.var_mut(name);
+        var.array = Some(Array::Indexed(values.into_iter().enumerate().collect()));
+        var.value.clear();
+    }
+
+    /// The element `subscript` of `name`, as [`Array::get`] finds it; a
+    /// variable that isn't an array only has element 0.
+    pub fn element(&self, name: &str, subscript: &str) -> Result<Option<&str>, String> {
+        let Some(var) = self.vars.get(name) else {
+            return Ok(None);
+        };
+        match &var.array {
+            Some(array) => array.get(self, subscript),
+            None => Ok((arith::eval(self, subscript)? == 0).then_some(var.value.as_str())),
+        }
+    }
+
+    /// Sets the elements of `name` from index `start` on to `values`,
+    /// keeping the ones before; a scalar becomes the array's element 0.
+    pub fn set_elements(&mut self, name: &str, start: usize, values: Vec<String>) {
+        let var = self.var_mut(name);
+        let scalar = std::mem::take(&mut var.value);
+        let array = var.array.get_or_insert_with(|| {
+            let first = Some(scalar).filter(|value| !value.is_empty());
+            Array::Indexed(first.into_iter().enumerate().collect())
+        });
+        match array {
+            Array::Indexed(elements) => elements.extend((start..).zip(values)),
+            Array::Associative(elements) => {
+                elements.extend((start..).map(|index| index.to_string()).zip(values))
+            }
+        }
+    }
+
+    /// Makes `name` an array, associative or indexed, as `declare -A` and
+    /// `declare -a` do; a scalar's value becomes its element 0.
+    pub fn make_array(&mut self, name: &str, associative: bool) -> Result<(), String> {
+        let var = self.var_mut(name);
+        match (&var.array, associative) {
+            (Some(Array::Indexed(_)), true) => Err(format!(
+                "{}: cannot convert indexed to associative array",
+                name
+            )),
+            (Some(Array::Associative(_)), false) => Err(format!(
+                "{}: cannot convert associative to indexed array",
+                name
+            )),
+            (Some(_), _) => Ok(()),
+            (None, true) => {
+                let first = Some(std::mem::take(&mut var.value)).filter(|value| !value.is_empty());
+                let elements = first
+                    .map(|value| ("0".to_string(), value))
+                    .into_iter()
+                    .collect();
+                var.array = Some(Array::Associative(elements));
+                Ok(())
+            }
+            (None, false) => {
+                let first = Some(std::mem::take(&mut var.value)).filter(|value| !value.is_empty());
+                var.array = Some(Array::Indexed(first.into_iter().enumerate().collect()));
+                Ok(())
+            }
+        }
+    }
+
+    /// Sets the element `subscript` of `name` to `value`, the way an
+    /// assignment to `name[subscript]` does: `subscript` is the key of an
+    /// associative array, and otherwise an expression for the index.
+    pub fn assign_element(
+        &mut self,
+        name: &str,
+        subscript: &str,
+        value: String,
+    ) -> Result<(), String> {
+        let value = self.assignment_value(name, value)?;
+        if let Some(Array::Associative(elements)) = self.var_mut(name).array.as_mut() {
+            elements.insert(subscript.to_string(), value);
+            return Ok(());
+        }
+        let index = self.element_index(name, subscript)?;
+        self.set_elements(name, index, vec![value]);
+        Ok(())
+    }
+
+    /// Removes the element `subscript` of `name`, as `unset 'name[subscript]'`
+    /// does.
+    pub fn unset_element(&mut self, name: &str, subscript: &str) -> Result<(), String> {
+        let array = self.vars.get(name).and_then(|var| var.array.as_ref());
+        let index = match array {
+            Some(Array::Associative(_)) => 0,
+            _ => self.element_index(name, subscript)?,
+        };
+        self.var_changed(name);
+        let Some(var) = self.vars.get_mut(name) else {
+            return Ok(());
+        };
+        match &mut var.array {
+            Some(Array::Indexed(elements)) => {
+                elements.remove(&index);
+            }
+            Some(Array::Associative(elements)) => {
+                elements.remove(subscript);
+            }
+            // a variable that isn't an array only has element 0
+            None if index == 0 => {
+                self.vars.remove(name);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// The index `subscript` stands for in the indexed array `name`, where
+    /// a negative one counts back from just past the last element.
+    fn element_index(&self, name: &str, subscript: &str) -> Result<usize, String> {
+        let index = arith::eval(self, subscript)?;
+        if index >= 0 {
+            return Ok(index as usize);
+        }
+        let len = match self.vars.get(name).and_then(|var| var.array.as_ref()) {
+            Some(Array::Indexed(elements)) => {
+                elements.keys().next_back().map_or(0, |&last| last + 1)
+            }
+            _ => 1,
+        };
+        usize::try_from(len as i64 + index)
+            .map_err(|_| format!("{}: bad array subscript", subscript))
+    }
+
+    /// What `name+=value` assigns: the value appended to the current one,
+    /// or for an integer variable, an expression adding it.
+    pub fn appended_value(&self, name: &str, value: String) -> String {
+        match self.vars.get(name) {
+            Some(var) if var.integer => format!("{}+({})", var.scalar(), value),
+            Some(var) => var.scalar().to_string() + &value,
+            None => value,
+        }
+    }
+
+    /// The variable `name`, created empty if it doesn't exist, for changing
+    /// its attributes.
+    pub fn var_mut(&mut self, name: &str) -> &mut Variable {
+        self.var_changed(name);
+        self.vars.entry(name.to_string()).or_default()
+    }
+
+    /// Every variable, sorted by name.
+    pub fn vars(&self) -> Vec<(&str, &Variable)> {
+        let mut vars: Vec<_> = self.vars.iter().map(|(k, v)| (k.as_str(), v)).collect();
+        vars.sort_by_key(|(name, _)| *name);
+        vars
+    }
+
+    pub fn var(&self, name: &str) -> Option<&Variable> {
+        self.vars.get(name)
+    }
+
+    /// Puts back a variable saved with [`ShellState::var`], removing it if
+    /// it didn't exist before.
+    pub fn restore_var(&mut self, name: &str, saved: Option<Variable>) {
+        self.var_changed(name);
+        match saved {
+            Some(var) => self.vars.insert(name.to_string(), var),
+            None => self.vars.remove(name),
+        };
+    }
+
+    fn var_changed(&self, name: &str) {
+        if name == "PATH" || name == "PATHEXT" {
+            self.commands.clear();
+        }
+    }
+
+    /// The terminal's width in columns, or `COLUMNS` if the shell isn't
+    /// on a terminal, or 80.
+    pub fn columns(&self) -> usize {
+        #[cfg(unix)]
+        if let Some(width) = crate::sys::terminal_width() {
+            return width;
+        }
+        let columns = self.get_var("COLUMNS").and_then(|c| c.parse().ok());
+        columns.filter(|&c| c > 0).unwrap_or(80)
+    }
+
+    /// Sets `COLUMNS` and `LINES` to the terminal's size when it is new,
+    /// as after a resize; values assigned in between are left alone.
+    #[cfg(unix)]
+    pub fn sync_window_size(&mut self) {
+        let size = crate::sys::terminal_size();
+        if let Some((columns, lines)) = size.filter(|_| size != self.window_size) {
+            self.window_size = size;
+            self.set_var("COLUMNS", columns.to_string());
+            self.set_var("LINES", lines.to_string());
+        }
+    }
+
+    /// Where `name` is found on `PATH`, remembered until `PATH` or
+    /// `PATHEXT` changes.
+    pub fn find_command(&self, name: &str) -> Option<PathBuf> {
+        self.commands
+            .find(name, self.search_var("PATH"), self.search_var("PATHEXT"))
+    }
+
+    /// `PATH` or `PATHEXT`, as the lookups in [`crate::path`] take them.
+    pub fn search_var(&self, name: &str) -> Option<&OsStr> {
+        self.get_var(name).map(OsStr::new)
+    }
+
+    /// The environment handed to child processes. Arrays can't be put in
+    /// it, so they are left out, as bash does.
+    pub fn exported_vars(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars
+            .iter()
+            .filter(|(_, v)| v.exported && v.array.is_none())
+            .map(|(k, v)| (k.as_str(), v.value.as_str()))
+    }
+}
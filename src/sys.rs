@@ -0,0 +1,533 @@
+//! The few process-control calls std doesn't expose, declared directly
+//! against the C library it already links.
+
+use crate::jobs::Pid;
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::raw::{c_char, c_int, c_long, c_ulong};
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+pub const SIGHUP: c_int = 1;
+pub const SIGINT: c_int = 2;
+pub const SIGQUIT: c_int = 3;
+pub const SIGKILL: c_int = 9;
+pub const SIGPIPE: c_int = 13;
+pub const SIGALRM: c_int = 14;
+pub const SIGTERM: c_int = 15;
+pub const SIGTTIN: c_int = 21;
+pub const SIGTTOU: c_int = 22;
+pub const SIGWINCH: c_int = 28;
+#[cfg(target_os = "linux")]
+mod numbers {
+    use std::os::raw::c_int;
+    pub const SIGUSR1: c_int = 10;
+    pub const SIGUSR2: c_int = 12;
+    pub const SIGCHLD: c_int = 17;
+    pub const SIGCONT: c_int = 18;
+    pub const SIGSTOP: c_int = 19;
+    pub const SIGTSTP: c_int = 20;
+}
+#[cfg(not(target_os = "linux"))]
+mod numbers {
+    use std::os::raw::c_int;
+    pub const SIGUSR1: c_int = 30;
+    pub const SIGUSR2: c_int = 31;
+    pub const SIGCHLD: c_int = 20;
+    pub const SIGCONT: c_int = 19;
+    pub const SIGSTOP: c_int = 17;
+    pub const SIGTSTP: c_int = 18;
+}
+pub use numbers::*;
+
+/// Signal names without the `SIG` prefix, as `kill -l` lists them.
+pub const SIGNALS: &[(&str, c_int)] = &[
+    ("HUP", SIGHUP),
+    ("INT", SIGINT),
+    ("QUIT", SIGQUIT),
+    ("KILL", SIGKILL),
+    ("USR1", SIGUSR1),
+    ("USR2", SIGUSR2),
+    ("PIPE", SIGPIPE),
+    ("ALRM", SIGALRM),
+    ("TERM", SIGTERM),
+    ("CHLD", SIGCHLD),
+    ("CONT", SIGCONT),
+    ("STOP", SIGSTOP),
+    ("TSTP", SIGTSTP),
+    ("TTIN", SIGTTIN),
+    ("TTOU", SIGTTOU),
+    ("WINCH", SIGWINCH),
+];
+
+const SIG_DFL: usize = 0;
+const SIG_IGN: usize = 1;
+const WNOHANG: c_int = 1;
+const WUNTRACED: c_int = 2;
+#[cfg(target_os = "linux")]
+const F_DUPFD_CLOEXEC: c_int = 1030;
+#[cfg(not(target_os = "linux"))]
+const F_DUPFD_CLOEXEC: c_int = 67;
+const F_GETFD: c_int = 1;
+#[cfg(target_os = "linux")]
+const TIOCGWINSZ: c_ulong = 0x5413;
+#[cfg(not(target_os = "linux"))]
+const TIOCGWINSZ: c_ulong = 0x4008_7468;
+const TCSANOW: c_int = 0;
+const POLLIN: i16 = 1;
+
+extern "C" {
+    fn fork() -> c_int;
+    fn waitpid(pid: c_int, status: *mut c_int, options: c_int) -> c_int;
+    fn signal(signum: c_int, handler: usize) -> usize;
+    fn kill(pid: c_int, sig: c_int) -> c_int;
+    fn setpgid(pid: c_int, pgid: c_int) -> c_int;
+    fn getpgrp() -> c_int;
+    fn tcsetpgrp(fd: c_int, pgrp: c_int) -> c_int;
+    fn isatty(fd: c_int) -> c_int;
+    fn dup2(oldfd: c_int, newfd: c_int) -> c_int;
+    fn close(fd: c_int) -> c_int;
+    fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+    fn localtime_r(time: *const i64, tm: *mut Tm) -> *mut Tm;
+    fn strftime(s: *mut c_char, max: usize, format: *const c_char, tm: *const Tm) -> usize;
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+    fn tcsetattr(fd: c_int, action: c_int, termios: *const Termios) -> c_int;
+    fn cfmakeraw(termios: *mut Termios);
+    fn poll(fds: *mut PollFd, nfds: c_ulong, timeout: c_int) -> c_int;
+    fn gethostname(name: *mut c_char, len: usize) -> c_int;
+    fn geteuid() -> u32;
+    fn access(path: *const c_char, mode: c_int) -> c_int;
+    fn getrusage(who: c_int, usage: *mut Rusage) -> c_int;
+}
+
+/// Room for a `struct termios`, which is only ever handed to the C library.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Termios([u64; 32]);
+
+#[repr(C)]
+struct Winsize {
+    ws_row: u16,
+    ws_col: u16,
+    ws_xpixel: u16,
+    ws_ypixel: u16,
+}
+
+/// C's `struct tm`, as glibc and the BSDs lay it out.
+#[repr(C)]
+struct Tm {
+    tm_sec: c_int,
+    tm_min: c_int,
+    tm_hour: c_int,
+    tm_mday: c_int,
+    tm_mon: c_int,
+    tm_year: c_int,
+    tm_wday: c_int,
+    tm_yday: c_int,
+    tm_isdst: c_int,
+    tm_gmtoff: c_long,
+    tm_zone: *const c_char,
+}
+
+#[cfg(target_os = "linux")]
+type Suseconds = c_long;
+#[cfg(not(target_os = "linux"))]
+type Suseconds = i32;
+
+#[repr(C)]
+struct Timeval {
+    tv_sec: i64,
+    tv_usec: Suseconds,
+}
+
+/// C's `struct rusage`: the two times, then counters the shell never reads.
+#[repr(C)]
+struct Rusage {
+    ru_utime: Timeval,
+    ru_stime: Timeval,
+    rest: [c_long; 14],
+}
+
+const RUSAGE_SELF: c_int = 0;
+const RUSAGE_CHILDREN: c_int = -1;
+
+pub enum Fork {
+    Child,
+    Parent(Pid),
+}
+
+/// Forks the shell. The shell is single-threaded, so the child can keep
+/// running Rust code, but it must leave through `process::exit`.
+pub fn fork_process() -> io::Result<Fork> {
+    match unsafe { fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        0 => Ok(Fork::Child),
+        pid => Ok(Fork::Parent(pid)),
+    }
+}
+
+pub fn wait_pid(pid: Pid) -> io::Result<ExitStatus> {
+    match waitpid_with(pid, 0)? {
+        Some(status) => Ok(status),
+        None => unreachable!("waitpid without WNOHANG always reports"),
+    }
+}
+
+/// Like [`wait_pid`] but also returns when the process stops; check
+/// [`ExitStatusExt::stopped_signal`] on the result.
+pub fn wait_pid_untraced(pid: Pid) -> io::Result<ExitStatus> {
+    match waitpid_with(pid, WUNTRACED)? {
+        Some(status) => Ok(status),
+        None => unreachable!("waitpid without WNOHANG always reports"),
+    }
+}
+
+/// Polls `pid` without blocking; `None` if it hasn't changed state.
+pub fn try_wait_pid(pid: Pid) -> io::Result<Option<ExitStatus>> {
+    waitpid_with(pid, WNOHANG | WUNTRACED)
+}
+
+/// Waits for whichever child finishes first, returning its pid too.
+pub fn wait_any() -> io::Result<(Pid, ExitStatus)> {
+    match waitpid_raw(-1, 0)? {
+        Some(found) => Ok(found),
+        None => unreachable!("waitpid without WNOHANG always reports"),
+    }
+}
+
+fn waitpid_with(pid: Pid, options: c_int) -> io::Result<Option<ExitStatus>> {
+    Ok(waitpid_raw(pid, options)?.map(|(_, status)| status))
+}
+
+fn waitpid_raw(pid: Pid, options: c_int) -> io::Result<Option<(Pid, ExitStatus)>> {
+    let mut status = 0;
+    loop {
+        match unsafe { waitpid(pid, &mut status, options) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => return Ok(None),
+            found => return Ok(Some((found, ExitStatus::from_raw(status)))),
+        }
+    }
+}
+
+/// Sends `sig` to `pid`, or to the process group `-pid` if negative.
+pub fn send_signal(pid: Pid, sig: c_int) -> io::Result<()> {
+    match unsafe { kill(pid, sig) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Moves `pid` into the process group `pgid`; 0 means the caller or its
+/// own pid respectively.
+pub fn set_process_group(pid: Pid, pgid: Pid) {
+    unsafe {
+        setpgid(pid, pgid);
+    }
+}
+
+pub fn process_group() -> Pid {
+    unsafe { getpgrp() }
+}
+
+/// Makes `pgid` the terminal's foreground process group, if stdin is one.
+pub fn give_terminal_to(pgid: Pid) {
+    unsafe {
+        if isatty(0) == 1 {
+            tcsetpgrp(0, pgid);
+        }
+    }
+}
+
+/// Set by the SIGCHLD handler; the handler does nothing else, so the
+/// children are reaped later, wherever it's safe to touch the job table.
+static CHILD_CHANGED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigchld(_: c_int) {
+    CHILD_CHANGED.store(true, Ordering::SeqCst);
+}
+
+/// Starts noting SIGCHLD, for [`take_child_changed`].
+pub fn watch_children() {
+    unsafe {
+        signal(SIGCHLD, on_sigchld as extern "C" fn(c_int) as usize);
+    }
+}
+
+/// Whether a child exited, was killed or stopped since the last call.
+pub fn take_child_changed() -> bool {
+    CHILD_CHANGED.swap(false, Ordering::SeqCst)
+}
+
+/// One bit per signal that arrived with a trap set and hasn't been acted
+/// on yet.
+static TRAPPED: AtomicU64 = AtomicU64::new(0);
+
+extern "C" fn on_trapped(sig: c_int) {
+    TRAPPED.fetch_or(1 << sig, Ordering::SeqCst);
+    // a trap replaces the handlers the shell itself relies on
+    if sig == SIGCHLD {
+        CHILD_CHANGED.store(true, Ordering::SeqCst);
+    } else if sig == SIGWINCH {
+        WINDOW_CHANGED.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Starts noting `sig`, for [`take_trapped`].
+pub fn trap_signal(sig: c_int) {
+    unsafe {
+        signal(sig, on_trapped as extern "C" fn(c_int) as usize);
+    }
+}
+
+/// The trapped signals that arrived since the last call, lowest first.
+pub fn take_trapped() -> Vec<c_int> {
+    let bits = TRAPPED.swap(0, Ordering::SeqCst);
+    (1..64).filter(|sig| bits & (1 << sig) != 0).collect()
+}
+
+/// Set by SIGINT in an interactive shell, which Ctrl-C mustn't kill;
+/// cleared before each command line.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigint(_: c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Starts noting SIGINT instead of dying of it, for [`interrupted`].
+pub fn watch_interrupts() {
+    unsafe {
+        signal(SIGINT, on_sigint as extern "C" fn(c_int) as usize);
+    }
+}
+
+/// Whether SIGINT arrived since the last [`clear_interrupted`].
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+pub fn clear_interrupted() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+/// The terminal's size as last queried, columns in the high half and
+/// lines in the low, 0 until then; SIGWINCH sets [`WINDOW_CHANGED`] so the
+/// next [`terminal_size`] asks again.
+static SIZE: AtomicU64 = AtomicU64::new(0);
+static WINDOW_CHANGED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigwinch(_: c_int) {
+    WINDOW_CHANGED.store(true, Ordering::SeqCst);
+}
+
+/// Starts noting SIGWINCH, so a resized terminal's new size is seen.
+pub fn watch_window_size() {
+    unsafe {
+        signal(SIGWINCH, on_sigwinch as extern "C" fn(c_int) as usize);
+    }
+}
+
+/// The columns and lines of the terminal on stdout, stderr or stdin,
+/// whichever is one.
+pub fn terminal_size() -> Option<(usize, usize)> {
+    let cached = SIZE.load(Ordering::SeqCst);
+    if cached == 0 || WINDOW_CHANGED.swap(false, Ordering::SeqCst) {
+        let mut size = Winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let found = [1, 2, 0]
+            .into_iter()
+            .any(|fd| unsafe { ioctl(fd, TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0);
+        let packed = match found {
+            true => (u64::from(size.ws_col) << 32) | u64::from(size.ws_row),
+            false => 0,
+        };
+        SIZE.store(packed, Ordering::SeqCst);
+        return found.then(|| (usize::from(size.ws_col), usize::from(size.ws_row)));
+    }
+    Some(((cached >> 32) as usize, (cached & 0xffff_ffff) as usize))
+}
+
+/// The width of the terminal, as [`terminal_size`] finds it.
+pub fn terminal_width() -> Option<usize> {
+    terminal_size().map(|(columns, _)| columns)
+}
+
+pub fn host_name() -> String {
+    let mut buf = [0 as c_char; 256];
+    if unsafe { gethostname(buf.as_mut_ptr(), buf.len() - 1) } == -1 {
+        return String::new();
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(buf.as_ptr()) };
+    name.to_string_lossy().into_owned()
+}
+
+pub fn is_root() -> bool {
+    unsafe { geteuid() == 0 }
+}
+
+pub const R_OK: c_int = 4;
+pub const W_OK: c_int = 2;
+pub const X_OK: c_int = 1;
+
+/// Whether `path` may be used in the ways `mode` combines from `R_OK`,
+/// `W_OK` and `X_OK`, going by the real user and group as `access(2)` does.
+pub fn accessible(path: &std::path::Path, mode: c_int) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { access(path.as_ptr(), mode) == 0 }
+}
+
+pub fn is_terminal(fd: c_int) -> bool {
+    unsafe { isatty(fd) == 1 }
+}
+
+/// Puts the terminal on `fd` into raw mode, where each key is read as it
+/// is pressed and nothing is echoed, until the guard is dropped.
+pub struct RawMode {
+    fd: c_int,
+    saved: Termios,
+}
+
+impl RawMode {
+    pub fn enable(fd: c_int) -> io::Result<RawMode> {
+        let mut saved = Termios([0; 32]);
+        if unsafe { tcgetattr(fd, &mut saved) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = saved;
+        unsafe {
+            cfmakeraw(&mut raw);
+            if tcsetattr(fd, TCSANOW, &raw) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(RawMode { fd, saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            tcsetattr(self.fd, TCSANOW, &self.saved);
+        }
+    }
+}
+
+#[repr(C)]
+struct PollFd {
+    fd: c_int,
+    events: i16,
+    revents: i16,
+}
+
+/// Whether `fd` has input to read within `timeout`, as a lone Escape key
+/// is told from the start of an escape sequence.
+pub fn input_ready(fd: c_int, timeout: Duration) -> bool {
+    let mut poll_fd = PollFd {
+        fd,
+        events: POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis().try_into().unwrap_or(c_int::MAX);
+    unsafe { poll(&mut poll_fd, 1, timeout) > 0 }
+}
+
+/// Rust ignores SIGPIPE; a forked pipeline stage wants the default so it
+/// dies once its reader goes away, like any other process would.
+pub fn default_sigpipe() {
+    default_signal(SIGPIPE);
+}
+
+pub fn default_signal(sig: c_int) {
+    unsafe {
+        signal(sig, SIG_DFL);
+    }
+}
+
+pub fn ignore_signal(sig: c_int) {
+    unsafe {
+        signal(sig, SIG_IGN);
+    }
+}
+
+/// Makes `to` a copy of `from`, closing whatever `to` was first.
+pub fn dup_fd(from: c_int, to: c_int) -> io::Result<()> {
+    if from == to {
+        return Ok(());
+    }
+    match unsafe { dup2(from, to) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+pub fn close_fd(fd: c_int) {
+    unsafe {
+        close(fd);
+    }
+}
+
+pub fn fd_is_open(fd: c_int) -> bool {
+    unsafe { fcntl(fd, F_GETFD) != -1 }
+}
+
+/// Moves `file` to a descriptor of at least `min`, out of the way of the
+/// low numbers scripts open with `exec 3>log`.
+pub fn move_fd_above(file: File, min: c_int) -> File {
+    match unsafe { fcntl(file.as_raw_fd(), F_DUPFD_CLOEXEC, min) } {
+        -1 => file,
+        fd => unsafe { File::from_raw_fd(fd) },
+    }
+}
+
+/// The user and system CPU time used so far by the shell and the children
+/// it has waited for.
+pub fn cpu_times() -> (Duration, Duration) {
+    let (mut user, mut system) = (Duration::ZERO, Duration::ZERO);
+    for who in [RUSAGE_SELF, RUSAGE_CHILDREN] {
+        let mut usage = unsafe { std::mem::zeroed::<Rusage>() };
+        if unsafe { getrusage(who, &mut usage) } == 0 {
+            user += duration(&usage.ru_utime);
+            system += duration(&usage.ru_stime);
+        }
+    }
+    (user, system)
+}
+
+fn duration(time: &Timeval) -> Duration {
+    Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+}
+
+/// Formats `time`, in seconds since the epoch, as local time with
+/// `strftime` specifiers like `%F %T`.
+pub fn format_time(format: &str, time: i64) -> String {
+    let Ok(format) = CString::new(format) else {
+        return String::new();
+    };
+    let mut buf = [0 as c_char; 256];
+    unsafe {
+        let mut tm = std::mem::zeroed::<Tm>();
+        if localtime_r(&time, &mut tm).is_null() {
+            return String::new();
+        }
+        let len = strftime(buf.as_mut_ptr(), buf.len(), format.as_ptr(), &tm);
+        let bytes = std::slice::from_raw_parts(buf.as_ptr() as *const u8, len);
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
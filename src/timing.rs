@@ -0,0 +1,143 @@
+//! The report `time` prints after its pipeline: the real time it took and
+//! the CPU time it used, laid out as `TIMEFORMAT` says.
+
+use std::time::{Duration, Instant};
+
+/// bash's format when `TIMEFORMAT` is unset.
+pub const DEFAULT_FORMAT: &str = "\nreal\t%3lR\nuser\t%3lU\nsys\t%3lS";
+
+/// The format of `time -p`, as POSIX requires.
+pub const POSIX_FORMAT: &str = "real %2R\nuser %2U\nsys %2S";
+
+/// When a timed pipeline started, in real and CPU time.
+pub struct Start {
+    real: Instant,
+    cpu: (Duration, Duration),
+}
+
+/// The times a pipeline took: real, then user and system CPU time.
+#[derive(Debug, Clone, Copy)]
+pub struct Times {
+    pub real: Duration,
+    pub user: Duration,
+    pub sys: Duration,
+}
+
+impl Start {
+    pub fn now() -> Start {
+        Start {
+            real: Instant::now(),
+            cpu: cpu_times(),
+        }
+    }
+
+    pub fn elapsed(&self) -> Times {
+        let (user, sys) = cpu_times();
+        Times {
+            real: self.real.elapsed(),
+            user: user.saturating_sub(self.cpu.0),
+            sys: sys.saturating_sub(self.cpu.1),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn cpu_times() -> (Duration, Duration) {
+    crate::sys::cpu_times()
+}
+
+/// Without `getrusage` only the real time is known.
+#[cfg(not(unix))]
+fn cpu_times() -> (Duration, Duration) {
+    (Duration::ZERO, Duration::ZERO)
+}
+
+/// Fills in `format`: `%R`, `%U` and `%S` are the real, user and system
+/// times in seconds. A digit after the `%` gives the decimal places, three
+/// at most and by default, and an `l` shows minutes too, as in `1m2.500s`.
+/// `%%` is a `%`; anything else is kept as it is.
+pub fn format(format: &str, times: &Times) -> String {
+    let mut out = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let mut spec = String::from("%");
+        let mut places = 3;
+        if let Some(digit) = chars.peek().and_then(|d| d.to_digit(10)) {
+            places = digit.min(3);
+            spec.extend(chars.next());
+        }
+        let long = chars.next_if_eq(&'l').is_some();
+        if long {
+            spec.push('l');
+        }
+        let time = match chars.next() {
+            Some('R') => times.real,
+            Some('U') => times.user,
+            Some('S') => times.sys,
+            Some('%') if spec == "%" => {
+                out.push('%');
+                continue;
+            }
+            other => {
+                out.push_str(&spec);
+                out.extend(other);
+                continue;
+            }
+        };
+        out.push_str(&seconds(time, places, long));
+    }
+    out
+}
+
+/// `time` in seconds to `places` decimals, cut off rather than rounded,
+/// or in minutes and seconds when `long`.
+fn seconds(time: Duration, places: u32, long: bool) -> String {
+    let scale = 10u128.pow(places);
+    let units = time.as_micros() / (1_000_000 / scale);
+    let (whole, fraction) = (units / scale, units % scale);
+    let fraction = match places {
+        0 => String::new(),
+        _ => format!(".{:0width$}", fraction, width = places as usize),
+    };
+    match long {
+        true => format!("{}m{}{}s", whole / 60, whole % 60, fraction),
+        false => format!("{}{}", whole, fraction),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times() -> Times {
+        Times {
+            real: Duration::from_micros(62_504_999),
+            user: Duration::from_millis(1_250),
+            sys: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn default_format_shows_minutes_and_milliseconds() {
+        let report = format(DEFAULT_FORMAT, &times());
+        assert_eq!(report, "\nreal\t1m2.504s\nuser\t0m1.250s\nsys\t0m0.000s");
+    }
+
+    #[test]
+    fn posix_format_shows_seconds_to_two_places() {
+        assert_eq!(
+            format(POSIX_FORMAT, &times()),
+            "real 62.50\nuser 1.25\nsys 0.00"
+        );
+    }
+
+    #[test]
+    fn places_and_unknown_specifiers() {
+        assert_eq!(format("%0R %1U %9S", &times()), "62 1.2 0.000");
+        assert_eq!(format("100%% %0lR %x %", &times()), "100% 1m2s %x %");
+    }
+}
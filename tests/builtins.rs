@@ -0,0 +1,347 @@
+mod common;
+
+use common::{run, run_in, run_script, scratch_dir, shell, stderr, stdout};
+use std::fs;
+
+#[test]
+fn type_reports_reserved_words() {
+    let output = run("type function; type -t '[['; type -t while");
+    assert_eq!(
+        stdout(&output),
+        "function is a shell keyword\nkeyword\nkeyword\n"
+    );
+}
+
+#[test]
+fn failed_cd_stops_an_and_list() {
+    let output = run("cd /nonexistent && echo X");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: cd: /nonexistent: No such file or directory\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn failed_cd_stops_a_set_e_script() {
+    let output = run("set -e; cd /nonexistent; echo X");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn getopts_stops_at_double_dash() {
+    let output = run(r#"
+        while getopts ab: o -a -b x -- -c; do echo "$o $OPTARG"; done
+        echo $OPTIND
+    "#);
+    assert_eq!(stdout(&output), "a \nb x\n5\n");
+}
+
+#[test]
+fn getopts_reports_an_unknown_option() {
+    let output = run(r#"
+        getopts ab o -z; echo "$o [$OPTARG]"
+        OPTIND=1; getopts :ab o -z; echo "$o [$OPTARG]"
+    "#);
+    assert_eq!(stdout(&output), "? []\n? [z]\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: getopts: illegal option -- z\n"
+    );
+}
+
+#[test]
+fn getopts_reports_a_missing_argument() {
+    let output = run(r#"
+        getopts b: o -b; echo "$o [$OPTARG]"
+        OPTIND=1; getopts :b: o -b; echo "$o [$OPTARG]"
+    "#);
+    assert_eq!(stdout(&output), "? []\n: [b]\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: getopts: option requires an argument -- b\n"
+    );
+}
+
+#[test]
+fn printf_q_output_reads_back_as_the_same_words() {
+    let output = run(r#"printf '%q\n' "a b" "it's" "$(printf 'x\ty')" '$HOME' ''"#);
+    assert_eq!(stdout(&output), "a\\ b\nit\\'s\n'x\ty'\n\\$HOME\n''\n");
+    let dir = scratch_dir("printf-q");
+    let words = run_in(
+        &dir,
+        r#"
+        echo "printf '<%s>' $(printf '%q ' "a b" "it's" "$(printf 'x\ty')")" > cmd
+        . ./cmd
+    "#,
+    );
+    assert_eq!(stdout(&words), "<a b><it's><x\ty>");
+}
+
+#[test]
+fn type_t_classifies_each_kind_of_name() {
+    let output = run("alias ll=ls; type -t ll if cd ls");
+    assert_eq!(stdout(&output), "alias\nkeyword\nbuiltin\nfile\n");
+    let output = run("type -t nosuchcommand; echo $?");
+    assert_eq!(stdout(&output), "1\n");
+}
+
+#[test]
+fn echo_without_words() {
+    assert_eq!(stdout(&run("echo")), "\n");
+    assert_eq!(stdout(&run("echo -n")), "");
+    assert_eq!(stdout(&run("echo \"\"")), "\n");
+}
+
+#[test]
+fn command_v_and_capital_v() {
+    let output = run("command -v cd; command -v sh; command -V cd; command -V sh");
+    let sh = stdout(&output).lines().nth(1).unwrap().to_string();
+    assert!(sh.ends_with("/sh"), "{}", sh);
+    let expected = format!("cd\n{}\ncd is a shell builtin\nsh is {}\n", sh, sh);
+    assert_eq!(stdout(&output), expected);
+    let output = run("command -v nope; echo $?; command -V nope; echo $?");
+    assert_eq!(stdout(&output), "1\n1\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: command: nope: not found\n"
+    );
+}
+
+#[test]
+fn thousands_of_builtin_lines_run_in_one_shell() {
+    let mut script = String::from("declare -i n=0 total=0\n");
+    for i in 1..=5000 {
+        script.push_str(&format!("n+=1; total+={}; last=$n\n", i));
+    }
+    script.push_str("for i in {1..5000}; do n+=1; done; echo $n $total $last\n");
+    let started = std::time::Instant::now();
+    let output = run_script(&scratch_dir("builtin-batch"), &script);
+    assert_eq!(stdout(&output), "10000 12502500 5000\n");
+    assert!(
+        started.elapsed().as_secs() < 10,
+        "took {:?}",
+        started.elapsed()
+    );
+}
+
+#[test]
+fn help_describes_builtins_matching_patterns() {
+    let output = run("help -s echo; help 'e*'; help -s 'read*' nothing; echo $?");
+    let expected = "echo: echo [-neE] [arg ...]
+echo: echo [-neE] [arg ...]
+    Write the arguments to standard output.
+exit: exit [n]
+    Leave the shell.
+export: export [-n] [name[=value] ...]
+    Mark variables for the environment.
+read: read [-r] [-a array] [name ...]
+readarray: readarray [-t] [-n count] [-O origin] [-s count] [-u fd] [-C callback] \
+[-c quantum] [array]
+readonly: readonly [name[=value] ...]
+1
+";
+    assert_eq!(stdout(&output), expected);
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: help: no help topics match `nothing'\n"
+    );
+    let output = run("help | grep -c .");
+    assert!(
+        stdout(&output).trim().parse::<usize>().unwrap() >= 25,
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn declare_p_prints_reusable_definitions() {
+    let output = run("a='x y\"z'; export e=1; readonly r=2; declare -i n=3; declare -p a e r n");
+    let expected = "declare -- a=\"x y\\\"z\"\ndeclare -x e=\"1\"\ndeclare -r r=\"2\"\n\
+                    declare -i n=\"3\"\n";
+    assert_eq!(stdout(&output), expected);
+    let dir = scratch_dir("declare-p");
+    let output = run_in(
+        &dir,
+        "a='x y\"z $'; declare -p a > def; a=; . ./def; echo \"$a\"",
+    );
+    assert_eq!(stdout(&output), "x y\"z $\n");
+}
+
+#[test]
+fn cd_dash_without_oldpwd() {
+    let output = shell()
+        .arg("-c")
+        .arg("cd -")
+        .env_remove("OLDPWD")
+        .output()
+        .unwrap();
+    assert_eq!(stderr(&output), "codecrafters-shell: cd: OLDPWD not set\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn read_handles_backslashes_unless_raw() {
+    let dir = scratch_dir("read-backslash");
+    fs::write(dir.join("in"), "a\\ b\\\nc\n").unwrap();
+    let script = "read x < in; echo \"[$x]\"; read p q < in; echo \"[$p|$q]\"; \
+                  read -r y < in; echo \"[$y]\"";
+    let output = run_in(&dir, script);
+    assert_eq!(stdout(&output), "[a bc]\n[a bc|]\n[a\\ b\\]\n");
+}
+
+#[test]
+fn read_a_splits_into_a_replaced_array() {
+    let dir = scratch_dir("read-array");
+    fs::write(dir.join("in"), "  x y\\ z\tw  \n").unwrap();
+    let script = "arr[7]=old; read -a arr < in; echo \"${arr[1]}|${arr[2]}|[${arr[7]}]\"
+        read -ra raw < in; echo ${raw[@]}; declare -p arr | cut -c 1-12";
+    let output = run_in(&dir, script);
+    assert_eq!(stdout(&output), "y z|w|[]\nx y\\ z w\ndeclare -a a\n");
+}
+
+#[test]
+fn unicode_escapes() {
+    let output = run(r#"echo -e 'é \U0001F600 ☺' '[\UFFFFFFFF]'; printf '%b\n' 'é'"#);
+    assert_eq!(
+        stdout(&output),
+        "\u{e9} \u{1F600} \u{263a} [\u{FFFD}]\n\u{e9}\n"
+    );
+}
+
+#[test]
+fn trap_p_prints_traps_that_can_be_run_again() {
+    let output = run("trap 'echo \"it'\\''s\"' EXIT; trap 'echo int' INT; trap -p; trap -p INT");
+    let expected = "trap -- 'echo \"it'\\''s\"' EXIT\ntrap -- 'echo int' SIGINT\n\
+                    trap -- 'echo int' SIGINT\nit's\n";
+    assert_eq!(stdout(&output), expected);
+    let dir = scratch_dir("trap-p");
+    let output = run_in(
+        &dir,
+        "trap 'echo bye' EXIT; trap -p > saved; trap - EXIT; . ./saved",
+    );
+    assert_eq!(stdout(&output), "bye\n");
+}
+
+#[test]
+fn colon_expands_its_words_and_succeeds() {
+    let output = run(": ${X:=5}; echo $X; false; : nothing; echo $?; n=0; while :; do break; done");
+    assert_eq!(stdout(&output), "5\n0\n");
+}
+
+#[test]
+fn too_deep_aliases_stop_with_a_warning() {
+    let mut script = String::from("shopt -s expand_aliases\nalias a=b b=a\n");
+    for i in 0..120 {
+        script += &format!("alias a{}=a{}\n", i, i + 1);
+    }
+    script += "a\na0\necho done\n";
+    let output = run_script(&scratch_dir("alias-depth"), &script);
+    assert_eq!(stdout(&output), "done\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: a: command not found\n\
+         codecrafters-shell: a100: alias expansion nested too deeply\n\
+         codecrafters-shell: a100: command not found\n"
+    );
+}
+
+#[test]
+fn cd_without_home() {
+    let output = shell()
+        .arg("-c")
+        .arg("cd; echo $?; pwd")
+        .env_remove("HOME")
+        .output()
+        .unwrap();
+    assert_eq!(stderr(&output), "codecrafters-shell: cd: HOME not set\n");
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(stdout(&output), format!("1\n{}\n", cwd.display()));
+}
+
+#[test]
+fn large_builtin_output_arrives_whole_and_in_order() {
+    let script = "echo {1..100000} | wc -c; printf '%s\\n' {1..100000} | tail -n 1
+        for i in {1..3000}; do alias a$i=$i; done; alias | wc -l; alias | tail -n 1; echo after";
+    let output = run(script);
+    let echoed: usize = (1..=100000).map(|n: usize| n.to_string().len() + 1).sum();
+    let text = stdout(&output);
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    let echoed = echoed.to_string();
+    assert_eq!(
+        lines,
+        [
+            echoed.as_str(),
+            "100000",
+            "3000",
+            "alias a999='999'",
+            "after"
+        ]
+    );
+}
+
+#[test]
+fn set_replaces_the_positional_parameters() {
+    let output = run(
+        "set -- x y; echo $2 $#; set --; echo $#; set -e -- -a b; echo $1 $#
+        set p q r; echo $2 $3; set -; echo $#; set - -x; echo $1",
+    );
+    assert_eq!(stdout(&output), "y 2\n0\n-a 2\nq r\n3\n-x\n");
+}
+
+#[test]
+fn declare_a_makes_associative_arrays() {
+    let script = "declare -A m; m[one]=1; m[two]=2; k=two; m[$k]+=x
+        echo ${m[one]} ${m[$k]} \"[${m[three]}]\"
+        for key in \"${!m[@]}\"; do echo \"$key=${m[$key]}\"; done | sort
+        printf '%s\\n' \"${m[@]}\" | sort
+        declare -p m | cut -c 1-12";
+    let output = run(script);
+    assert_eq!(
+        stdout(&output),
+        "1 2x []\none=1\ntwo=2x\n1\n2x\ndeclare -A m\n"
+    );
+    let output = run("a[1]=x; declare -A a");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("a: cannot convert indexed to associative array"));
+}
+
+#[test]
+fn unset_removes_variables_and_single_elements() {
+    let script = "declare -A m; m[a]=1; m[b]=2; unset 'm[a]'; echo ${!m[@]} \"[${m[a]}]\"
+        x=5; unset x; echo \"[$x]\"; l[0]=p; l[1]=q; l[2]=r; unset 'l[-2]'; echo ${l[@]}
+        readonly r=1; unset r; echo $? $r";
+    let output = run(script);
+    assert_eq!(stdout(&output), "b []\n[]\np r\n1 1\n");
+    assert!(stderr(&output).contains("unset: r: cannot unset: readonly variable"));
+}
+
+#[test]
+fn mapfile_reads_lines_into_an_array() {
+    let dir = scratch_dir("mapfile");
+    fs::write(dir.join("in"), "one\ntwo 2\nthree\nfour\nfive\n").unwrap();
+    let script = "mapfile -t lines < in; echo \"${lines[1]}|${lines[4]}\"
+        mapfile -s 1 -n 2 < in; echo \"[${MAPFILE[@]}]\"
+        x=keep; readarray -t -O 2 x < in; echo ${x[0]} ${x[1]} ${x[2]} ${x[6]}";
+    let output = run_in(&dir, script);
+    assert_eq!(
+        stdout(&output),
+        "two 2|five\n[two 2\n three\n]\nkeep one five\n"
+    );
+}
+
+#[test]
+fn mapfile_runs_its_callback_every_quantum_lines() {
+    let dir = scratch_dir("mapfile-callback");
+    let lines: String = (1..=10).map(|n| format!("line {}\n", n)).collect();
+    fs::write(dir.join("in"), lines).unwrap();
+    let script = "mapfile -t -C 'printf \"%s=%s;\"' -c 3 lines < in; echo; echo ${lines[9]}
+        mapfile -O 5 -C 'printf \"[%s]\"' -c1 -n 2 more < in";
+    let output = run_in(&dir, script);
+    assert_eq!(
+        stdout(&output),
+        "2=line 3;5=line 6;8=line 9;\nline 10\n[5][line 1\n][6][line 2\n]"
+    );
+}
@@ -0,0 +1,77 @@
+//! Helpers for running the shell binary from the integration tests.
+
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+pub fn shell() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_codecrafters-shell"))
+}
+
+/// Runs `script` with `-c`, with no input.
+pub fn run(script: &str) -> Output {
+    shell()
+        .arg("-c")
+        .arg(script)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+/// Runs `script` with `-c` in `dir`.
+pub fn run_in(dir: &Path, script: &str) -> Output {
+    shell()
+        .arg("-c")
+        .arg(script)
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+/// Writes `text` to a script in `dir` and runs it there.
+pub fn run_script(dir: &Path, text: &str) -> Output {
+    std::fs::write(dir.join("script.sh"), text).unwrap();
+    shell()
+        .arg("script.sh")
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+/// Feeds `input` to the shell on a pipe, as the codecrafters tester does,
+/// so it prompts but isn't reading from a terminal.
+pub fn run_piped(mut command: Command, input: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// A fresh, empty directory for one test to work in.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rust-cli-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
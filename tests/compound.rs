@@ -0,0 +1,112 @@
+mod common;
+
+use common::{run, run_in, run_script, scratch_dir, stderr, stdout};
+
+#[test]
+fn subshell_changes_stay_inside_it() {
+    let dir = scratch_dir("subshell");
+    let output = run_in(&dir, "(cd /; pwd; x=1; exit 3); echo $? [$x]; pwd");
+    assert_eq!(stdout(&output), format!("/\n3 []\n{}\n", dir.display()));
+}
+
+#[test]
+fn compound_commands_leave_the_status_of_their_last_command() {
+    let output = run(r#"
+        if false; then :; fi; echo "if $?"
+        if false; then :; else (exit 4); fi; echo "else $?"
+        while false; do :; done; echo "while $?"
+        until true; do :; done; echo "until $?"
+        for i in 1 2; do (exit $i); done; echo "for $?"
+        for i in; do false; done; echo "empty for $?"
+        case x in y) false;; esac; echo "case $?"
+        { (exit 6); }; echo "group $?"
+    "#);
+    assert_eq!(
+        stdout(&output),
+        "if 0\nelse 4\nwhile 0\nuntil 0\nfor 2\nempty for 0\ncase 0\ngroup 6\n"
+    );
+}
+
+#[test]
+fn comments_and_blank_lines_inside_a_loop_body() {
+    let dir = scratch_dir("loop-comments");
+    let script = "# a loop\nfor i in 1 2\ndo\n  # comment\n\n  echo $i # trailing\ndone\n";
+    let output = run_script(&dir, script);
+    assert_eq!(stdout(&output), "1\n2\n");
+}
+
+#[test]
+fn while_read_loop_reads_a_redirected_file() {
+    let dir = scratch_dir("while-read");
+    std::fs::write(dir.join("in"), "l1\nl2\n").unwrap();
+    let output = run_in(&dir, r#"while read l; do echo "<$l>"; done < in"#);
+    assert_eq!(stdout(&output), "<l1>\n<l2>\n");
+}
+
+#[test]
+fn loop_and_if_output_can_be_redirected_and_piped() {
+    let dir = scratch_dir("loop-redirect");
+    let script = "for i in a b; do echo $i; done > o; cat o; \
+        if true; then echo if; fi | tr a-z A-Z";
+    let output = run_in(&dir, script);
+    assert_eq!(stdout(&output), "a\nb\nIF\n");
+}
+
+#[test]
+fn keywords_only_start_compound_commands_as_the_first_word() {
+    let script = "type if; echo if then fi; if true; then echo yes; fi; x=if; $x true; echo $?";
+    let output = run(script);
+    assert_eq!(
+        stdout(&output),
+        "if is a shell keyword\nif then fi\nyes\n127\n"
+    );
+}
+
+#[test]
+fn bare_exit_keeps_the_status_of_the_last_command() {
+    let cases = [
+        ("false; exit", 1),
+        ("if false; then :; fi; exit", 0),
+        ("for i in 1; do false; exit; done", 1),
+        ("while false; do :; done; exit", 0),
+        ("case x in x) false;; esac; exit", 1),
+        ("(false); exit", 1),
+        ("false || { exit; }", 1),
+        ("for i in 1 2; do (exit 3); done; (exit)", 3),
+        ("exit 5; exit", 5),
+    ];
+    for (script, code) in cases {
+        assert_eq!(run(script).status.code(), Some(code), "{}", script);
+    }
+    let output = run("true; exit abc; echo still here");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: exit: abc: numeric argument required\n"
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn functions_take_arguments_and_return_a_status() {
+    let script = "greet() { echo \"hi $1 $#\"; return 3; echo unreached; }
+        greet world x; echo $? $#
+        f()
+        {
+            for i in 1 2 3; do [[ $i == 2 ]] && return; echo $i; done
+        }
+        f; echo $?; type greet; type -t f; echo | greet piped; echo $(greet sub)";
+    let output = run(script);
+    assert_eq!(
+        stdout(&output),
+        "hi world 2\n3 0\n1\n0\ngreet is a function\n\
+         greet() { echo \"hi $1 $#\"; return 3; echo unreached; }\n\
+         function\nhi piped 1\nhi sub 1\n"
+    );
+    let output = run("return 1; echo $?");
+    assert_eq!(stdout(&output), "1\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: return: can only `return' from a function\n"
+    );
+}
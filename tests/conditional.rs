@@ -0,0 +1,31 @@
+mod common;
+
+use common::{run, stdout};
+
+#[test]
+fn regex_match_fills_bash_rematch_with_the_groups() {
+    let output = run(concat!(
+        "[[ abc =~ ^(a)(b)(x)? ]]; ",
+        r#"echo "${BASH_REMATCH[0]} ${BASH_REMATCH[1]} ${BASH_REMATCH[2]} [${BASH_REMATCH[3]}]""#
+    ));
+    assert_eq!(stdout(&output), "ab a b []\n");
+}
+
+#[test]
+fn each_rematch_element_is_its_own_word_in_quotes() {
+    let output = run(r#"[[ xy =~ (x)(y) ]]; for w in "${BASH_REMATCH[@]}"; do echo "<$w>"; done"#);
+    assert_eq!(stdout(&output), "<xy>\n<x>\n<y>\n");
+}
+
+#[test]
+fn failed_match_empties_bash_rematch() {
+    let output = run(r#"[[ ab =~ (a) ]]; [[ zz =~ a ]]; echo $? "[${BASH_REMATCH[1]}]""#);
+    assert_eq!(stdout(&output), "1 []\n");
+}
+
+#[test]
+fn double_brackets_match_patterns_without_splitting() {
+    let script = r#"f="a b.c"; [[ $f == *.c ]] && echo m1; [[ $f == "a b"* ]] && echo m2;
+        [[ $f == "*.c" ]] || echo m3; [[ -n $f && $f != a ]] && echo m4"#;
+    assert_eq!(stdout(&run(script)), "m1\nm2\nm3\nm4\n");
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use common::{run, run_in, run_script, scratch_dir, shell, stderr, stdout};
+use std::fs;
+
+#[test]
+fn braces_expand_before_globs() {
+    let dir = scratch_dir("brace-glob");
+    for file in ["src/main.rs", "src/lib.rs", "tests/cli.rs", "docs/guide.rs"] {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+    let output = run_in(&dir, "echo {src,tests}/*.rs");
+    assert_eq!(stdout(&output), "src/lib.rs src/main.rs tests/cli.rs\n");
+}
+
+#[test]
+fn globbing_a_large_directory_stays_fast() {
+    let dir = scratch_dir("glob-many");
+    for n in 0..5000 {
+        let name = if n % 2 == 0 {
+            format!("f{}.rs", n)
+        } else {
+            format!("f{}.txt", n)
+        };
+        fs::write(dir.join(name), "").unwrap();
+    }
+    let started = std::time::Instant::now();
+    let output = run_in(&dir, "set -- [f]*[0-9]8.r?; echo $#; set -- *.txt; echo $#");
+    assert_eq!(stdout(&output), "499\n2500\n");
+    assert!(
+        started.elapsed().as_secs() < 10,
+        "took {:?}",
+        started.elapsed()
+    );
+}
+
+#[test]
+fn lineno_is_the_line_of_the_running_command() {
+    let dir = scratch_dir("lineno");
+    fs::write(dir.join("lib.sh"), "\necho lib $LINENO\n").unwrap();
+    let script = "echo $LINENO
+if true; then
+  echo $LINENO
+fi
+for i in 1 2; do
+
+  echo \"$i:$LINENO\"; done
+echo \"a
+b\" $(echo $LINENO)
+. ./lib.sh; echo $LINENO
+";
+    let output = run_script(&dir, script);
+    assert_eq!(stdout(&output), "1\n3\n1:7\n2:7\na\nb 8\nlib 2\n10\n");
+}
+
+#[test]
+fn seconds_counts_up_from_the_start_or_an_assignment() {
+    let output = run("echo $SECONDS; sleep 1.2; echo ${SECONDS}; SECONDS=100; echo $SECONDS");
+    assert_eq!(stdout(&output), "0\n1\n100\n");
+}
+
+#[test]
+fn random_repeats_after_a_seed_and_varies_without() {
+    let script = "RANDOM=42; echo $RANDOM $RANDOM; RANDOM=42; echo $RANDOM $RANDOM";
+    let seeded = stdout(&run(script));
+    let lines: Vec<&str> = seeded.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], lines[1]);
+    assert_eq!(seeded, stdout(&run(script)));
+    let unseeded = || stdout(&run("echo $RANDOM $RANDOM $RANDOM $RANDOM"));
+    let (first, second) = (unseeded(), unseeded());
+    assert_ne!(first, second);
+    for n in first.split_whitespace().chain(second.split_whitespace()) {
+        assert!(n.parse::<u16>().is_ok_and(|n| n < 32768), "{}", n);
+    }
+}
+
+#[test]
+fn tilde_expands_at_the_start_and_after_assignment_colons() {
+    let output = run("HOME=/h; echo a~b ~ ~/x; X=~/dir; PATH=~/a:~/b; echo $X $PATH");
+    assert_eq!(stdout(&output), "a~b /h /h/x\n/h/dir /h/a:/h/b\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn tilde_ignores_the_windows_profile_variables() {
+    let mut command = shell();
+    command
+        .arg("-c")
+        .arg("echo ~")
+        .env_remove("HOME")
+        .env("USERPROFILE", "/p");
+    assert_eq!(stdout(&command.output().unwrap()), "~\n");
+}
+
+#[test]
+fn append_assignments() {
+    let output = run("X=a; X+=b; declare -i n=1; n+=4; unset_before+=z; echo $X $n $unset_before");
+    assert_eq!(stdout(&output), "ab 5 z\n");
+}
+
+#[test]
+fn leading_assignments_go_to_the_command_only() {
+    let output = run("v=x; A=1 B=$v$v sh -c 'echo $A $B'; A=1 echo B=2; echo \"[$A][$B]\"");
+    assert_eq!(stdout(&output), "1 xx\nB=2\n[][]\n");
+}
+
+#[test]
+fn required_parameter_aborts_the_script() {
+    let output = run("echo ${X:?needed}; echo after");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "codecrafters-shell: X: needed\n");
+    assert_eq!(output.status.code(), Some(1));
+    let output = run("X=; echo \"[${X?unset only}]\"; X=set; echo ${X:?needed}");
+    assert_eq!(stdout(&output), "[]\nset\n");
+}
+
+#[test]
+fn assign_default_sets_the_variable() {
+    let output = run(": ${X:=5}; echo $X; Y=; : ${Y:=6}; Z=7; : ${Z:=8}; echo $Y $Z");
+    assert_eq!(stdout(&output), "5\n6 7\n");
+}
+
+#[test]
+fn indirect_values_and_names_by_prefix() {
+    let output = run("a=1 ab=2 b=3 r=b; echo ${!r} ${!a*} \"${!a@}\"");
+    assert_eq!(stdout(&output), "3 a ab a ab\n");
+}
+
+#[test]
+fn case_modification() {
+    let output = run("v='MiXed wOrds'; echo \"${v^}|${v^^}|${v,}|${v,,}|${v^^[ix]}|${v,,[MX]}\"");
+    assert_eq!(
+        stdout(&output),
+        "MiXed wOrds|MIXED WORDS|miXed wOrds|mixed words|MIXed wOrds|mixed wOrds\n"
+    );
+}
+
+#[test]
+fn command_substitution_captures_only_stdout_in_a_subshell() {
+    let dir = scratch_dir("capture");
+    let script = "x=$(echo hi; echo err >&2; cd /; y=1; exit 3); echo \"[$x] $? [$y]\"; pwd; \
+                  z=`printf 'a\\n\\n'`; echo \"[$z]\" \"$(echo inner $(echo nested))\"";
+    let output = run_in(&dir, script);
+    let expected = format!("[hi] 3 []\n{}\n[a] inner nested\n", dir.display());
+    assert_eq!(stdout(&output), expected);
+    assert_eq!(stderr(&output), "err\n");
+}
+
+#[test]
+fn output_that_is_not_captured_goes_to_the_redirection() {
+    let dir = scratch_dir("inherit");
+    let output = run_in(&dir, "{ echo a; x=$(echo b); echo $x; } > out; cat out");
+    assert_eq!(stdout(&output), "a\nb\n");
+}
+
+#[test]
+fn double_quotes_expand_without_splitting_or_globbing() {
+    let dir = scratch_dir("quoted-expansion");
+    fs::write(dir.join("file"), "").unwrap();
+    let script = "v='a  b'; printf '[%s]\\n' \"$v $(echo 'x  y') *\" '$v $(echo x) *'";
+    let output = run_in(&dir, script);
+    assert_eq!(stdout(&output), "[a  b x  y *]\n[$v $(echo x) *]\n");
+}
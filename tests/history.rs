@@ -0,0 +1,39 @@
+mod common;
+
+use common::{run_piped, scratch_dir, shell, stdout};
+
+#[test]
+fn piped_input_is_not_history_expanded() {
+    let output = run_piped(shell(), "echo foo!bar\n");
+    assert_eq!(stdout(&output), "$ foo!bar\n$ ");
+}
+
+#[test]
+fn set_h_turns_expansion_on_for_piped_input() {
+    let output = run_piped(shell(), "set -H\necho one\necho !!\n");
+    assert!(
+        stdout(&output).contains("echo one\n"),
+        "{:?}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn history_lists_timestamps_in_histtimeformat() {
+    let dir = scratch_dir("histtimeformat");
+    let file = dir.join("history");
+    std::fs::write(&file, "#86400\necho old\n").unwrap();
+    let mut command = shell();
+    command.env("HISTFILE", &file).env("TZ", "UTC");
+    let output = run_piped(command, "HISTTIMEFORMAT='%Y-%m-%d '\nhistory 3\n");
+    let out = stdout(&output);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[0], "$ $     1  1970-01-02 echo old");
+    assert!(
+        lines[2].starts_with("    3  2") && lines[2].ends_with(" history 3"),
+        "{}",
+        out
+    );
+    let saved = std::fs::read_to_string(&file).unwrap();
+    assert!(saved.starts_with("#86400\necho old\n#"), "{}", saved);
+}
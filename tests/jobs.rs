@@ -0,0 +1,104 @@
+mod common;
+
+use common::{run, run_piped, scratch_dir, shell, stderr, stdout};
+use std::fs;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+#[test]
+fn wait_n_returns_when_the_first_job_finishes() {
+    let dir = scratch_dir("wait-n");
+    let start = Instant::now();
+    // not through a pipe, which the slow job would hold open
+    let status = shell()
+        .arg("-c")
+        .arg("sleep 0.2 & sleep 3 & wait -n; echo $? > status; jobs | wc -l >> status")
+        .current_dir(&dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(
+        start.elapsed() < Duration::from_secs(2),
+        "took {:?}",
+        start.elapsed()
+    );
+    assert!(status.success());
+    let text = fs::read_to_string(dir.join("status")).unwrap();
+    assert_eq!(text.split_whitespace().collect::<Vec<_>>(), ["0", "1"]);
+}
+
+#[test]
+fn wait_n_gives_the_status_of_the_finished_job() {
+    let output = run("(exit 7) & wait -n; echo $?");
+    assert_eq!(stdout(&output), "7\n");
+}
+
+#[test]
+fn wait_n_without_jobs_fails() {
+    let output = run("wait -n; echo $?");
+    assert_eq!(stdout(&output), "127\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn finished_background_job_is_reported_done_once() {
+    let output = run("true & sleep 0.3; jobs; echo next; jobs");
+    assert_eq!(
+        stdout(&output),
+        "[1]+  Done                    true\nnext\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn background_pipeline_is_one_job() {
+    let start = Instant::now();
+    let output = run("sleep 5 | sleep 6 & jobs; kill %1; wait %1; echo $?; jobs");
+    assert_eq!(
+        stdout(&output),
+        "[1]+  Running                 sleep 5 | sleep 6 &\n143\n"
+    );
+    // the kill reached both stages, or they would hold stdout open
+    assert!(
+        start.elapsed() < Duration::from_secs(4),
+        "took {:?}",
+        start.elapsed()
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn kill_0_checks_for_a_process_without_signalling_it() {
+    let script = "kill -0 $$; echo $?; kill -0 999999; echo $?
+        sleep 5 & kill -STOP $!; sleep 0.2; jobs >/dev/null; kill -0 %1; echo $?
+        sleep 0.2; cut -d' ' -f3 /proc/$!/stat; kill -9 %1";
+    let output = run(script);
+    assert_eq!(stdout(&output), "0\n1\n0\nT\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: kill: (999999) - No such process\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn background_jobs_run_without_a_terminal() {
+    let script = "sleep 0.1 & echo started; fg %1 >/dev/null; echo $?; (exit 3) & wait $!; echo $?";
+    let output = run(script);
+    assert_eq!(stdout(&output), "started\n0\n3\n");
+    assert_eq!(stderr(&output), "");
+    let output = run_piped(shell(), "sleep 0.1 &\nfg >/dev/null\necho $?\n");
+    assert_eq!(stdout(&output), "$ $ $ 0\n$ ");
+    // the job is announced, and nothing complains about the terminal
+    let announced = stderr(&output)
+        .strip_prefix("[1] ")
+        .unwrap_or_default()
+        .to_string();
+    assert!(
+        announced.trim_end().parse::<u32>().is_ok(),
+        "{}",
+        stderr(&output)
+    );
+}
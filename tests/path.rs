@@ -0,0 +1,190 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{run_in, scratch_dir, stderr, stdout};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+fn commands_are_not_run_from_the_working_directory() {
+    let dir = scratch_dir("cwd-not-on-path");
+    fs::write(dir.join("here"), "#!/bin/sh\necho ran\n").unwrap();
+    fs::set_permissions(dir.join("here"), fs::Permissions::from_mode(0o755)).unwrap();
+    let output = run_in(&dir, "PATH=/bin:/usr/bin; here; echo $?");
+    assert_eq!(stdout(&output), "127\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: here: command not found\n"
+    );
+    let output = run_in(&dir, "./here; PATH=/bin:.; here");
+    assert_eq!(stdout(&output), "ran\nran\n");
+}
+
+#[test]
+fn path_changes_take_effect_after_a_lookup() {
+    let dir = scratch_dir("path-prepend");
+    fs::create_dir(dir.join("bin")).unwrap();
+    fs::write(dir.join("bin/ls"), "#!/bin/sh\necho mine\n").unwrap();
+    fs::set_permissions(dir.join("bin/ls"), fs::Permissions::from_mode(0o755)).unwrap();
+    let bin = dir.join("bin");
+    let script = format!(
+        "command -v ls; export PATH={}:$PATH; ls; command -v ls",
+        bin.display()
+    );
+    let output = run_in(&dir, &script);
+    let out = stdout(&output);
+    let lines: Vec<&str> = out.lines().collect();
+    let mine = format!("{}/bin/ls", dir.display());
+    assert_eq!(lines.len(), 3, "{:?}", lines);
+    assert_ne!(lines[0], mine);
+    assert_eq!(lines[1..], ["mine", mine.as_str()]);
+}
+
+#[test]
+fn empty_path_entries_are_the_working_directory() {
+    let dir = scratch_dir("empty-path-entry");
+    fs::write(dir.join("here"), "#!/bin/sh\necho ran\n").unwrap();
+    fs::set_permissions(dir.join("here"), fs::Permissions::from_mode(0o755)).unwrap();
+    let output = run_in(
+        &dir,
+        "PATH=:/usr/bin; here; PATH=/usr/bin::/bin; here; PATH=/bin:; here",
+    );
+    assert_eq!(stdout(&output), "ran\nran\nran\n");
+}
+
+#[test]
+fn cd_keeps_the_symlink_path_in_pwd() {
+    let dir = scratch_dir("cd-symlink");
+    fs::create_dir(dir.join("real")).unwrap();
+    std::os::unix::fs::symlink(dir.join("real"), dir.join("link")).unwrap();
+    let script = format!(
+        "cd {}/link; echo $PWD; pwd -P; /bin/pwd; cd ..; echo $PWD $OLDPWD; cd -P link; echo $PWD",
+        dir.display()
+    );
+    let output = run_in(&dir, &script);
+    let d = dir.display();
+    let expected = format!("{d}/link\n{d}/real\n{d}/real\n{d} {d}/link\n{d}/real\n");
+    assert_eq!(stdout(&output), expected);
+}
+
+#[test]
+fn cd_drops_trailing_and_doubled_slashes_from_pwd() {
+    let dir = scratch_dir("cd-slashes");
+    fs::create_dir_all(dir.join("a/b")).unwrap();
+    let script = format!(
+        "cd {}/a/; echo $PWD; cd /; pwd; cd {}; cd a//b/; pwd; echo $OLDPWD",
+        dir.display(),
+        dir.display()
+    );
+    let output = run_in(&dir, &script);
+    let d = dir.display();
+    assert_eq!(stdout(&output), format!("{d}/a\n/\n{d}/a/b\n{d}\n"));
+}
+
+#[test]
+fn bogus_pwd_and_oldpwd_give_way_to_the_real_directory() {
+    let dir = scratch_dir("cd-bogus-pwd");
+    fs::create_dir_all(dir.join("a")).unwrap();
+    let script = "cd a; PWD=/nowhere; pwd; cd ..; pwd; echo $OLDPWD; PWD=/; cd a; pwd; \
+                  cd -; OLDPWD=/nowhere; cd -; echo $? $PWD";
+    let output = run_in(&dir, script);
+    let d = dir.display();
+    assert_eq!(
+        stdout(&output),
+        format!("{d}/a\n{d}\n{d}/a\n{d}/a\n{d}\n1 {d}\n")
+    );
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: cd: /nowhere: No such file or directory\n"
+    );
+}
+
+#[test]
+fn type_a_lists_every_match_in_path_order() {
+    let dir = scratch_dir("type-a");
+    for sub in ["one", "two", "three"] {
+        fs::create_dir(dir.join(sub)).unwrap();
+        fs::write(dir.join(sub).join("echo"), "#!/bin/sh\n").unwrap();
+    }
+    // not executable, so neither run nor listed
+    for sub in ["one", "three"] {
+        fs::set_permissions(
+            dir.join(sub).join("echo"),
+            fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+    }
+    let d = dir.display();
+    let script = format!("PATH={d}/one:{d}/two:{d}/three; type -a echo; type -at echo; type echo");
+    let output = run_in(&dir, &script);
+    let expected = format!(
+        "echo is a shell builtin\necho is {d}/one/echo\necho is {d}/three/echo\n\
+         builtin\nfile\nfile\necho is a shell builtin\n"
+    );
+    assert_eq!(stdout(&output), expected);
+}
+
+#[test]
+fn missing_commands_fail_with_127_wherever_they_run() {
+    let dir = scratch_dir("not-found");
+    let output = run_in(
+        &dir,
+        "nope1 a b; echo $?; echo x | nope2; echo $?; x=$(nope3 c); echo $?",
+    );
+    assert_eq!(stdout(&output), "127\n127\n127\n");
+    let expected: String = (1..=3)
+        .map(|n| format!("codecrafters-shell: nope{}: command not found\n", n))
+        .collect();
+    assert_eq!(stderr(&output), expected);
+}
+
+#[test]
+fn command_not_found_handle_gets_the_missing_command() {
+    let dir = scratch_dir("not-found-handler");
+    let script =
+        "command_not_found_handle() { echo \"[$1] [$2] [$3] $#\"; x=set; nope2; return 42; }
+        nope1 a 'b c'; echo $? \"[$x]\"; ./nope3; echo $?";
+    let output = run_in(&dir, script);
+    assert_eq!(stdout(&output), "[nope1] [a] [b c] 3\n42 []\n127\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: nope2: command not found\n\
+         codecrafters-shell: ./nope3: No such file or directory\n"
+    );
+}
+
+#[test]
+fn directories_and_unexecutable_files_fail_with_126() {
+    let dir = scratch_dir("not-executable");
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("plain"), "echo ran\n").unwrap();
+    let script = "./sub; echo $?; ./plain; echo $?; echo | ./plain; echo $?; ./gone; echo $?";
+    let output = run_in(&dir, script);
+    assert_eq!(stdout(&output), "126\n126\n126\n127\n");
+    let expected = "codecrafters-shell: ./sub: is a directory
+codecrafters-shell: ./plain: Permission denied
+codecrafters-shell: ./plain: Permission denied
+codecrafters-shell: ./gone: No such file or directory
+";
+    assert_eq!(stderr(&output), expected);
+}
+
+#[test]
+fn source_looks_in_path_for_a_bare_name() {
+    let dir = scratch_dir("source-path");
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::create_dir_all(dir.join("work")).unwrap();
+    fs::write(dir.join("lib/helpers.sh"), "greeting=from-path\n").unwrap();
+    fs::write(dir.join("work/local.sh"), "greeting=local\n").unwrap();
+    fs::write(dir.join("lib/local.sh"), "greeting=shadowed\n").unwrap();
+    let script = "PATH=../lib; . helpers.sh; echo $greeting; source local.sh; echo $greeting
+        . ./helpers.sh; . missing.sh";
+    let output = run_in(&dir.join("work"), script);
+    assert_eq!(stdout(&output), "from-path\nlocal\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: ./helpers.sh: No such file or directory\n\
+         codecrafters-shell: missing.sh: No such file or directory\n"
+    );
+}
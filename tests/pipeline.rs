@@ -0,0 +1,89 @@
+mod common;
+
+use common::{run, run_in, scratch_dir, stderr, stdout};
+
+#[test]
+fn endless_producer_stops_when_the_reader_is_done() {
+    let output = run("yes | head -n 3");
+    assert_eq!(stdout(&output), "y\ny\ny\n");
+    let output = run("yes | head -c 1000000 | wc -c");
+    assert_eq!(stdout(&output).trim(), "1000000");
+}
+
+#[test]
+fn builtin_output_pipes_into_externals() {
+    let output = run("echo hello | tr a-z A-Z | cat");
+    assert_eq!(stdout(&output), "HELLO\n");
+}
+
+#[test]
+fn bang_negates_a_pipeline() {
+    let output = run("! false; echo $?; ! true; echo $?; ! echo a | grep -q b; echo $?");
+    assert_eq!(stdout(&output), "0\n1\n0\n");
+}
+
+#[test]
+fn negated_status_drives_and_or_lists() {
+    let output = run("! false && echo and; ! true && echo skipped; ! true || echo or");
+    assert_eq!(stdout(&output), "and\nor\n");
+}
+
+#[test]
+fn negated_failure_does_not_trip_set_e() {
+    let output = run("set -e; ! true; echo survived");
+    assert_eq!(stdout(&output), "survived\n");
+}
+
+#[test]
+fn pipeline_stages_do_not_change_the_shell() {
+    let dir = scratch_dir("pipeline-stage");
+    let script = "cd / | true; pwd; x=1 | true; echo \"[$x]\"; exit 5 | true; echo on";
+    let output = run_in(&dir, script);
+    let expected = format!("{}\n[]\non\n", dir.display());
+    assert_eq!(stdout(&output), expected);
+}
+
+#[test]
+fn operators_next_to_quotes() {
+    let output = run(r#"echo a&&echo "b|c";echo 'd;e'||echo no"#);
+    assert_eq!(stdout(&output), "a\nb|c\nd;e\n");
+}
+
+#[test]
+fn pipe_amp_sends_stderr_down_the_pipe() {
+    let output = run("sh -c 'echo out; echo err >&2' |& tr a-z A-Z");
+    assert_eq!(stdout(&output), "OUT\nERR\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn time_p_reports_in_the_posix_format() {
+    let output = run("TIMEFORMAT=ignored; time -p sleep 0.2 | cat; echo $?");
+    assert_eq!(stdout(&output), "0\n");
+    let report = stderr(&output);
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", report);
+    for (line, name) in lines.iter().zip(["real", "user", "sys"]) {
+        let seconds = line
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix(' '));
+        let seconds = seconds.unwrap_or_else(|| panic!("{:?}", line));
+        let (whole, fraction) = seconds.split_once('.').unwrap();
+        assert!(
+            whole.parse::<u64>().is_ok() && fraction.len() == 2,
+            "{:?}",
+            line
+        );
+        assert!(fraction.bytes().all(|b| b.is_ascii_digit()), "{:?}", line);
+    }
+    assert!(lines[0] >= "real 0.20", "{}", lines[0]);
+}
+
+#[test]
+fn time_follows_timeformat_and_keeps_the_status() {
+    let output = run("TIMEFORMAT='took %0R'; time false; echo $?; TIMEFORMAT=; time true");
+    assert_eq!(
+        (stdout(&output).as_str(), stderr(&output).as_str()),
+        ("1\n", "took 0\n")
+    );
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::{run_piped, scratch_dir, shell, stdout};
+
+#[test]
+fn basename_and_trimmed_directories() {
+    let home = scratch_dir("prompt-dirs");
+    let dir = home.join("a/b/c/d");
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut command = shell();
+    command.current_dir(&dir).env("HOME", &home);
+    let input = "PS1='[\\W|\\w] '\nPROMPT_DIRTRIM=2\nPROMPT_DIRTRIM=1\nPROMPT_DIRTRIM=9\ncd /\n";
+    let output = run_piped(command, input);
+    assert_eq!(
+        stdout(&output),
+        "$ [d|~/a/b/c/d] [d|~/.../c/d] [d|~/.../d] [d|~/a/b/c/d] [/|/] "
+    );
+}
+
+#[test]
+fn non_printing_markers_are_not_shown() {
+    let input = "PS1='\\[\\e[32m\\]ok\\[\\e[0m\\] '\nPS1='a\\]b\\[ '\n";
+    let output = run_piped(shell(), input);
+    assert_eq!(stdout(&output), "$ \x1b[32mok\x1b[0m ab ");
+}
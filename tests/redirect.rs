@@ -0,0 +1,200 @@
+mod common;
+
+use common::{run_in, run_script, scratch_dir, stderr, stdout};
+use std::fs;
+
+#[test]
+fn group_output_goes_through_one_redirection() {
+    let dir = scratch_dir("group-redirect");
+    let output = run_in(&dir, "{ echo a; pwd; } > out");
+    assert_eq!(stdout(&output), "");
+    let expected = format!("a\n{}\n", dir.display());
+    assert_eq!(fs::read_to_string(dir.join("out")).unwrap(), expected);
+}
+
+#[test]
+fn groups_share_the_shell_but_subshells_do_not() {
+    let dir = scratch_dir("group-vars");
+    let output = run_in(&dir, "x=1; { x=2; }; echo $x; (x=3); echo $x");
+    assert_eq!(stdout(&output), "2\n2\n");
+}
+
+#[test]
+fn exec_redirections_stay_on_the_shell() {
+    let dir = scratch_dir("exec-redirect");
+    let output = run_in(
+        &dir,
+        "exec > file; echo hi; exec 3> log; echo three >&3; exec 3>&-",
+    );
+    assert_eq!(stdout(&output), "");
+    assert_eq!(fs::read_to_string(dir.join("file")).unwrap(), "hi\n");
+    assert_eq!(fs::read_to_string(dir.join("log")).unwrap(), "three\n");
+}
+
+#[test]
+fn exec_closes_an_fd() {
+    let dir = scratch_dir("exec-close");
+    let output = run_in(&dir, "exec 3> log; exec 3>&-; echo x >&3");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: 3: Bad file descriptor\n"
+    );
+    assert_eq!(fs::read_to_string(dir.join("log")).unwrap(), "");
+}
+
+#[test]
+fn redirections_glued_to_words() {
+    let dir = scratch_dir("glued");
+    fs::write(dir.join("in"), "from in\n").unwrap();
+    let output = run_in(&dir, "echo hi>out; cat<in; ls nope2>err; cat out");
+    assert_eq!(stdout(&output), "from in\nhi\n");
+    // `nope2>err` is the word `nope2` and stdout to `err`, not `2>err`
+    assert_eq!(fs::read_to_string(dir.join("err")).unwrap(), "");
+    assert!(stderr(&output).contains("nope2"));
+}
+
+#[test]
+fn builtin_output_follows_redirections() {
+    let dir = scratch_dir("builtin-redirect");
+    let output = run_in(&dir, "pwd > file; type echo | cat");
+    assert_eq!(stdout(&output), "echo is a shell builtin\n");
+    let expected = format!("{}\n", dir.display());
+    assert_eq!(fs::read_to_string(dir.join("file")).unwrap(), expected);
+}
+
+#[test]
+fn duplication_shorthands() {
+    let dir = scratch_dir("dup-shorthand");
+    fs::write(dir.join("in"), "from-in\n").unwrap();
+    let output = run_in(&dir, "echo error >&2; exec 3< in; read x <&3; echo $x");
+    assert_eq!(stdout(&output), "from-in\n");
+    assert_eq!(stderr(&output), "error\n");
+}
+
+#[test]
+fn and_great_great_appends_both_streams() {
+    let dir = scratch_dir("append-both");
+    let script = "sh -c 'echo out1; echo err1 >&2' &>> log";
+    run_in(&dir, script);
+    run_in(&dir, &script.replace('1', "2"));
+    let log = fs::read_to_string(dir.join("log")).unwrap();
+    assert_eq!(log, "out1\nerr1\nout2\nerr2\n");
+}
+
+#[test]
+fn redirections_alone_create_files_and_run_nothing() {
+    let dir = scratch_dir("redirect-only");
+    fs::write(dir.join("full"), "old\n").unwrap();
+    let output = run_in(
+        &dir,
+        "> full; echo $?; >> fresh; echo $?; < missing; echo $?",
+    );
+    assert_eq!(stdout(&output), "0\n0\n1\n");
+    assert_eq!(fs::read_to_string(dir.join("full")).unwrap(), "");
+    assert_eq!(fs::read_to_string(dir.join("fresh")).unwrap(), "");
+    assert!(stderr(&output).contains("missing"), "{}", stderr(&output));
+}
+
+#[test]
+fn builtins_swap_stdout_and_stderr() {
+    let dir = scratch_dir("builtin-swap");
+    let script = "cd /nonexistent 3>&1 1>/dev/null 2>&3; pwd 3>&1 1>&2 2>&3
+        printf 'to %s\\n' three 3> log >&3; echo error >&2 2>/dev/null";
+    let output = run_in(&dir, script);
+    assert!(stdout(&output).starts_with("codecrafters-shell: cd: /nonexistent"));
+    assert_eq!(stderr(&output), format!("{}\nerror\n", dir.display()));
+    assert_eq!(fs::read_to_string(dir.join("log")).unwrap(), "to three\n");
+}
+
+#[test]
+fn exec_fds_keep_their_output_through_exit() {
+    let dir = scratch_dir("exec-exit");
+    let script = "exec 3> log 4>> log2; trap 'echo bye >&3' EXIT
+        echo one >&3; printf two >&4; exec 4>&-; exec > out; echo to out; exit 3";
+    let output = run_script(&dir, script);
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(fs::read_to_string(dir.join("log")).unwrap(), "one\nbye\n");
+    assert_eq!(fs::read_to_string(dir.join("log2")).unwrap(), "two");
+    assert_eq!(fs::read_to_string(dir.join("out")).unwrap(), "to out\n");
+}
+
+#[test]
+fn redirections_apply_left_to_right() {
+    let dir = scratch_dir("redirect-order");
+    let both = "sh -c 'echo out; echo err >&2'";
+    let script = format!(
+        "{both} > a 2>&1 > b; {both} 2>&1 > c; echo x > d > e
+        {{ echo o; echo e >&2; }} 2> f 1>&2; pwd > g 2>&1 > h"
+    );
+    let output = run_in(&dir, &script);
+    assert_eq!(stdout(&output), "err\n");
+    let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+    let files = ["a", "b", "c", "d", "e", "f", "g", "h"].map(read);
+    let pwd = format!("{}\n", dir.display());
+    let expected = [
+        "err\n",
+        "out\n",
+        "out\n",
+        "",
+        "x\n",
+        "o\ne\n",
+        "",
+        pwd.as_str(),
+    ];
+    assert_eq!(files, expected);
+}
+
+#[test]
+fn redirections_can_come_anywhere_in_a_command() {
+    let dir = scratch_dir("redirect-anywhere");
+    let script = "> a echo hi; echo > b hi there; 2> c x=1 ls nope; < a cat; > d; echo $x";
+    let output = run_in(&dir, script);
+    assert_eq!(stdout(&output), "hi\n\n");
+    let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+    assert_eq!(read("a"), "hi\n");
+    assert_eq!(read("b"), "hi there\n");
+    assert!(read("c").contains("nope"));
+    assert_eq!(read("d"), "");
+}
+
+#[test]
+fn unquoted_here_documents_expand_their_body() {
+    let dir = scratch_dir("here-doc-expand");
+    let script = [
+        "HOME=/home/me; x='a  b*'",
+        "cat <<EOF",
+        "$HOME $x $(echo sub) `echo bq`",
+        "\\$HOME \"q\" a\\b",
+        "EOF",
+        "while read -r line; do echo \"<$line>\"; done <<-END",
+        "\tone",
+        "\t\ttwo",
+        "\tEND",
+    ]
+    .join("\n");
+    let output = run_script(&dir, &script);
+    assert_eq!(stderr(&output), "");
+    assert_eq!(
+        stdout(&output),
+        "/home/me a  b* sub bq\n$HOME \"q\" a\\b\n<one>\n<two>\n"
+    );
+}
+
+#[test]
+fn quoted_here_document_delimiters_keep_the_body_literal() {
+    let dir = scratch_dir("here-doc-literal");
+    let script = [
+        "x=1",
+        "cat <<'EOF'",
+        "$x `echo no` \\$x",
+        "EOF",
+        "cat <<\"A\"; cat <<B",
+        "$x",
+        "A",
+        "$x",
+        "B",
+    ]
+    .join("\n");
+    let output = run_script(&dir, &script);
+    assert_eq!(stdout(&output), "$x `echo no` \\$x\n$x\n1\n");
+}
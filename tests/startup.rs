@@ -0,0 +1,114 @@
+mod common;
+
+use common::{run_piped, run_script, scratch_dir, shell, stderr, stdout};
+
+#[test]
+fn env_file_is_not_run_for_piped_input() {
+    let dir = scratch_dir("env-piped");
+    std::fs::write(dir.join("rc"), "echo from-env\n").unwrap();
+    let mut command = shell();
+    command.env("ENV", dir.join("rc"));
+    let output = run_piped(command, "echo hey\n");
+    assert_eq!(stdout(&output), "$ hey\n$ ");
+}
+
+#[cfg(unix)]
+#[test]
+fn invoked_as_sh_turns_off_brace_expansion() {
+    use std::os::unix::process::CommandExt;
+    let script = "echo {a,b}";
+    let output = shell().arg0("sh").arg("-c").arg(script).output().unwrap();
+    assert_eq!(stdout(&output), "{a,b}\n");
+    let output = shell().arg("-c").arg(script).output().unwrap();
+    assert_eq!(stdout(&output), "a b\n");
+}
+
+#[test]
+fn interactive_comments_toggle_only_affects_the_prompt() {
+    let output = run_piped(
+        shell(),
+        "echo a # b\nshopt -u interactive_comments\necho a # b\n",
+    );
+    assert_eq!(stdout(&output), "$ a\n$ $ a # b\n$ ");
+    let dir = scratch_dir("interactive-comments");
+    let output = run_script(&dir, "shopt -u interactive_comments\necho a # b\n");
+    assert_eq!(stdout(&output), "a\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn errors_start_with_the_name_the_shell_was_run_as() {
+    use std::os::unix::process::CommandExt;
+    let script = "cd /x; nope; echo \"${u:?unset}\"";
+    let output = shell()
+        .arg0("/usr/local/bin/rust-cli")
+        .arg("-c")
+        .arg(script)
+        .output()
+        .unwrap();
+    assert_eq!(
+        stderr(&output),
+        "rust-cli: cd: /x: No such file or directory\n\
+         rust-cli: nope: command not found\n\
+         rust-cli: u: unset\n"
+    );
+}
+
+#[test]
+fn dash_c_takes_the_name_and_arguments_after_the_command() {
+    let args = ["-c", "echo $0 $1 \"$2\" $#", "name", "arg1", "a  b"];
+    let output = shell().args(args).output().unwrap();
+    assert_eq!(stdout(&output), "name arg1 a  b 2\n");
+    let output = shell().arg("-c").output().unwrap();
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: -c: option requires an argument\n"
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn script_arguments_are_positional_parameters() {
+    let dir = scratch_dir("script-args");
+    std::fs::write(dir.join("s.sh"), "echo $0 $1 $#\n").unwrap();
+    let output = shell()
+        .args(["s.sh", "x", "y"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "s.sh x 2\n");
+}
+
+#[test]
+fn norc_and_noprofile_come_before_the_other_arguments() {
+    let args = [
+        "--norc",
+        "--noprofile",
+        "-c",
+        "echo $0 $1",
+        "name",
+        "--norc",
+    ];
+    let output = shell().args(args).output().unwrap();
+    assert_eq!(stdout(&output), "name --norc\n");
+}
+
+#[test]
+fn posix_mode_flips_bash_extensions_off() {
+    let script = "echo {a,b}; echo -n x; echo; readonly r=1; r=2; echo after";
+    let output = shell().arg("-c").arg(script).output().unwrap();
+    assert_eq!(stdout(&output), "a b\nx\nafter\n");
+    let output = shell().args(["--posix", "-c", script]).output().unwrap();
+    assert_eq!(stdout(&output), "{a,b}\n-n x\n\n");
+    assert_eq!(output.status.code(), Some(1));
+    let output = shell()
+        .arg("-c")
+        .arg(format!("set -o posix; {}", script))
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "{a,b}\n-n x\n\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: r: readonly variable\n"
+    );
+}
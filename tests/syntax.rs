@@ -0,0 +1,115 @@
+mod common;
+
+use common::{run, run_piped, run_script, scratch_dir, shell, stderr, stdout};
+
+#[test]
+fn syntax_errors_name_the_token_and_where_it_is() {
+    let cases = [
+        (
+            "echo \"abc",
+            "unexpected EOF while looking for matching `\"'",
+        ),
+        (
+            "if true; then echo x",
+            "syntax error: unexpected end of file",
+        ),
+        (
+            "echo a ;; echo b",
+            "syntax error near unexpected token `;;' (column 8)",
+        ),
+        (
+            "true\necho a; )",
+            "syntax error near unexpected token `)' (line 2, column 9)",
+        ),
+    ];
+    for (script, message) in cases {
+        let output = run(script);
+        assert_eq!(stdout(&output), "", "{}", script);
+        assert_eq!(
+            stderr(&output),
+            format!("codecrafters-shell: {}\n", message)
+        );
+        assert_eq!(output.status.code(), Some(2), "{}", script);
+    }
+}
+
+#[test]
+fn prompt_carries_on_after_a_syntax_error() {
+    let output = run_piped(shell(), "echo a |\n| b\necho after\n");
+    assert_eq!(stdout(&output), "$ > $ after\n$ ");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: syntax error near unexpected token `|' (line 2, column 1)\n"
+    );
+}
+
+#[test]
+fn a_line_megabytes_long_is_read_whole() {
+    let long = "x".repeat(1 << 21);
+    let output = run_piped(shell(), &format!("echo \"{}\" '{}' | wc -c\n", long, long));
+    assert_eq!(stdout(&output).trim_end_matches("$ ").trim(), "$ 4194306");
+}
+
+#[test]
+fn nesting_too_deep_is_an_error_not_a_crash() {
+    let script = format!(
+        "{}echo hi{}\necho after\n",
+        "(".repeat(100_000),
+        ")".repeat(100_000)
+    );
+    let output = run_script(&scratch_dir("deep-nesting"), &script);
+    assert_eq!(stdout(&output), "after\n");
+    assert_eq!(
+        stderr(&output),
+        "codecrafters-shell: syntax error: commands nested too deeply (column 501)\n"
+    );
+}
+
+#[test]
+fn hash_starts_a_comment_only_at_a_word() {
+    let output = run("#whole line\necho foo#bar\necho foo #bar\necho $#x '#'y;#z");
+    assert_eq!(stdout(&output), "foo#bar\nfoo\n0x #y\n");
+}
+
+/// The AST `--parse-only` prints for `script`, without its whitespace.
+fn parsed(dir: &std::path::Path, script: &str) -> String {
+    let output = shell()
+        .args(["--parse-only", "-c", script])
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    stdout(&output).split_whitespace().collect()
+}
+
+#[test]
+fn parse_only_prints_the_tree_instead_of_running() {
+    let dir = scratch_dir("parse-only");
+    let ast = parsed(&dir, "ls -l | wc > out 2>&1");
+    let ls = "Simple(SimpleCommand{line:1,assignments:[],words:[\"ls\",\"-l\",],redirects:[],},)";
+    let redirects = "redirects:[Redirect{fd:1,op:Out,target:\"out\",body:None,},\
+                     Redirect{fd:2,op:DupOut,target:\"1\",body:None,},]";
+    assert!(
+        ast.contains(&format!("negated:false,commands:[{}", ls)),
+        "{}",
+        ast
+    );
+    assert!(ast.contains(redirects), "{}", ast);
+    assert!(!dir.join("out").exists());
+    let ast = parsed(&dir, "if true\nthen echo y; fi &");
+    assert!(ast.contains("Compound(If{branches:[(List{"), "{}", ast);
+    assert!(
+        ast.contains("line:2,assignments:[],words:[\"echo\",\"y\",]"),
+        "{}",
+        ast
+    );
+    assert!(ast.ends_with("background:true,},],}"), "{}", ast);
+    let ast = parsed(&dir, "! a=1 b && c");
+    assert!(ast.contains("negated:true"), "{}", ast);
+    assert!(
+        ast.contains("assignments:[Assignment{name:\"a\",subscript:None"),
+        "{}",
+        ast
+    );
+    assert!(ast.contains("rest:[(And,Pipeline{"), "{}", ast);
+}
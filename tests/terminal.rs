@@ -0,0 +1,241 @@
+//! Tests that need the shell on a terminal, run on a pseudo-terminal.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{scratch_dir, shell};
+use std::ffi::CStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::fd::FromRawFd;
+use std::os::raw::{c_char, c_int, c_ulong};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+const O_RDWR: c_int = 2;
+const O_NOCTTY: c_int = 0o400;
+const TIOCSCTTY: c_ulong = 0x540e;
+
+extern "C" {
+    fn posix_openpt(flags: c_int) -> c_int;
+    fn grantpt(fd: c_int) -> c_int;
+    fn unlockpt(fd: c_int) -> c_int;
+    fn ptsname(fd: c_int) -> *mut c_char;
+    fn setsid() -> c_int;
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+/// Runs the shell on a new terminal, typing each of `keys` a little after
+/// the last, and returns everything it wrote. The last keys should make
+/// it exit.
+fn type_keys(keys: &[&str]) -> String {
+    let mut command = shell();
+    command.env_remove("ENV");
+    type_keys_into(command, keys)
+}
+
+/// [`type_keys`] for a shell `command` set up by the caller.
+fn type_keys_into(mut command: Command, keys: &[&str]) -> String {
+    let (mut master, slave) = unsafe {
+        let fd = posix_openpt(O_RDWR | O_NOCTTY);
+        assert!(fd >= 0 && grantpt(fd) == 0 && unlockpt(fd) == 0);
+        let name = CStr::from_ptr(ptsname(fd)).to_str().unwrap().to_string();
+        (
+            File::from_raw_fd(fd),
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(name)
+                .unwrap(),
+        )
+    };
+    command
+        .stdin(slave.try_clone().unwrap())
+        .stdout(slave.try_clone().unwrap())
+        .stderr(slave)
+        .env("PS1", "$ ")
+        .env_remove("HISTFILE");
+    unsafe {
+        command.pre_exec(|| {
+            // a session of its own, with the terminal as its controlling one
+            setsid();
+            ioctl(0, TIOCSCTTY, 0);
+            Ok(())
+        });
+    }
+    // the parent's copies of the terminal go with `command`
+    let mut child = command.spawn().unwrap();
+    drop(command);
+    let mut reader = master.try_clone().unwrap();
+    let output = thread::spawn(move || {
+        let mut output = Vec::new();
+        let mut buf = [0; 4096];
+        // reading fails with EIO once the shell has closed the terminal
+        while let Ok(n @ 1..) = reader.read(&mut buf) {
+            output.extend_from_slice(&buf[..n]);
+        }
+        output
+    });
+    for keys in keys {
+        thread::sleep(Duration::from_millis(200));
+        master.write_all(keys.as_bytes()).unwrap();
+    }
+    child.wait().unwrap();
+    String::from_utf8_lossy(&output.join().unwrap()).replace("\r\n", "\n")
+}
+
+#[test]
+fn interrupt_drops_an_unfinished_command() {
+    let output = type_keys(&["echo 'abc\r", "def", "\x03", "echo \"[$?]\"\r", "exit\r"]);
+    // back at the first prompt, with the quoted lines never run
+    assert!(output.contains("^C\n$ "), "{:?}", output);
+    assert!(output.contains("\n[130]\n"), "{:?}", output);
+    assert!(!output.contains("abc\ndef"), "{:?}", output);
+}
+
+#[test]
+fn interrupt_stops_a_command_and_the_rest_of_its_line() {
+    let output = type_keys(&[
+        "sleep 5; echo next\r",
+        "\x03",
+        "echo \"[$?]\"; while :; do :; done; echo loop\r",
+        "\x03",
+        "echo \"[$?]\"; exit\r",
+    ]);
+    assert_eq!(output.matches("\n[130]\n").count(), 2, "{:?}", output);
+    assert!(
+        !output.contains("\nnext\n") && !output.contains("\nloop\n"),
+        "{:?}",
+        output
+    );
+}
+
+#[test]
+fn interrupt_kills_a_command_substitution() {
+    let dir = scratch_dir("interrupt-substitution");
+    let pid_file = dir.join("pid");
+    let line = format!(
+        "echo \"<$(sh -c 'echo $$ > {}; exec sleep 30'; echo inner)>\"; echo after\r",
+        pid_file.display()
+    );
+    let output = type_keys(&[&line, "\x03", "echo \"[$?]\"; exit\r"]);
+    assert!(output.contains("\n[130]\n"), "{:?}", output);
+    assert!(
+        !output.contains("\n<") && !output.contains("\nafter\n"),
+        "{:?}",
+        output
+    );
+    // the sleep is gone, or at most a zombie nothing has reaped yet
+    let pid = fs::read_to_string(&pid_file).unwrap();
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap_or_default();
+    let state = stat
+        .rsplit(") ")
+        .next()
+        .and_then(|rest| rest.chars().next());
+    assert!(matches!(state, None | Some('Z')), "{:?}", stat);
+}
+
+#[test]
+fn completion_keeps_to_the_quoting_of_the_word() {
+    let dir = scratch_dir("complete-quoted");
+    fs::write(dir.join("my file.txt"), "contents\n").unwrap();
+    let mut command = shell();
+    command.current_dir(&dir).env_remove("ENV");
+    let output = type_keys_into(command, &["cat \"my f\t", "\r", "cat my\t", "\r", "exit\r"]);
+    assert!(
+        output.contains("cat \"my file.txt\" \x1b[K\ncontents\n"),
+        "{:?}",
+        output
+    );
+    assert!(
+        output.contains("cat my\\ file.txt \x1b[K\ncontents\n"),
+        "{:?}",
+        output
+    );
+}
+
+#[test]
+fn exit_warns_about_jobs_until_repeated() {
+    let output = type_keys(&[
+        "sleep 5 &\r",
+        "exit\r",
+        "echo \"[$?]\"\r",
+        "exit\r",
+        "exit\r",
+    ]);
+    assert_eq!(
+        output.matches("There are running jobs.\n").count(),
+        2,
+        "{:?}",
+        output
+    );
+    assert!(output.contains("\n[1]\n"), "{:?}", output);
+    // the empty line gives the shell a prompt to notice the stop at
+    let stop = "sleep 5 & p=$! ; kill -STOP $p; echo \"<$p>\"\r";
+    let output = type_keys(&[stop, "\r", "exit\r", "exit\r"]);
+    assert_eq!(
+        output.matches("There are stopped jobs.\n").count(),
+        1,
+        "{:?}",
+        output
+    );
+    // nothing would wake the stopped sleep up otherwise
+    let pid = output
+        .split("\n<")
+        .nth(1)
+        .and_then(|rest| rest.split('>').next())
+        .unwrap();
+    Command::new("kill").args(["-KILL", pid]).status().unwrap();
+}
+
+#[test]
+fn set_o_vi_and_emacs_switch_the_keys() {
+    let output = type_keys(&[
+        "set -o vi\r",
+        "echo helo\x1b",
+        "il\r",
+        "set -o emacs; echo \"[$?]\"\r",
+        "echo xy\x02Z\x1b\r",
+        "exit\r",
+    ]);
+    assert!(output.contains("\nhello\n"), "{:?}", output);
+    assert!(
+        output.contains("\n[0]\n") && output.contains("\nxZy\n"),
+        "{:?}",
+        output
+    );
+}
+
+#[test]
+fn norc_skips_the_env_file() {
+    let dir = scratch_dir("norc");
+    fs::write(dir.join("rc"), "alias hi='echo from rc'\n").unwrap();
+    let run = |options: &[&str]| {
+        let mut command = shell();
+        command.args(options).env("ENV", dir.join("rc"));
+        type_keys_into(command, &["type -t hi || echo none\r", "exit\r"])
+    };
+    let output = run(&[]);
+    assert!(output.contains("\nalias\n"), "{:?}", output);
+    let output = run(&["--norc"]);
+    assert!(output.contains("\nnone\n"), "{:?}", output);
+    let output = run(&["--noprofile", "--norc"]);
+    assert!(output.contains("\nnone\n"), "{:?}", output);
+}
+
+#[test]
+fn columns_and_lines_follow_a_resize() {
+    let output = type_keys(&[
+        "stty cols 90 rows 20\r",
+        "echo \"[$COLUMNS $LINES]\"; COLUMNS=7\r",
+        "echo \"[$COLUMNS]\"; stty cols 100 rows 30\r",
+        "echo \"[$COLUMNS $LINES]\"; exit\r",
+    ]);
+    assert!(output.contains("\n[90 20]\n"), "{:?}", output);
+    // an assignment stands until the size really changes
+    assert!(output.contains("\n[7]\n"), "{:?}", output);
+    assert!(output.contains("\n[100 30]\n"), "{:?}", output);
+}
@@ -0,0 +1,81 @@
+#![cfg(windows)]
+
+mod common;
+
+use common::{scratch_dir, shell, stdout};
+use std::fs;
+
+#[test]
+fn batch_file_runs_by_its_bare_name() {
+    let dir = scratch_dir("bare-name");
+    fs::write(dir.join("hello.bat"), "@echo hi\r\n").unwrap();
+    let output = shell()
+        .arg("-c")
+        .arg("hello")
+        .env("PATH", &dir)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output).trim_end(), "hi");
+}
+
+#[test]
+fn pathext_decides_which_extensions_run() {
+    let dir = scratch_dir("pathext");
+    fs::write(dir.join("hello.bat"), "@echo bat\r\n").unwrap();
+    fs::write(dir.join("hello.cmd"), "@echo cmd\r\n").unwrap();
+    let run = |pathext: &str| {
+        let mut command = shell();
+        command
+            .arg("-c")
+            .arg("hello")
+            .env("PATH", &dir)
+            .env("PATHEXT", pathext);
+        stdout(&command.output().unwrap()).trim_end().to_string()
+    };
+    assert_eq!(run(".CMD;.BAT"), "cmd");
+    assert_eq!(run(".BAT"), "bat");
+    assert_eq!(run(".EXE"), "");
+}
+
+#[test]
+fn pathext_assigned_in_the_shell_changes_lookups() {
+    let dir = scratch_dir("pathext-assigned");
+    fs::write(dir.join("hello.bat"), "@echo bat\r\n").unwrap();
+    fs::write(dir.join("hello.cmd"), "@echo cmd\r\n").unwrap();
+    // the first lookup is remembered, and must be forgotten once PATHEXT changes
+    let script = "hello; PATHEXT=.CMD; hello; PATHEXT=.EXE hello; type -t hello";
+    let output = shell()
+        .arg("-c")
+        .arg(script)
+        .env("PATH", &dir)
+        .env("PATHEXT", ".BAT")
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output).replace("\r\n", "\n"), "bat\ncmd\nfile\n");
+}
+
+#[test]
+fn tilde_is_the_user_profile_without_home() {
+    let dir = scratch_dir("profile");
+    let output = shell()
+        .arg("-c")
+        .arg("echo ~")
+        .env_remove("HOME")
+        .env("USERPROFILE", &dir)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), format!("{}\n", dir.display()));
+}
+
+#[test]
+fn command_names_resolve_whatever_their_case() {
+    let dir = scratch_dir("mixed-case");
+    fs::write(dir.join("Hello.bat"), "@echo hi\r\n").unwrap();
+    let output = shell()
+        .arg("-c")
+        .arg("HELLO; type -t hELLo")
+        .env("PATH", &dir)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output).replace("\r\n", "\n"), "hi\nfile\n");
+}